@@ -0,0 +1,51 @@
+//! Drives the title of a window from a future running on the event loop.
+
+#[cfg(any(x11_platform, wayland_platform))]
+fn main() {
+    use std::{rc::Rc, time::Duration};
+
+    use simple_logger::SimpleLogger;
+    use winit::{
+        event::{Event, WindowEvent},
+        event_loop::EventLoop,
+        platform::unix::EventLoopWindowTargetExtUnix,
+        window::WindowBuilder,
+    };
+
+    SimpleLogger::new().init().unwrap();
+    let event_loop = EventLoop::new();
+
+    let window = Rc::new(
+        WindowBuilder::new()
+            .with_title("Counting...")
+            .build(&event_loop)
+            .unwrap(),
+    );
+
+    let window_ = window.clone();
+    event_loop.spawn_local(async move {
+        for seconds in 1.. {
+            glib::timeout_future(Duration::from_secs(1)).await;
+            window_.set_title(&format!("{seconds} seconds since the start"));
+        }
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_wait();
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            window_id,
+        } = event
+        {
+            if window_id == window.id() {
+                control_flow.set_exit();
+            }
+        }
+    });
+}
+
+#[cfg(not(any(x11_platform, wayland_platform)))]
+fn main() {
+    panic!("This example is supported only on X11 and Wayland.");
+}
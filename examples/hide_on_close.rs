@@ -0,0 +1,70 @@
+//! Hides the window when it is closed, Ctrl+Alt+H shows or hides it from anywhere on X11.
+
+#[cfg(any(x11_platform, wayland_platform))]
+fn main() {
+    use std::rc::Rc;
+
+    use simple_logger::SimpleLogger;
+    use winit::{
+        event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
+        event_loop::EventLoop,
+        platform::unix::{EventLoopWindowTargetExtUnix, WindowExtUnix},
+        window::WindowBuilder,
+    };
+
+    // The evdev code of H, X11 keycodes are offset by 8.
+    const H_SCANCODE: u32 = 35 + 8;
+
+    SimpleLogger::new().init().unwrap();
+    let event_loop = EventLoop::new();
+
+    let window = Rc::new(
+        WindowBuilder::new()
+            .with_title("Close me, then press Ctrl+Alt+H. Escape quits.")
+            .build(&event_loop)
+            .unwrap(),
+    );
+    window.set_hide_on_close(true);
+
+    match event_loop
+        .register_global_shortcut(ModifiersState::CTRL | ModifiersState::ALT, H_SCANCODE)
+    {
+        Ok(_) => {
+            let window = window.clone();
+            event_loop.connect_global_shortcut(move |_| {
+                if window.is_visible() == Some(true) {
+                    window.set_visible(false);
+                } else {
+                    window.focus_window();
+                }
+            });
+        }
+        Err(e) => println!("The global shortcut is unavailable, the window stays hidden: {e}"),
+    }
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_wait();
+
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } = event
+        {
+            control_flow.set_exit();
+        }
+    });
+}
+
+#[cfg(not(any(x11_platform, wayland_platform)))]
+fn main() {
+    panic!("This example is supported only on X11 and Wayland.");
+}
@@ -0,0 +1,67 @@
+//! Measures the latency from a mouse click to the redraw it triggers.
+
+#[cfg(any(x11_platform, wayland_platform))]
+fn main() {
+    use std::{cell::Cell, rc::Rc, time::Instant};
+
+    use simple_logger::SimpleLogger;
+    use winit::{
+        event::{ElementState, Event, WindowEvent},
+        event_loop::EventLoop,
+        platform::unix::{RawInputEvent, WindowExtUnix},
+        window::WindowBuilder,
+    };
+
+    SimpleLogger::new().init().unwrap();
+    let event_loop = EventLoop::new();
+
+    let window = WindowBuilder::new()
+        .with_title("Click to measure the input latency")
+        .build(&event_loop)
+        .unwrap();
+
+    // Time of the last click, as reported by the display server.
+    let last_click = Rc::new(Cell::new(None));
+    let last_click_ = last_click.clone();
+    window.connect_raw_event_timestamps(move |timestamp| {
+        if timestamp.event == RawInputEvent::MouseInput {
+            last_click_.set(Some(timestamp));
+        }
+    });
+
+    let mut clicked_at: Option<Instant> = None;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_wait();
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => control_flow.set_exit(),
+                WindowEvent::MouseInput {
+                    device_id,
+                    state: ElementState::Pressed,
+                    ..
+                } => {
+                    clicked_at = last_click
+                        .get()
+                        .filter(|timestamp| timestamp.device_id == device_id)
+                        .map(|timestamp| timestamp.instant);
+                    window.request_redraw();
+                }
+                _ => (),
+            },
+            Event::RedrawRequested(_) => {
+                // Rendering the frame would happen here.
+                if let Some(clicked_at) = clicked_at.take() {
+                    println!("Click to render: {:?}", clicked_at.elapsed());
+                }
+            }
+            _ => (),
+        }
+    });
+}
+
+#[cfg(not(any(x11_platform, wayland_platform)))]
+fn main() {
+    panic!("This example is supported only on X11 and Wayland.");
+}
@@ -0,0 +1,58 @@
+//! A bar anchored to the top edge of the monitor, which other windows leave room for.
+//!
+//! Run with `cargo run --example layer_shell_bar --features layer-shell` on a Wayland compositor
+//! supporting the layer shell.
+
+#[cfg(all(feature = "layer-shell", wayland_platform))]
+fn main() {
+    use simple_logger::SimpleLogger;
+    use winit::{
+        dpi::LogicalSize,
+        event::{Event, WindowEvent},
+        event_loop::EventLoop,
+        platform::unix::{
+            Anchors, EventLoopWindowTargetExtUnix, KeyboardInteractivity, Layer, LayerShellConfig,
+            WindowBuilderExtUnix,
+        },
+        window::WindowBuilder,
+    };
+
+    const BAR_HEIGHT: u32 = 32;
+
+    SimpleLogger::new().init().unwrap();
+    let event_loop = EventLoop::new();
+
+    if !event_loop.wayland_capabilities().layer_shell {
+        println!("The compositor doesn't support the layer shell, the bar is a regular window");
+    }
+
+    // Anchored to the left and right edges too, the bar is stretched over the whole width.
+    let _window = WindowBuilder::new()
+        .with_title("A bar")
+        .with_inner_size(LogicalSize::new(1, BAR_HEIGHT))
+        .with_layer_shell(LayerShellConfig {
+            layer: Layer::Top,
+            anchors: Anchors::TOP | Anchors::LEFT | Anchors::RIGHT,
+            exclusive_zone: BAR_HEIGHT as i32,
+            keyboard_interactivity: KeyboardInteractivity::None,
+        })
+        .build(&event_loop)
+        .unwrap();
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_wait();
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            control_flow.set_exit();
+        }
+    });
+}
+
+#[cfg(not(all(feature = "layer-shell", wayland_platform)))]
+fn main() {
+    panic!("This example needs the `layer-shell` feature and is supported only on Wayland.");
+}
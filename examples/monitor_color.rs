@@ -0,0 +1,29 @@
+//! Prints the identification of every monitor, as needed to look up its ICC profile.
+
+#[cfg(any(x11_platform, wayland_platform))]
+fn main() {
+    use simple_logger::SimpleLogger;
+    use winit::{event_loop::EventLoop, platform::unix::MonitorHandleExtUnix};
+
+    SimpleLogger::new().init().unwrap();
+    let event_loop = EventLoop::new();
+
+    println!(
+        "{:<12} {:<14} {:<24} {:<8}",
+        "Connector", "Manufacturer", "Model", "Built-in"
+    );
+    for monitor in event_loop.available_monitors() {
+        println!(
+            "{:<12} {:<14} {:<24} {:<8}",
+            monitor.connector().unwrap_or_else(|| "-".into()),
+            monitor.manufacturer().unwrap_or_else(|| "-".into()),
+            monitor.model().unwrap_or_else(|| "-".into()),
+            monitor.is_builtin(),
+        );
+    }
+}
+
+#[cfg(not(any(x11_platform, wayland_platform)))]
+fn main() {
+    panic!("This example is supported only on X11 and Wayland.");
+}
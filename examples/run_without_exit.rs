@@ -0,0 +1,57 @@
+//! Embeds the event loop twice in one process and keeps running after it is torn down.
+
+#[cfg(any(x11_platform, wayland_platform))]
+fn main() {
+    use simple_logger::SimpleLogger;
+    use winit::{
+        event::{Event, WindowEvent},
+        event_loop::EventLoop,
+        platform::{run_return::EventLoopExtRunReturn, unix::EventLoopExtUnix},
+        window::WindowBuilder,
+    };
+
+    SimpleLogger::new().init().unwrap();
+    let mut event_loop = EventLoop::new();
+
+    let first = WindowBuilder::new()
+        .with_title("First run, close me")
+        .build(&event_loop)
+        .unwrap();
+
+    let first_code = event_loop.run_return(|event, _, control_flow| {
+        control_flow.set_wait();
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            control_flow.set_exit_with_code(1);
+        }
+    });
+    first.set_visible(false);
+
+    let _second = WindowBuilder::new()
+        .with_title("Second run, close me")
+        .build(&event_loop)
+        .unwrap();
+
+    let second_code = event_loop.run_without_exit(|event, _, control_flow| {
+        control_flow.set_wait();
+
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            control_flow.set_exit_with_code(2);
+        }
+    });
+
+    println!("The runs exited with {first_code} and {second_code}, the process is still running");
+}
+
+#[cfg(not(any(x11_platform, wayland_platform)))]
+fn main() {
+    panic!("This example is supported only on X11 and Wayland.");
+}
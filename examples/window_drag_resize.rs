@@ -1,6 +1,8 @@
 //! Demonstrates capability to create in-app draggable regions for client-side decoration support.
 
 use simple_logger::SimpleLogger;
+#[cfg(any(x11_platform, wayland_platform))]
+use winit::platform::unix::{DefaultHandlers, WindowBuilderExtUnix};
 use winit::{
     event::{
         ElementState, Event, KeyboardInput, MouseButton, StartCause, VirtualKeyCode, WindowEvent,
@@ -15,12 +17,15 @@ fn main() {
     SimpleLogger::new().init().unwrap();
     let event_loop = EventLoop::new();
 
-    let window = WindowBuilder::new()
+    let builder = WindowBuilder::new()
         .with_inner_size(winit::dpi::LogicalSize::new(600.0, 400.0))
         .with_min_inner_size(winit::dpi::LogicalSize::new(400.0, 200.0))
-        .with_decorations(false)
-        .build(&event_loop)
-        .unwrap();
+        .with_decorations(false);
+    // The edges are handled below, so opt out of the built-in resize border.
+    #[cfg(any(x11_platform, wayland_platform))]
+    let builder =
+        builder.with_default_handlers(DefaultHandlers::all() - DefaultHandlers::BORDERLESS_RESIZE);
+    let window = builder.build(&event_loop).unwrap();
 
     let mut border = false;
     let mut cursor_location = None;
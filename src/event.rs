@@ -337,6 +337,12 @@ pub enum WindowEvent<'a> {
     Moved(PhysicalPosition<i32>),
 
     /// The window has been requested to close.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **X11 / Wayland:** The window stays open, call
+    ///   [`WindowExtUnix::close`](crate::platform::unix::WindowExtUnix::close) or drop the event
+    ///   loop to close it. This way the application can ask to save changes first.
     CloseRequested,
 
     /// The window has been destroyed.
@@ -446,7 +452,7 @@ pub enum WindowEvent<'a> {
     ///
     /// ## Platform-specific
     ///
-    /// - Only available on **macOS**.
+    /// - Only available on **macOS**, **X11** and **Wayland**.
     TouchpadMagnify {
         device_id: DeviceId,
         delta: f64,
@@ -479,7 +485,7 @@ pub enum WindowEvent<'a> {
     ///
     /// ## Platform-specific
     ///
-    /// - Only available on **macOS**.
+    /// - Only available on **macOS**, **X11** and **Wayland**.
     TouchpadRotate {
         device_id: DeviceId,
         delta: f32,
@@ -536,7 +542,7 @@ pub enum WindowEvent<'a> {
     ///
     /// ## Platform-specific
     ///
-    /// - **iOS / Android / X11 / Wayland / Orbital:** Unsupported.
+    /// - **iOS / Android / Orbital:** Unsupported.
     ThemeChanged(Theme),
 
     /// The window has been occluded (completely hidden from view).
@@ -545,7 +551,9 @@ pub enum WindowEvent<'a> {
     /// minimised, set invisible, or fully occluded by another window.
     ///
     /// Platform-specific behavior:
-    /// - **iOS / Android / Web / Wayland / Windows / Orbital:** Unsupported.
+    /// - **Wayland:** Only reported when the window is hidden or shown, compositors don't tell
+    ///   about minimizing or obscuring.
+    /// - **iOS / Android / Web / Windows / Orbital:** Unsupported.
     Occluded(bool),
 }
 
@@ -798,6 +806,11 @@ impl DeviceId {
 /// may not match.
 ///
 /// Note that these events are delivered regardless of input focus.
+///
+/// ## Platform-specific
+///
+/// - **X11:** Reported from the XInput2 raw events of the master devices, so motion is unaccelerated.
+/// - **Wayland:** Only `Added` and `Removed` are reported.
 #[derive(Clone, Debug, PartialEq)]
 pub enum DeviceEvent {
     Added,
@@ -9,10 +9,10 @@
 //! handle events.
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{error, fmt};
 
 use instant::{Duration, Instant};
-use once_cell::sync::OnceCell;
 use raw_window_handle::{HasRawDisplayHandle, RawDisplayHandle};
 
 use crate::{event::Event, monitor::MonitorHandle, platform_impl};
@@ -48,6 +48,12 @@ pub struct EventLoopWindowTarget<T: 'static> {
     pub(crate) _marker: PhantomData<*mut ()>, // Not Send nor Sync
 }
 
+/// Whether an [`EventLoop`] was created.
+///
+/// On X11 and Wayland it is reset once the [`EventLoop`] is dropped, so another one can be
+/// created afterwards.
+pub(crate) static EVENT_LOOP_CREATED: AtomicBool = AtomicBool::new(false);
+
 /// Object that allows building the event loop.
 ///
 /// This is used to make specifying options that affect the whole application
@@ -97,6 +103,8 @@ impl<T> EventLoopBuilder<T> {
     ///   `WINIT_UNIX_BACKEND`. Legal values are `x11` and `wayland`.
     ///   If it is not set, winit will try to connect to a Wayland connection, and if that fails,
     ///   will fall back on X11. If this variable is set with any other value, winit will panic.
+    /// - **X11 / Wayland:** Another event loop can be created once the previous one is dropped,
+    ///   which destroys its windows.
     /// - **Android:** Must be configured with an `AndroidApp` from `android_main()` by calling
     ///     [`.with_android_app(app)`] before calling `.build()`.
     ///
@@ -111,8 +119,7 @@ impl<T> EventLoopBuilder<T> {
     )]
     #[inline]
     pub fn build(&mut self) -> EventLoop<T> {
-        static EVENT_LOOP_CREATED: OnceCell<()> = OnceCell::new();
-        if EVENT_LOOP_CREATED.set(()).is_err() {
+        if EVENT_LOOP_CREATED.swap(true, Ordering::AcqRel) {
             panic!("Creating EventLoop multiple times is not supported.");
         }
         // Certain platforms accept a mutable reference in their API.
@@ -359,9 +366,13 @@ impl<T> EventLoopWindowTarget<T> {
     ///
     /// ## Platform-specific
     ///
+    /// - **X11:** Filters the raw input events, [`DeviceEvent::Added`] and
+    ///   [`DeviceEvent::Removed`] are always reported.
     /// - **Wayland / macOS / iOS / Android / Web / Orbital:** Unsupported.
     ///
     /// [`DeviceEvent`]: crate::event::DeviceEvent
+    /// [`DeviceEvent::Added`]: crate::event::DeviceEvent::Added
+    /// [`DeviceEvent::Removed`]: crate::event::DeviceEvent::Removed
     pub fn set_device_event_filter(&self, _filter: DeviceEventFilter) {
         #[cfg(any(x11_platform, wayland_platform, windows))]
         self.p.set_device_event_filter(_filter);
@@ -377,7 +388,7 @@ unsafe impl<T> HasRawDisplayHandle for EventLoopWindowTarget<T> {
 
 /// Used to send custom events to [`EventLoop`].
 pub struct EventLoopProxy<T: 'static> {
-    event_loop_proxy: platform_impl::EventLoopProxy<T>,
+    pub(crate) event_loop_proxy: platform_impl::EventLoopProxy<T>,
 }
 
 impl<T: 'static> Clone for EventLoopProxy<T> {
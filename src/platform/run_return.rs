@@ -27,7 +27,9 @@ pub trait EventLoopExtRunReturn {
     /// ## Platform-specific
     ///
     /// - **X11 / Wayland:** This function returns `1` upon disconnection from
-    ///   the display server.
+    ///   the display server. Each call starts with a single `NewEvents(StartCause::Init)`, the
+    ///   events that were queued but not delivered when the previous call exited follow right
+    ///   after. Windows created in a previous call keep working.
     fn run_return<F>(&mut self, event_handler: F) -> i32
     where
         F: FnMut(
@@ -1,9 +1,20 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
 use glib::IsA;
 
 use crate::{
-    event_loop::EventLoopWindowTarget,
+    error::{ExternalError, NotSupportedError},
+    event::{DeviceId, Event, ModifiersState, ScanCode},
+    event_loop::{
+        ControlFlow, EventLoop, EventLoopBuilder, EventLoopClosed, EventLoopProxy,
+        EventLoopWindowTarget,
+    },
+    monitor::MonitorHandle,
     platform_impl::ApplicationName,
-    window::{Window, WindowBuilder},
+    window::{UserAttentionType, Window, WindowBuilder, WindowId},
 };
 
 pub use crate::platform_impl::hit_test;
@@ -19,6 +30,123 @@ pub trait WindowExtUnix {
 
     /// Whether to show the window icon in the taskbar or not.
     fn set_skip_taskbar(&self, skip: bool);
+
+    /// Whether the window is left out of the taskbar and pager, see
+    /// [`set_skip_taskbar`](Self::set_skip_taskbar).
+    fn is_skip_taskbar(&self) -> bool;
+
+    /// Whether the window blocks the input of the other windows of the application, see
+    /// [`WindowBuilderExtUnix::with_modal`].
+    fn set_modal(&self, modal: bool);
+
+    /// Whether the window is shown on all workspaces, see
+    /// [`Window::set_visible_on_all_workspaces`].
+    ///
+    /// This follows the window manager, which may pin or unpin the window on its own.
+    fn is_visible_on_all_workspaces(&self) -> bool;
+
+    /// Whether the window takes the input focus, which is not the case if it was built with
+    /// [`WindowBuilder::with_active`]`(false)`.
+    ///
+    /// [`Window::focus_window`] only raises windows which aren't focusable.
+    fn is_focusable(&self) -> bool;
+
+    /// Ring the bell of the window, e.g. for the BEL character of a terminal.
+    ///
+    /// This may be a no-op, depending on the compositor and the user's settings.
+    fn beep(&self);
+
+    /// Close the window for good, e.g. once the user confirmed a
+    /// [`WindowEvent::CloseRequested`](crate::event::WindowEvent::CloseRequested).
+    ///
+    /// The window is destroyed and
+    /// [`WindowEvent::Destroyed`](crate::event::WindowEvent::Destroyed) is emitted, later
+    /// requests of the `Window` fail.
+    fn close(&self);
+
+    /// Whether closing the window hides it instead, e.g. for applications living in the tray.
+    ///
+    /// A hidden window emits no
+    /// [`WindowEvent::CloseRequested`](crate::event::WindowEvent::CloseRequested). It keeps its
+    /// size, position and fullscreen state, and gets them back once shown again with
+    /// [`Window::set_visible`] or [`Window::focus_window`].
+    ///
+    /// This needs [`DefaultHandlers::CLOSE`]. Default is `false`.
+    fn set_hide_on_close(&self, hide: bool);
+
+    /// Sets the size of the cursor icons in logical pixels, `None` follows the size of the
+    /// cursor theme.
+    ///
+    /// The cursor is rendered for the scale factor of the window either way, so it matches the
+    /// cursor of GTK applications on HiDPI monitors. The images are loaded through libXcursor,
+    /// without it the size of the theme is used.
+    fn set_cursor_size_override(&self, size: Option<u32>);
+
+    /// Resize the window to the natural size of its content.
+    ///
+    /// The new size is reported through the usual [`WindowEvent::Resized`](crate::event::WindowEvent::Resized).
+    fn resize_to_content(&self);
+
+    /// Request the user's attention like [`Window::request_user_attention`], but only for
+    /// `duration`.
+    ///
+    /// Some desktops keep flashing the taskbar entry for as long as the urgency hint is set, so it
+    /// is cleared once `duration` elapsed, or earlier when the window gets focused. Both kinds of
+    /// [`UserAttentionType`] set the same urgency hint.
+    fn request_user_attention_for(&self, duration: Duration, request_type: UserAttentionType);
+
+    /// Ask the compositor to stop compositing the window, e.g. so a fullscreen game presents
+    /// immediately.
+    ///
+    /// This sets `_NET_WM_BYPASS_COMPOSITOR` on X11, compositors usually only honor it for
+    /// fullscreen windows. It is a no-op on Wayland.
+    fn set_compositor_bypass_hint(&self, bypass: bool);
+
+    /// Whether the compositor bypass hint is set on the window.
+    fn compositor_bypass_hint(&self) -> bool;
+
+    /// Whether the window is composited right now.
+    ///
+    /// This is the case if the screen has a compositing manager, unless the window is fullscreen
+    /// and has the [compositor bypass hint](WindowExtUnix::set_compositor_bypass_hint) set.
+    /// Without compositing, transparency doesn't render and vsync isn't driven by the compositor.
+    fn is_composited(&self) -> bool;
+
+    /// Register a callback invoked with the new state whenever [`WindowExtUnix::is_composited`]
+    /// changes, due to the compositing manager of the screen or the fullscreen state.
+    fn connect_composited_changed<F: Fn(bool) + 'static>(&self, f: F);
+
+    /// Allow the compositor to present the window immediately, which may tear.
+    ///
+    /// This sends the presentation hint of `wp_tearing_control_v1` on Wayland, whenever the window
+    /// is shown. Returns [`NotSupportedError`] if the compositor doesn't offer the protocol, and on
+    /// X11, which has no such hint, see [`WindowExtUnix::set_compositor_bypass_hint`] instead.
+    fn set_allow_tearing(&self, allow: bool) -> Result<(), NotSupportedError>;
+
+    /// Whether the compositor was told that tearing is fine for the window.
+    ///
+    /// This is `false` until the window is shown.
+    fn allows_tearing(&self) -> bool;
+
+    /// Register a callback invoked with the timestamp of each input event of the window, e.g. to
+    /// measure input latency.
+    ///
+    /// The callback runs when the display server's event arrives, before the corresponding
+    /// [`WindowEvent`](crate::event::WindowEvent) is delivered. [`RawTimestamp::event`] and
+    /// [`RawTimestamp::device_id`] tell which event that is. That covers pointer, scroll, key,
+    /// touch and touchpad pinch events.
+    fn connect_raw_event_timestamps<F: Fn(RawTimestamp) + 'static>(&self, f: F);
+
+    /// Whether GTK draws the decorations of the window itself.
+    ///
+    /// With [`Window::set_decorations`], X11 window managers and Wayland compositors offering the
+    /// KDE server decoration protocol decorate the window on their side, unless it has a custom
+    /// titlebar or `GTK_CSD=1` is set. This is `false` for undecorated windows.
+    ///
+    /// Server-side decorations aren't negotiated through `xdg-decoration`: GTK 3 doesn't bind the
+    /// protocol and doesn't expose the `xdg_toplevel` of the window to do so here. Compositors
+    /// offering only `xdg-decoration` always get client-side decorations.
+    fn is_using_csd(&self) -> bool;
 }
 
 impl WindowExtUnix for Window {
@@ -33,13 +161,89 @@ impl WindowExtUnix for Window {
     fn set_skip_taskbar(&self, skip: bool) {
         self.window.set_skip_taskbar(skip);
     }
+
+    fn is_skip_taskbar(&self) -> bool {
+        self.window.is_skip_taskbar()
+    }
+
+    fn set_modal(&self, modal: bool) {
+        self.window.set_modal(modal);
+    }
+
+    fn is_visible_on_all_workspaces(&self) -> bool {
+        self.window.is_visible_on_all_workspaces()
+    }
+
+    fn is_focusable(&self) -> bool {
+        self.window.is_focusable()
+    }
+
+    fn beep(&self) {
+        self.window.beep();
+    }
+
+    fn close(&self) {
+        self.window.close();
+    }
+
+    fn set_hide_on_close(&self, hide: bool) {
+        self.window.set_hide_on_close(hide);
+    }
+
+    fn set_cursor_size_override(&self, size: Option<u32>) {
+        self.window.set_cursor_size_override(size);
+    }
+
+    fn resize_to_content(&self) {
+        self.window.resize_to_content();
+    }
+
+    fn request_user_attention_for(&self, duration: Duration, _request_type: UserAttentionType) {
+        self.window.request_user_attention_for(duration);
+    }
+
+    fn set_compositor_bypass_hint(&self, bypass: bool) {
+        self.window.set_compositor_bypass_hint(bypass);
+    }
+
+    fn compositor_bypass_hint(&self) -> bool {
+        self.window.compositor_bypass_hint()
+    }
+
+    fn is_composited(&self) -> bool {
+        self.window.is_composited()
+    }
+
+    fn connect_composited_changed<F: Fn(bool) + 'static>(&self, f: F) {
+        self.window.connect_composited_changed(f);
+    }
+
+    fn set_allow_tearing(&self, allow: bool) -> Result<(), NotSupportedError> {
+        self.window.set_allow_tearing(allow)
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.window.allows_tearing()
+    }
+
+    fn connect_raw_event_timestamps<F: Fn(RawTimestamp) + 'static>(&self, f: F) {
+        self.window.connect_raw_event_timestamps(f);
+    }
+
+    fn is_using_csd(&self) -> bool {
+        self.window.is_using_csd()
+    }
 }
 
 pub trait WindowBuilderExtUnix {
     /// Build window with the given `general` and `instance` names.
     ///
-    /// The `general` sets general class of `WM_CLASS(STRING)`, while `instance` set the
-    /// instance part of it. The resulted property looks like `WM_CLASS(STRING) = "general", "instance"`.
+    /// On X11 the `general` sets the class part of `WM_CLASS(STRING)`, while `instance` sets the
+    /// instance part of it. The resulting property looks like
+    /// `WM_CLASS(STRING) = "instance", "general"`.
+    ///
+    /// On Wayland `general` is the app id of the window, `instance` is ignored. Without a name the
+    /// window uses the id given to [`EventLoopBuilderExtUnix::with_app_id`].
     ///
     /// For details about application ID conventions, see the
     /// [Desktop Entry Spec](https://specifications.freedesktop.org/desktop-entry-spec/desktop-entry-spec-latest.html#desktop-file-id)
@@ -50,8 +254,19 @@ pub trait WindowBuilderExtUnix {
 
     /// Set this window as a transient dialog for `parent`
     /// <https://gtk-rs.org/gtk3-rs/stable/latest/docs/gdk/struct.Window.html#method.set_transient_for>
+    ///
+    /// The window manager keeps the window above its parent, and the window is centered on the
+    /// parent unless [`WindowBuilder::with_position`] places it.
     fn with_transient_for(self, parent: &impl IsA<gtk::Window>) -> WindowBuilder;
 
+    /// Whether the window blocks the input of the other windows of the application while shown,
+    /// like a blocking dialog.
+    ///
+    /// Modality is meant for windows with a parent, see
+    /// [`with_transient_for`](Self::with_transient_for). Without one it still applies and the
+    /// window blocks every other window of the application. Default is `false`.
+    fn with_modal(self, modal: bool) -> WindowBuilder;
+
     /// Whether to enable or disable the internal draw for transparent window.
     ///
     /// When tranparent attribute is enabled, we will call `connect_draw` and draw a transparent background.
@@ -79,11 +294,57 @@ pub trait WindowBuilderExtUnix {
     /// Whether to create a vertical `gtk::Box` and add it as the sole child of this window.
     /// Created by default.
     fn with_default_vbox(self, add: bool) -> WindowBuilder;
+
+    /// Whether to let GTK size the window from the natural size of its content.
+    ///
+    /// This only applies when no size is given with
+    /// [`WindowBuilder::with_inner_size`](crate::window::WindowBuilder::with_inner_size).
+    /// Default is `false`.
+    fn with_size_from_content(self, size_from_content: bool) -> WindowBuilder;
+
+    /// Which of the backend's own GTK signal handlers to connect to the window.
+    ///
+    /// Leave out the handlers you want to connect yourself through
+    /// [`WindowExtUnix::gtk_window`], the events they would emit are then not reported:
+    ///
+    /// - Without [`DefaultHandlers::CLOSE`], GTK destroys the window on its own when the user
+    ///   closes it, no [`WindowEvent::CloseRequested`](crate::event::WindowEvent::CloseRequested)
+    ///   is emitted and the window can't be kept open.
+    /// - Without [`DefaultHandlers::DRAW_CLEAR`], transparent windows are not cleared before
+    ///   drawing.
+    /// - Without [`DefaultHandlers::BORDERLESS_RESIZE`], undecorated windows can't be resized
+    ///   from their edges unless you call [`Window::drag_resize_window`] yourself.
+    /// - Without [`DefaultHandlers::KEYBOARD`], no keyboard, IME or text input events are emitted.
+    /// - Without [`DefaultHandlers::POINTER`], no cursor, mouse button or scroll events are
+    ///   emitted.
+    ///
+    /// Default is [`DefaultHandlers::all`].
+    fn with_default_handlers(self, handlers: DefaultHandlers) -> WindowBuilder;
+
+    /// Whether to emit [`WindowEvent::CursorMoved`](crate::event::WindowEvent::CursorMoved).
+    ///
+    /// Applications reading the cursor position on their own can turn it off to cut down on
+    /// events, the other pointer events are still emitted with [`DefaultHandlers::POINTER`].
+    /// Default is `true`.
+    fn with_cursor_moved(self, cursor_moved: bool) -> WindowBuilder;
+
+    /// Make the window a layer surface of the compositor, like a panel, a dock or a wallpaper.
+    ///
+    /// This needs the `layer-shell` feature and the `gtk-layer-shell` library. It only works on
+    /// Wayland compositors supporting the `wlr-layer-shell` protocol, see
+    /// [`WaylandCapabilities::layer_shell`], elsewhere a warning is logged and a regular window
+    /// is created.
+    ///
+    /// The compositor places layer surfaces from their [`LayerShellConfig`], so
+    /// [`Window::set_outer_position`], [`Window::set_fullscreen`] and
+    /// [`Window::set_decorations`] are no-ops for them and the matching [`WindowBuilder`]
+    /// attributes are ignored.
+    #[cfg(feature = "layer-shell")]
+    fn with_layer_shell(self, config: LayerShellConfig) -> WindowBuilder;
 }
 
 impl WindowBuilderExtUnix for WindowBuilder {
     fn with_name(mut self, general: impl Into<String>, instance: impl Into<String>) -> Self {
-        // TODO We haven't implemented it yet.
         self.platform_specific.name = Some(ApplicationName::new(general.into(), instance.into()));
         self
     }
@@ -98,6 +359,11 @@ impl WindowBuilderExtUnix for WindowBuilder {
         self
     }
 
+    fn with_modal(mut self, modal: bool) -> WindowBuilder {
+        self.platform_specific.modal = modal;
+        self
+    }
+
     fn with_transparent_draw(mut self, draw: bool) -> WindowBuilder {
         self.platform_specific.auto_transparent = draw;
         self
@@ -122,12 +388,331 @@ impl WindowBuilderExtUnix for WindowBuilder {
         self.platform_specific.default_vbox = add;
         self
     }
+
+    fn with_size_from_content(mut self, size_from_content: bool) -> WindowBuilder {
+        self.platform_specific.size_from_content = size_from_content;
+        self
+    }
+
+    fn with_default_handlers(mut self, handlers: DefaultHandlers) -> WindowBuilder {
+        self.platform_specific.default_handlers = handlers;
+        self
+    }
+
+    fn with_cursor_moved(mut self, cursor_moved: bool) -> WindowBuilder {
+        self.platform_specific.cursor_moved = cursor_moved;
+        self
+    }
+
+    #[cfg(feature = "layer-shell")]
+    fn with_layer_shell(mut self, config: LayerShellConfig) -> WindowBuilder {
+        self.platform_specific.layer_shell = Some(config);
+        self
+    }
+}
+
+/// How a layer surface is placed by the compositor, see
+/// [`WindowBuilderExtUnix::with_layer_shell`].
+#[cfg(feature = "layer-shell")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerShellConfig {
+    /// The layer the surface is stacked in.
+    pub layer: Layer,
+    /// The edges of the monitor the surface is attached to.
+    ///
+    /// A surface anchored to two opposite edges is stretched between them.
+    pub anchors: Anchors,
+    /// How many logical pixels from the anchored edge other surfaces should leave free, like
+    /// the height of a bar.
+    ///
+    /// `0` lets other surfaces overlap, `-1` asks not to be moved by other exclusive zones.
+    pub exclusive_zone: i32,
+    /// Whether the surface can get the keyboard focus.
+    pub keyboard_interactivity: KeyboardInteractivity,
+}
+
+#[cfg(feature = "layer-shell")]
+impl Default for LayerShellConfig {
+    fn default() -> Self {
+        Self {
+            layer: Layer::Top,
+            anchors: Anchors::empty(),
+            exclusive_zone: 0,
+            keyboard_interactivity: KeyboardInteractivity::None,
+        }
+    }
+}
+
+/// The layers of the compositor, from the bottom to the top.
+#[cfg(feature = "layer-shell")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+#[cfg(feature = "layer-shell")]
+bitflags! {
+    /// The edges of the monitor a layer surface is attached to.
+    pub struct Anchors: u32 {
+        const TOP = 1 << 0;
+        const BOTTOM = 1 << 1;
+        const LEFT = 1 << 2;
+        const RIGHT = 1 << 3;
+    }
+}
+
+/// Whether a layer surface gets keyboard input.
+#[cfg(feature = "layer-shell")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyboardInteractivity {
+    /// The surface never gets the keyboard focus.
+    None,
+    /// The surface takes the keyboard focus while it is mapped, like a lock screen.
+    Exclusive,
+    /// The surface gets the keyboard focus when the user clicks it, like a regular window.
+    OnDemand,
+}
+
+bitflags! {
+    /// The GTK signal handlers the backend connects to each window, see
+    /// [`WindowBuilderExtUnix::with_default_handlers`].
+    pub struct DefaultHandlers: u32 {
+        /// `delete-event`, reports `CloseRequested` and keeps the window open.
+        const CLOSE = 1 << 0;
+        /// `draw`, clears the background of transparent windows.
+        const DRAW_CLEAR = 1 << 1;
+        /// Button, motion and touch events starting resize drags on undecorated windows.
+        const BORDERLESS_RESIZE = 1 << 2;
+        /// Key events and input methods.
+        const KEYBOARD = 1 << 3;
+        /// Crossing, motion, button and scroll events.
+        const POINTER = 1 << 4;
+    }
+}
+
+/// Additional methods on `MonitorHandle` that are specific to Unix.
+///
+/// These identify the physical monitor, e.g. to look up its ICC profile. They are queried from the
+/// display server the first time one of them is called on a handle or its clones.
+///
+/// Whether the monitor supports HDR or a wide color gamut isn't reported, neither GDK nor RandR
+/// expose it.
+pub trait MonitorHandleExtUnix {
+    /// Returns the manufacturer of the monitor, the three letter PNP id on X11.
+    fn manufacturer(&self) -> Option<String>;
+
+    /// Returns the product name of the monitor.
+    ///
+    /// On X11 this is read from the EDID, unlike [`MonitorHandle::name`].
+    fn model(&self) -> Option<String>;
+
+    /// Returns the name of the connector of the monitor, like `eDP-1` or `HDMI-1`.
+    ///
+    /// Always `None` on Wayland.
+    fn connector(&self) -> Option<String>;
+
+    /// Whether the monitor is built into the device, like a laptop panel.
+    ///
+    /// This is derived from the [`connector`](Self::connector), so it is always `false` on
+    /// Wayland.
+    fn is_builtin(&self) -> bool;
+}
+
+impl MonitorHandleExtUnix for MonitorHandle {
+    #[inline]
+    fn manufacturer(&self) -> Option<String> {
+        self.inner.manufacturer()
+    }
+
+    #[inline]
+    fn model(&self) -> Option<String> {
+        self.inner.model()
+    }
+
+    #[inline]
+    fn connector(&self) -> Option<String> {
+        self.inner.connector()
+    }
+
+    #[inline]
+    fn is_builtin(&self) -> bool {
+        self.inner.is_builtin()
+    }
 }
 
+/// Timestamp of an input event, as reported by the display server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawTimestamp {
+    /// Time of the event in milliseconds, on the clock of the display server.
+    pub time: u32,
+    /// The moment of `time` on the clock of [`Instant`].
+    ///
+    /// This relies on the display server using the monotonic clock, like Xorg and the common
+    /// Wayland compositors do. Otherwise this is the moment the event was received.
+    pub instant: Instant,
+    /// The kind of the event.
+    pub event: RawInputEvent,
+    /// The device which caused the event, as in the [`WindowEvent`](crate::event::WindowEvent).
+    pub device_id: DeviceId,
+}
+
+/// The [`WindowEvent`](crate::event::WindowEvent) an input event with a [`RawTimestamp`] is
+/// reported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RawInputEvent {
+    CursorMoved,
+    MouseInput,
+    MouseWheel,
+    KeyboardInput,
+    Touch,
+    /// `TouchpadMagnify` and `TouchpadRotate`.
+    TouchpadPinch,
+}
+
+/// Input behavior settings of the desktop session, as exposed by `GtkSettings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputSettings {
+    /// Whether a middle click pastes the primary selection.
+    pub primary_paste: bool,
+    /// Whether scrollbars are drawn over the content and only shown on demand.
+    pub overlay_scrolling: bool,
+    /// How far the pointer has to move, in logical pixels, before a drag starts.
+    pub drag_threshold: u32,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            primary_paste: true,
+            overlay_scrolling: true,
+            drag_threshold: 8,
+        }
+    }
+}
+
+/// Optional protocols offered by the Wayland compositor, see
+/// [`EventLoopWindowTargetExtUnix::wayland_capabilities`].
+///
+/// GTK 3 only uses the protocols it knows itself, so this tells what the compositor could do,
+/// e.g. to decide which settings to offer. Features of this crate that would need a protocol GTK
+/// doesn't bind, like locking the cursor, stay unsupported even if the compositor has it.
+/// [`WindowExtUnix::set_allow_tearing`] binds its protocol itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WaylandCapabilities {
+    /// `zxdg_decoration_manager_v1` or `org_kde_kwin_server_decoration_manager`, GTK only uses
+    /// the latter, see [`WindowExtUnix::is_using_csd`].
+    pub server_side_decorations: bool,
+    /// `zwp_pointer_constraints_v1`, to lock or confine the pointer.
+    pub pointer_constraints: bool,
+    /// `zwp_relative_pointer_manager_v1`, for raw pointer motion.
+    pub relative_pointer: bool,
+    /// `wp_tearing_control_manager_v1`, for tearing presentation.
+    pub tearing_control: bool,
+    /// `xdg_activation_v1`, to focus a window without user interaction.
+    pub activation: bool,
+    /// `zwlr_layer_shell_v1`, for panels and overlays.
+    pub layer_shell: bool,
+}
+
+/// Identifies a global shortcut, see
+/// [`EventLoopWindowTargetExtUnix::register_global_shortcut`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShortcutId(pub(crate) u32);
+
 /// Additional methods on `EventLoopWindowTarget` that are specific to Unix.
 pub trait EventLoopWindowTargetExtUnix {
     /// True if the `EventLoopWindowTarget` uses Wayland.
     fn is_wayland(&self) -> bool;
+
+    /// Returns the `gtk::Application` from gtk crate that the windows belong to.
+    ///
+    /// This is the place for `GAction`s, application-wide accelerators and menus. The
+    /// application is already registered when the event loop is created.
+    fn gtk_application(&self) -> &gtk::Application;
+
+    /// Returns the optional protocols offered by the Wayland compositor.
+    ///
+    /// They are looked up once when the event loop is created. Everything is `false` on X11.
+    fn wayland_capabilities(&self) -> WaylandCapabilities;
+
+    /// Returns the ids of the windows of the event loop which weren't destroyed yet, in the order
+    /// they were created.
+    ///
+    /// An id may belong to a window whose [`Window`] was dropped while the window itself is kept
+    /// around by GTK, e.g. by a reference of [`WindowExtUnix::gtk_window`]. Settings like
+    /// [`Window::set_theme`] apply to every window already.
+    fn window_ids(&self) -> Vec<WindowId>;
+
+    /// Register a callback invoked when the application is launched again while this instance is
+    /// running.
+    ///
+    /// GIO routes the activation of a second launch to the instance that registered the
+    /// application id first. The callback receives the URIs of the files the new launch asked to
+    /// open, which are its command line arguments like with `g_application_run`, and is empty for
+    /// a plain activation. This is the place to present the main window of the running instance.
+    /// [`StartCause::Init`](crate::event::StartCause::Init) is still only emitted once per run.
+    ///
+    /// The `run` of the second launch hands over to the running instance and exits right away,
+    /// only [`Event::LoopDestroyed`](crate::event::Event::LoopDestroyed) is delivered to it.
+    fn connect_reactivated<F: Fn(&[String]) + 'static>(&self, f: F);
+
+    /// Returns the current input behavior settings of the desktop session.
+    fn input_settings(&self) -> InputSettings;
+
+    /// Register a callback invoked with the new [`InputSettings`] whenever one of them changes.
+    fn connect_input_settings_changed<F: Fn(InputSettings) + 'static>(&self, f: F);
+
+    /// Register a callback invoked whenever the event loop is idle, e.g. for background work
+    /// like autosaving.
+    ///
+    /// The callback runs on the event loop thread, inside an iteration of the GTK main loop once
+    /// no display server event or redraw of GTK is pending. That is between the dispatches of
+    /// those events, not after [`Event::MainEventsCleared`](crate::event::Event::MainEventsCleared).
+    /// It keeps being invoked as long as it returns `true`, which also keeps the event loop from
+    /// waiting, and is removed once it returns `false` or the event loop is destroyed.
+    fn connect_idle<F: FnMut() -> bool + 'static>(&self, f: F);
+
+    /// Run a future on the event loop thread, through the executor of the GLib main context.
+    ///
+    /// The future can await channels or timers and then call the methods of the windows
+    /// directly. Waking it up ends the waiting of [`ControlFlow::Wait`] and
+    /// [`ControlFlow::WaitUntil`] like any other event source. Futures still pending are dropped
+    /// once the event loop is destroyed, after [`Event::LoopDestroyed`].
+    ///
+    /// Panics if not called on the event loop thread.
+    fn spawn_local<F: Future<Output = ()> + 'static>(&self, f: F);
+
+    /// Grab a key combination for the whole session, e.g. to show or hide a window from anywhere.
+    ///
+    /// `scancode` is the X11 keycode, as reported by
+    /// [`KeyboardInput::scancode`](crate::event::KeyboardInput::scancode). The combination also
+    /// triggers while Caps Lock or Num Lock are on. Presses are reported to the callbacks of
+    /// [`connect_global_shortcut`](Self::connect_global_shortcut) and not to any window.
+    ///
+    /// Fails if another client already grabbed the combination.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Wayland:** Unsupported, clients can't grab keys. Use the `GlobalShortcuts` portal of
+    ///   `xdg-desktop-portal` instead.
+    fn register_global_shortcut(
+        &self,
+        modifiers: ModifiersState,
+        scancode: ScanCode,
+    ) -> Result<ShortcutId, ExternalError>;
+
+    /// Release a key combination grabbed with
+    /// [`register_global_shortcut`](Self::register_global_shortcut).
+    fn unregister_global_shortcut(&self, id: ShortcutId);
+
+    /// Register a callback invoked with the id of a global shortcut whenever it is pressed.
+    ///
+    /// The callbacks are removed once the event loop is destroyed. This is a no-op on Wayland.
+    fn connect_global_shortcut<F: Fn(ShortcutId) + 'static>(&self, f: F);
 }
 
 impl<T> EventLoopWindowTargetExtUnix for EventLoopWindowTarget<T> {
@@ -135,4 +720,157 @@ impl<T> EventLoopWindowTargetExtUnix for EventLoopWindowTarget<T> {
     fn is_wayland(&self) -> bool {
         self.p.is_wayland()
     }
+
+    #[inline]
+    fn gtk_application(&self) -> &gtk::Application {
+        &self.p.app
+    }
+
+    #[inline]
+    fn wayland_capabilities(&self) -> WaylandCapabilities {
+        self.p.wayland_capabilities
+    }
+
+    #[inline]
+    fn window_ids(&self) -> Vec<WindowId> {
+        self.p.window_ids()
+    }
+
+    #[inline]
+    fn connect_reactivated<F: Fn(&[String]) + 'static>(&self, f: F) {
+        self.p.connect_reactivated(f)
+    }
+
+    #[inline]
+    fn input_settings(&self) -> InputSettings {
+        self.p.input_settings()
+    }
+
+    #[inline]
+    fn connect_input_settings_changed<F: Fn(InputSettings) + 'static>(&self, f: F) {
+        self.p.connect_input_settings_changed(f)
+    }
+
+    #[inline]
+    fn connect_idle<F: FnMut() -> bool + 'static>(&self, f: F) {
+        self.p.connect_idle(f)
+    }
+
+    #[inline]
+    fn spawn_local<F: Future<Output = ()> + 'static>(&self, f: F) {
+        self.p.spawn_local(f)
+    }
+
+    #[inline]
+    fn register_global_shortcut(
+        &self,
+        modifiers: ModifiersState,
+        scancode: ScanCode,
+    ) -> Result<ShortcutId, ExternalError> {
+        self.p.register_global_shortcut(modifiers, scancode)
+    }
+
+    #[inline]
+    fn unregister_global_shortcut(&self, id: ShortcutId) {
+        self.p.unregister_global_shortcut(id)
+    }
+
+    #[inline]
+    fn connect_global_shortcut<F: Fn(ShortcutId) + 'static>(&self, f: F) {
+        self.p.connect_global_shortcut(f)
+    }
+}
+
+/// Additional methods on `EventLoop` that are specific to Unix.
+pub trait EventLoopExtUnix {
+    /// A type provided by the user that can be passed through [`Event::UserEvent`].
+    type UserEvent;
+
+    /// Runs the event loop like [`EventLoop::run`], but returns the exit code instead of exiting
+    /// the process.
+    ///
+    /// Once the loop exited, the window requests that are still queued are applied, then every
+    /// window is destroyed and its [`WindowEvent::Destroyed`](crate::event::WindowEvent::Destroyed)
+    /// is delivered before [`Event::LoopDestroyed`]. The `GtkApplication` quits along with the
+    /// event loop, requests of the [`Window`]s that are kept around fail afterwards. Another
+    /// event loop can be created then. [`EventLoop::run`] does the same teardown before it
+    /// exits.
+    fn run_without_exit<F>(self, event_handler: F) -> i32
+    where
+        F: 'static
+            + FnMut(
+                Event<'_, Self::UserEvent>,
+                &EventLoopWindowTarget<Self::UserEvent>,
+                &mut ControlFlow,
+            );
+}
+
+impl<T> EventLoopExtUnix for EventLoop<T> {
+    type UserEvent = T;
+
+    #[inline]
+    fn run_without_exit<F>(self, event_handler: F) -> i32
+    where
+        F: 'static
+            + FnMut(
+                Event<'_, Self::UserEvent>,
+                &EventLoopWindowTarget<Self::UserEvent>,
+                &mut ControlFlow,
+            ),
+    {
+        self.event_loop.run_without_exit(event_handler)
+    }
+}
+
+/// Additional methods on `EventLoopBuilder` that are specific to Unix.
+pub trait EventLoopBuilderExtUnix {
+    /// Whether to allow the event loop to be created off of the main thread.
+    ///
+    /// By default, the event loop is only allowed to be created on the main thread, to make
+    /// platform compatibility easier. GTK must then only be used from the thread the event loop
+    /// was created on, and no other thread may run the default GLib main context.
+    fn with_any_thread(&mut self, any_thread: bool) -> &mut Self;
+
+    /// Sets the id of the `gtk::Application`, in reverse DNS notation like `org.example.App`.
+    ///
+    /// The desktop uses it to find the desktop file and the icon of the application, and Flatpak
+    /// needs it to match the id of the sandbox. It also makes the application single-instance:
+    /// the `run` of another launch hands its files over to the running instance and exits
+    /// without running its loop, see [`EventLoopWindowTargetExtUnix::connect_reactivated`].
+    /// An invalid id is ignored with a warning.
+    fn with_app_id(&mut self, app_id: impl Into<String>) -> &mut Self;
+}
+
+impl<T> EventLoopBuilderExtUnix for EventLoopBuilder<T> {
+    #[inline]
+    fn with_any_thread(&mut self, any_thread: bool) -> &mut Self {
+        self.platform_specific.any_thread = any_thread;
+        self
+    }
+
+    #[inline]
+    fn with_app_id(&mut self, app_id: impl Into<String>) -> &mut Self {
+        self.platform_specific.app_id = Some(app_id.into());
+        self
+    }
+}
+
+/// Additional methods on `EventLoopProxy` that are specific to Unix.
+pub trait EventLoopProxyExtUnix<T: 'static> {
+    /// Send an event to the `EventLoop` ahead of every event that is already queued.
+    ///
+    /// Prioritized events are kept in a separate queue which is drained before any window, device
+    /// or regular user event each time the event loop processes its queue. They are delivered in
+    /// the order they were sent, but there is no ordering guarantee relative to events sent with
+    /// [`EventLoopProxy::send_event`].
+    ///
+    /// Returns an `Err` if the associated `EventLoop` no longer exists.
+    fn send_event_prioritized(&self, event: T) -> Result<(), EventLoopClosed<T>>;
+}
+
+impl<T: 'static> EventLoopProxyExtUnix<T> for EventLoopProxy<T> {
+    #[inline]
+    fn send_event_prioritized(&self, event: T) -> Result<(), EventLoopClosed<T>> {
+        self.event_loop_proxy.send_event_prioritized(event)
+    }
 }
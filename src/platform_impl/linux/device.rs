@@ -0,0 +1,490 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    os::raw::c_int,
+    ptr,
+    rc::Rc,
+    slice,
+};
+
+use gdk::prelude::DisplayExtManual;
+use glib::{translate::FromGlibPtrNone, ObjectType};
+use gtk::traits::{GtkApplicationExt, GtkWindowExt};
+use x11_dl::{xinput2, xlib};
+
+use crate::{
+    event::{
+        DeviceEvent, DeviceId as RootDeviceId, ElementState, Event, KeyboardInput, MouseButton,
+        ScanCode, VirtualKeyCode, WindowEvent,
+    },
+    event_loop::DeviceEventFilter,
+    window::WindowId as RootWindowId,
+};
+
+use super::{keyboard, DeviceId, WindowId, DEVICE_ID};
+
+/// Stable `DeviceId`s of the physical devices, so events of e.g. a drawing tablet and a mouse can
+/// be told apart.
+#[derive(Default)]
+pub(crate) struct DeviceIds {
+    ids: HashMap<gdk::Device, DeviceId>,
+    next: usize,
+}
+
+impl DeviceIds {
+    /// Returns the id of the physical device which caused `event`.
+    pub(crate) fn id(&mut self, event: &gdk::Event) -> RootDeviceId {
+        event
+            .source_device()
+            .map_or(DEVICE_ID, |device| self.insert(device))
+    }
+
+    pub(crate) fn insert(&mut self, device: gdk::Device) -> RootDeviceId {
+        let next = &mut self.next;
+        RootDeviceId(*self.ids.entry(device).or_insert_with(|| {
+            // 0 is left for `DEVICE_ID`.
+            *next += 1;
+            DeviceId(*next)
+        }))
+    }
+
+    pub(crate) fn remove(&mut self, device: &gdk::Device) -> Option<RootDeviceId> {
+        self.ids.remove(device).map(RootDeviceId)
+    }
+}
+
+/// The buttons and keys pressed in the windows, their releases are synthesized when the device is
+/// removed or the grab delivering them is broken.
+#[derive(Default)]
+pub(crate) struct PressedInputs {
+    buttons: Vec<(WindowId, RootDeviceId, MouseButton)>,
+    keys: Vec<(WindowId, RootDeviceId, ScanCode, Option<VirtualKeyCode>)>,
+}
+
+impl PressedInputs {
+    pub(crate) fn button(
+        &mut self,
+        window_id: WindowId,
+        device_id: RootDeviceId,
+        button: MouseButton,
+        state: ElementState,
+    ) {
+        let pressed = (window_id, device_id, button);
+        self.buttons.retain(|b| *b != pressed);
+        if state == ElementState::Pressed {
+            self.buttons.push(pressed);
+        }
+    }
+
+    pub(crate) fn key(
+        &mut self,
+        window_id: WindowId,
+        device_id: RootDeviceId,
+        scancode: ScanCode,
+        virtual_keycode: Option<VirtualKeyCode>,
+        state: ElementState,
+    ) {
+        // Repeated presses are only released once.
+        self.keys
+            .retain(|&(w, d, s, _)| (w, d, s) != (window_id, device_id, scancode));
+        if state == ElementState::Pressed {
+            self.keys
+                .push((window_id, device_id, scancode, virtual_keycode));
+        }
+    }
+
+    /// Releases everything pressed with a removed device.
+    pub(crate) fn release_device<T>(&mut self, device_id: RootDeviceId) -> Vec<Event<'static, T>> {
+        let mut events = self.release_buttons(|_, d| d == device_id);
+        events.extend(self.release_keys(|_, d| d == device_id));
+        events
+    }
+
+    /// Releases the keys, or the buttons, pressed in a window which lost its grab.
+    pub(crate) fn release_window<T>(
+        &mut self,
+        window_id: WindowId,
+        keyboard: bool,
+    ) -> Vec<Event<'static, T>> {
+        if keyboard {
+            self.release_keys(|w, _| w == window_id)
+        } else {
+            self.release_buttons(|w, _| w == window_id)
+        }
+    }
+
+    /// Forgets the inputs of a destroyed window, there is nobody left to release them to.
+    pub(crate) fn remove_window(&mut self, window_id: WindowId) {
+        self.buttons.retain(|&(w, _, _)| w != window_id);
+        self.keys.retain(|&(w, _, _, _)| w != window_id);
+    }
+
+    fn release_buttons<T>(
+        &mut self,
+        released: impl Fn(WindowId, RootDeviceId) -> bool,
+    ) -> Vec<Event<'static, T>> {
+        let mut events = Vec::new();
+        self.buttons.retain(|&(window_id, device_id, button)| {
+            if !released(window_id, device_id) {
+                return true;
+            }
+            #[allow(deprecated)]
+            events.push(Event::WindowEvent {
+                window_id: RootWindowId(window_id),
+                event: WindowEvent::MouseInput {
+                    device_id,
+                    state: ElementState::Released,
+                    button,
+                    modifiers: Default::default(),
+                },
+            });
+            false
+        });
+        events
+    }
+
+    fn release_keys<T>(
+        &mut self,
+        released: impl Fn(WindowId, RootDeviceId) -> bool,
+    ) -> Vec<Event<'static, T>> {
+        let mut events = Vec::new();
+        self.keys
+            .retain(|&(window_id, device_id, scancode, virtual_keycode)| {
+                if !released(window_id, device_id) {
+                    return true;
+                }
+                #[allow(deprecated)]
+                events.push(Event::WindowEvent {
+                    window_id: RootWindowId(window_id),
+                    event: WindowEvent::KeyboardInput {
+                        device_id,
+                        input: KeyboardInput {
+                            scancode,
+                            state: ElementState::Released,
+                            virtual_keycode,
+                            modifiers: Default::default(),
+                        },
+                        is_synthetic: true,
+                    },
+                });
+                false
+            });
+        events
+    }
+}
+
+/// Listener of the XInput2 raw events, which are reported regardless of the window under the
+/// pointer and aren't affected by pointer acceleration.
+struct RawEventListener<T: 'static> {
+    xlib: xlib::Xlib,
+    xdisplay: *mut xlib::Display,
+    opcode: c_int,
+    device_manager: *mut gdk_sys::GdkDeviceManager,
+    app: gtk::Application,
+    filter: Rc<Cell<DeviceEventFilter>>,
+    device_ids: Rc<RefCell<DeviceIds>>,
+    event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+}
+
+/// The event filter of [`listen_raw_events`], removed once dropped.
+pub(crate) struct RawEventFilter<T: 'static>(*mut RawEventListener<T>);
+
+impl<T: 'static> Drop for RawEventFilter<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gdk_sys::gdk_window_remove_filter(
+                ptr::null_mut(),
+                Some(raw_event_filter::<T>),
+                self.0 as *mut _,
+            );
+            drop(Box::from_raw(self.0));
+        }
+    }
+}
+
+/// Start sending `DeviceEvent`s for the raw input of the master devices, until the returned
+/// filter is dropped.
+///
+/// Only X11 has raw input, this is a no-op on Wayland.
+pub(crate) fn listen_raw_events<T: 'static>(
+    display: &gdk::Display,
+    app: gtk::Application,
+    filter: Rc<Cell<DeviceEventFilter>>,
+    device_ids: Rc<RefCell<DeviceIds>>,
+    event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+) -> Option<RawEventFilter<T>> {
+    if !display.backend().is_x11() {
+        return None;
+    }
+    let (xlib, xinput2) = match (xlib::Xlib::open(), xinput2::XInput2::open()) {
+        (Ok(xlib), Ok(xinput2)) => (xlib, xinput2),
+        _ => {
+            log::warn!("Failed to load Xlib or XInput2, device events are disabled");
+            return None;
+        }
+    };
+
+    unsafe {
+        let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(display.as_ptr() as *mut _)
+            as *mut xlib::Display;
+        let (mut opcode, mut first_event, mut first_error) = (0, 0, 0);
+        if (xlib.XQueryExtension)(
+            xdisplay,
+            b"XInputExtension\0".as_ptr() as *const _,
+            &mut opcode,
+            &mut first_event,
+            &mut first_error,
+        ) == 0
+        {
+            return None;
+        }
+
+        let mut mask = [0; (xinput2::XI_LASTEVENT as usize + 7) / 8];
+        for event in [
+            xinput2::XI_RawMotion,
+            xinput2::XI_RawButtonPress,
+            xinput2::XI_RawButtonRelease,
+            xinput2::XI_RawKeyPress,
+            xinput2::XI_RawKeyRelease,
+        ] {
+            xinput2::XISetMask(&mut mask, event);
+        }
+        let mut event_mask = xinput2::XIEventMask {
+            deviceid: xinput2::XIAllMasterDevices,
+            mask_len: mask.len() as c_int,
+            mask: mask.as_mut_ptr(),
+        };
+        let root = gdk_x11_sys::gdk_x11_get_default_root_xwindow();
+        (xinput2.XISelectEvents)(xdisplay, root, &mut event_mask, 1);
+
+        let listener = Box::new(RawEventListener {
+            xlib,
+            xdisplay,
+            opcode,
+            device_manager: gdk_sys::gdk_display_get_device_manager(display.as_ptr()),
+            app,
+            filter,
+            device_ids,
+            event_tx,
+        });
+        let listener = Box::into_raw(listener);
+        gdk_sys::gdk_window_add_filter(
+            ptr::null_mut(),
+            Some(raw_event_filter::<T>),
+            listener as *mut _,
+        );
+        Some(RawEventFilter(listener))
+    }
+}
+
+unsafe extern "C" fn raw_event_filter<T: 'static>(
+    xevent: *mut gdk_sys::GdkXEvent,
+    _event: *mut gdk_sys::GdkEvent,
+    data: glib::ffi::gpointer,
+) -> gdk_sys::GdkFilterReturn {
+    let listener = &*(data as *const RawEventListener<T>);
+    let xevent = &*(xevent as *const xlib::XEvent);
+    if xevent.get_type() != xlib::GenericEvent {
+        return gdk_sys::GDK_FILTER_CONTINUE;
+    }
+
+    // GDK already fetched the data of the cookie.
+    let cookie: &xlib::XGenericEventCookie = xevent.as_ref();
+    if cookie.extension != listener.opcode || cookie.data.is_null() {
+        return gdk_sys::GDK_FILTER_CONTINUE;
+    }
+    match cookie.evtype {
+        xinput2::XI_RawMotion
+        | xinput2::XI_RawButtonPress
+        | xinput2::XI_RawButtonRelease
+        | xinput2::XI_RawKeyPress
+        | xinput2::XI_RawKeyRelease => {
+            listener.handle(&*(cookie.data as *const xinput2::XIRawEvent));
+            // GDK doesn't translate raw events, there's no point in passing them on.
+            gdk_sys::GDK_FILTER_REMOVE
+        }
+        _ => gdk_sys::GDK_FILTER_CONTINUE,
+    }
+}
+
+/// Whether `filter` lets raw events through, `has_focus` tells whether any window has the focus.
+fn is_reported(filter: DeviceEventFilter, has_focus: impl FnOnce() -> bool) -> bool {
+    match filter {
+        DeviceEventFilter::Always => false,
+        DeviceEventFilter::Unfocused => has_focus(),
+        DeviceEventFilter::Never => true,
+    }
+}
+
+impl<T: 'static> RawEventListener<T> {
+    unsafe fn handle(&self, xev: &xinput2::XIRawEvent) {
+        let has_focus = || self.app.windows().iter().any(|w| w.is_active());
+        if !is_reported(self.filter.get(), has_focus) {
+            return;
+        }
+
+        let device_id = self.device_id(xev.sourceid);
+        match xev.evtype {
+            xinput2::XI_RawMotion => {
+                let mask =
+                    slice::from_raw_parts(xev.valuators.mask, xev.valuators.mask_len as usize);
+                let mut value = xev.raw_values;
+                let mut delta = (0.0, 0.0);
+                for axis in 0..(mask.len() * 8) as i32 {
+                    if !xinput2::XIMaskIsSet(mask, axis) {
+                        continue;
+                    }
+                    match axis {
+                        0 => delta.0 = *value,
+                        1 => delta.1 = *value,
+                        _ => (),
+                    }
+                    self.send(
+                        device_id,
+                        DeviceEvent::Motion {
+                            axis: axis as u32,
+                            value: *value,
+                        },
+                    );
+                    value = value.add(1);
+                }
+                if delta != (0.0, 0.0) {
+                    self.send(device_id, DeviceEvent::MouseMotion { delta });
+                }
+            }
+            // Scrolling emulates button presses of the legacy wheel buttons.
+            xinput2::XI_RawButtonPress | xinput2::XI_RawButtonRelease
+                if xev.flags & xinput2::XIPointerEmulated == 0 =>
+            {
+                self.send(
+                    device_id,
+                    DeviceEvent::Button {
+                        button: xev.detail as u32,
+                        state: if xev.evtype == xinput2::XI_RawButtonPress {
+                            ElementState::Pressed
+                        } else {
+                            ElementState::Released
+                        },
+                    },
+                );
+            }
+            xinput2::XI_RawKeyPress | xinput2::XI_RawKeyRelease => {
+                let keysym = (self.xlib.XkbKeycodeToKeysym)(self.xdisplay, xev.detail as u8, 0, 0);
+                #[allow(deprecated)]
+                self.send(
+                    device_id,
+                    DeviceEvent::Key(KeyboardInput {
+                        scancode: xev.detail as u32,
+                        state: if xev.evtype == xinput2::XI_RawKeyPress {
+                            ElementState::Pressed
+                        } else {
+                            ElementState::Released
+                        },
+                        virtual_keycode: keyboard::gdk_key_to_virtual_key(gdk::keys::Key::from(
+                            keysym as u32,
+                        )),
+                        // this field is depracted so it is fine to pass empty state
+                        modifiers: Default::default(),
+                    }),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    /// Looks up the `DeviceId` of the XInput2 device with the given id.
+    unsafe fn device_id(&self, xi_device_id: c_int) -> RootDeviceId {
+        let device =
+            gdk_x11_sys::gdk_x11_device_manager_lookup(self.device_manager as *mut _, xi_device_id);
+        if device.is_null() {
+            DEVICE_ID
+        } else {
+            let device = gdk::Device::from_glib_none(device as *mut gdk_sys::GdkDevice);
+            self.device_ids.borrow_mut().insert(device)
+        }
+    }
+
+    fn send(&self, device_id: RootDeviceId, event: DeviceEvent) {
+        if let Err(e) = self.event_tx.send(Event::DeviceEvent { device_id, event }) {
+            log::warn!("Failed to send device event to event channel: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_event_filter() {
+        assert!(!is_reported(DeviceEventFilter::Always, || true));
+        assert!(is_reported(DeviceEventFilter::Unfocused, || true));
+        assert!(!is_reported(DeviceEventFilter::Unfocused, || false));
+        assert!(is_reported(DeviceEventFilter::Never, || false));
+    }
+
+    #[test]
+    fn removed_device_releases_its_inputs() {
+        let (window, mouse, other) = (
+            WindowId(1),
+            RootDeviceId(DeviceId(1)),
+            RootDeviceId(DeviceId(2)),
+        );
+        let mut pressed = PressedInputs::default();
+        pressed.button(window, mouse, MouseButton::Left, ElementState::Pressed);
+        pressed.button(window, mouse, MouseButton::Right, ElementState::Pressed);
+        pressed.button(window, mouse, MouseButton::Right, ElementState::Released);
+        pressed.button(window, other, MouseButton::Left, ElementState::Pressed);
+
+        #[allow(deprecated)]
+        let released = vec![Event::WindowEvent {
+            window_id: RootWindowId(window),
+            event: WindowEvent::MouseInput {
+                device_id: mouse,
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+            },
+        }];
+        assert_eq!(pressed.release_device::<()>(mouse), released);
+        assert!(pressed.release_device::<()>(mouse).is_empty());
+        assert_eq!(pressed.release_device::<()>(other).len(), 1);
+    }
+
+    #[test]
+    fn broken_keyboard_grab_releases_keys_once() {
+        let (window, keyboard) = (WindowId(1), RootDeviceId(DeviceId(1)));
+        let mut pressed = PressedInputs::default();
+        // Key repeat presses the same key again.
+        pressed.key(
+            window,
+            keyboard,
+            38,
+            Some(VirtualKeyCode::A),
+            ElementState::Pressed,
+        );
+        pressed.key(
+            window,
+            keyboard,
+            38,
+            Some(VirtualKeyCode::A),
+            ElementState::Pressed,
+        );
+        pressed.button(window, keyboard, MouseButton::Left, ElementState::Pressed);
+
+        let released = pressed.release_window::<()>(window, true);
+        assert_eq!(released.len(), 1);
+        assert!(matches!(
+            released[0],
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    is_synthetic: true,
+                    ..
+                },
+                ..
+            }
+        ));
+        // The buttons wait for a broken pointer grab.
+        assert_eq!(pressed.release_window::<()>(window, false).len(), 1);
+    }
+}
@@ -1,75 +1,396 @@
 use std::{
-    cell::RefCell,
-    collections::{HashSet, VecDeque},
+    cell::{Cell, RefCell},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    future::Future,
+    hash::{Hash, Hasher},
+    panic::{self, AssertUnwindSafe},
     process,
     rc::Rc,
-    sync::atomic::{AtomicU32, Ordering},
-    time::Instant,
+    sync::{atomic::Ordering, Once},
+    time::{Duration, Instant},
 };
 
-use cairo::{RectangleInt, Region};
-use crossbeam_channel::SendError;
+use cairo::Region;
+use crossbeam_channel::{SendError, TryRecvError};
 use gdk::{
     prelude::{ApplicationExt, DisplayExtManual},
-    Cursor, CursorType, EventKey, EventMask, ScrollDirection, WindowEdge, WindowState,
+    AxisUse, Cursor, CursorType, EventButton, EventKey, EventMask, EventMotion, EventTouchpadPinch,
+    EventType, EventVisibility, GrabStatus, ScrollDirection, SeatCapabilities, VisibilityState,
+    WMFunction, WindowEdge, WindowState,
 };
-use gio::Cancellable;
-use glib::{MainContext, ObjectType, Priority};
+use gio::{
+    prelude::{ApplicationExtManual, FileExt},
+    Cancellable,
+};
+use glib::{translate::ToGlibPtr, Cast, MainContext, ObjectExt, ObjectType, Priority, SourceId};
 use gtk::{
-    prelude::{DeviceExt, SeatExt, WidgetExtManual},
+    prelude::{DeviceExt, GtkSettingsExt, IMContextExt, SeatExt, WidgetExtManual},
     traits::{GtkApplicationExt, GtkWindowExt, WidgetExt},
+    InputPurpose, Settings,
 };
 use raw_window_handle::{RawDisplayHandle, WaylandDisplayHandle, XlibDisplayHandle};
 
 use crate::{
-    dpi::{LogicalPosition, LogicalSize},
+    dpi::{LogicalPosition, PhysicalPosition},
+    error::ExternalError,
     event::{
-        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
-        StartCause, TouchPhase, WindowEvent,
+        AxisId, DeviceEvent, DeviceId as RootDeviceId, ElementState, Event, Force, Ime,
+        KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, ScanCode, StartCause, Touch,
+        TouchPhase, WindowEvent,
     },
     event_loop::{
         ControlFlow, DeviceEventFilter, EventLoopClosed, EventLoopWindowTarget as RootELW,
+        EVENT_LOOP_CREATED,
     },
-    window::{CursorIcon, WindowId as RootWindowId},
+    platform::unix::{DefaultHandlers, InputSettings, ShortcutId, WaylandCapabilities},
+    window::{CursorGrabMode, CursorIcon, ImePurpose, WindowButtons, WindowId as RootWindowId},
 };
 
 use super::{
+    device::{self, DeviceIds, PressedInputs, RawEventFilter},
+    global_shortcut::GlobalShortcuts,
     keyboard,
-    monitor::MonitorHandle,
+    monitor::{self, MonitorHandle, SavedVideoModes},
     util,
-    window::{hit_test, WindowRequest},
-    Fullscreen, PlatformSpecificEventLoopAttributes, WindowId, DEVICE_ID,
+    window::{current_theme, hit_test_relative, CursorState, SystemTheme, WindowRequest},
+    Fullscreen, PlatformSpecificEventLoopAttributes, WindowId,
 };
 
+/// How long the window manager gets to swap the frame of a window before its position is restored.
+const DECORATIONS_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Device axes reported through `WindowEvent::AxisMotion`, with their `AxisId`.
+///
+/// The ids match the values of `GdkAxisUse`. The X and Y axes are left out since they are already
+/// reported through `WindowEvent::CursorMoved`.
+const MOTION_AXES: [(AxisUse, AxisId); 7] = [
+    (AxisUse::Pressure, 3),
+    (AxisUse::Xtilt, 4),
+    (AxisUse::Ytilt, 5),
+    (AxisUse::Wheel, 6),
+    (AxisUse::Distance, 7),
+    (AxisUse::Rotation, 8),
+    (AxisUse::Slider, 9),
+];
+
 pub struct EventLoop<T: 'static> {
     /// Window target.
     window_target: RootELW<T>,
     /// User event sender for EventLoopProxy
     pub(crate) user_event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+    /// Prioritized user event sender for EventLoopProxy
+    prioritized_event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+    /// Wakes up the main context of EventLoop for EventLoopProxy
+    wakeup_tx: glib::Sender<()>,
+    /// Source of the wake ups, removed once EventLoop is dropped
+    wakeup_source: Option<SourceId>,
     /// Event queue of EventLoop
-    events: crossbeam_channel::Receiver<Event<'static, T>>,
+    events: EventQueue<T>,
     /// Draw queue of EventLoop
     draws: crossbeam_channel::Receiver<WindowId>,
+    /// Source dispatching the window requests, removed once EventLoop is dropped
+    window_requests_source: Option<SourceId>,
+    /// Filter of the raw device events, removed once EventLoop is dropped
+    _raw_event_filter: Option<RawEventFilter<T>>,
 }
 
 /// Used to send custom events to `EventLoop`.
 #[derive(Debug)]
 pub struct EventLoopProxy<T: 'static> {
     user_event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+    prioritized_event_tx: crossbeam_channel::Sender<Event<'static, T>>,
+    wakeup_tx: glib::Sender<()>,
 }
 
 impl<T: 'static> Clone for EventLoopProxy<T> {
     fn clone(&self) -> Self {
         Self {
             user_event_tx: self.user_event_tx.clone(),
+            prioritized_event_tx: self.prioritized_event_tx.clone(),
+            wakeup_tx: self.wakeup_tx.clone(),
+        }
+    }
+}
+
+/// Event queue of `EventLoop`.
+///
+/// Prioritized user events have their own channel which is always drained before the regular
+/// event channel. Events within each channel keep their FIFO order.
+struct EventQueue<T: 'static> {
+    prioritized: crossbeam_channel::Receiver<Event<'static, T>>,
+    events: crossbeam_channel::Receiver<Event<'static, T>>,
+    /// Drained events that weren't delivered because the loop exited, they come first next run.
+    deferred: RefCell<Vec<Event<'static, T>>>,
+}
+
+impl<T: 'static> EventQueue<T> {
+    fn is_empty(&self) -> bool {
+        self.deferred.borrow().is_empty() && self.prioritized.is_empty() && self.events.is_empty()
+    }
+
+    fn try_recv(&self) -> Result<Event<'static, T>, TryRecvError> {
+        self.prioritized
+            .try_recv()
+            .or_else(|_| self.events.try_recv())
+    }
+
+    /// Takes every queued event, so they are all delivered within one loop iteration.
+    ///
+    /// Consecutive `CursorMoved` events of the same window and device are coalesced into the last
+    /// one, only the final position matters to the application.
+    fn drain(&self) -> Vec<Event<'static, T>> {
+        let mut events: Vec<Event<'static, T>> = self.deferred.take();
+        while let Ok(event) = self.try_recv() {
+            if let (
+                Some(Event::WindowEvent {
+                    window_id: last_window_id,
+                    event:
+                        WindowEvent::CursorMoved {
+                            device_id: last_device_id,
+                            ..
+                        },
+                }),
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::CursorMoved { device_id, .. },
+                },
+            ) = (events.last(), &event)
+            {
+                if last_window_id == window_id && last_device_id == device_id {
+                    events.pop();
+                }
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// Puts back drained events that weren't delivered, ahead of every queued one.
+    fn defer(&self, events: impl IntoIterator<Item = Event<'static, T>>) {
+        let mut deferred = self.deferred.borrow_mut();
+        let queued = std::mem::take(&mut *deferred);
+        deferred.extend(events);
+        deferred.extend(queued);
+    }
+}
+
+/// Creates a sender that wakes up a blocking iteration of `context` from any thread.
+///
+/// Sending makes a source of `context` ready, so the iteration returns once it is dispatched, no
+/// matter whether it was already polling.
+fn wakeup_channel(context: &MainContext) -> (glib::Sender<()>, SourceId) {
+    let (wakeup_tx, wakeup_rx) = glib::MainContext::channel(Priority::default());
+    let source = wakeup_rx.attach(Some(context), |()| glib::ControlFlow::Continue);
+    (wakeup_tx, source)
+}
+
+/// Hands a launch over to the primary instance of the application.
+///
+/// Like `g_application_run`, the command line arguments are opened as files there, or the
+/// primary instance is activated without any.
+fn forward_launch(app: &gtk::Application) {
+    let files: Vec<gio::File> = std::env::args_os()
+        .skip(1)
+        .map(gio::File::for_commandline_arg)
+        .collect();
+    if files.is_empty() {
+        app.activate();
+    } else {
+        app.open(&files, "");
+    }
+    // The calls are asynchronous, they must reach the primary instance before this one exits.
+    if let Some(connection) = app.dbus_connection() {
+        if let Err(e) = connection.flush_sync(Cancellable::NONE) {
+            log::warn!(
+                "Failed to hand the launch over to the primary instance: {}",
+                e
+            );
+        }
+    }
+}
+
+/// The events describing the current state of a window, in the order a new window gets them.
+///
+/// `position` is `None` while the position is unknown.
+fn initial_state_events<T>(
+    id: WindowId,
+    size: (i32, i32),
+    position: Option<(i32, i32)>,
+    focused: bool,
+    scale_factor: f64,
+) -> Vec<Event<'static, T>> {
+    let window_id = RootWindowId(id);
+    let mut events = vec![Event::WindowEvent {
+        window_id,
+        event: WindowEvent::Resized(util::logical_to_physical_size(size, scale_factor)),
+    }];
+    if let Some(position) = position {
+        events.push(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::Moved(util::logical_to_physical_position(position, scale_factor)),
+        });
+    }
+    if focused {
+        events.push(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::Focused(true),
+        });
+    }
+    events
+}
+
+/// The geometry of a window last reported through `Moved` and `Resized`.
+#[derive(Default)]
+struct ReportedGeometry {
+    position: Cell<Option<(i32, i32)>>,
+    size: Cell<Option<(i32, i32)>>,
+    /// Whether the geometry was replayed on startup, the first configure event may report it
+    /// again.
+    replayed: Cell<bool>,
+}
+
+/// Returns the events of a configure event, `position` is `None` while it is frozen.
+///
+/// The geometry a startup replay already reported isn't sent again.
+fn configure_events<T>(
+    id: WindowId,
+    reported: &ReportedGeometry,
+    position: Option<(i32, i32)>,
+    size: (i32, i32),
+    scale_factor: f64,
+) -> Vec<Event<'static, T>> {
+    let window_id = RootWindowId(id);
+    let replayed = reported.replayed.take();
+    let mut events = Vec::new();
+    if let Some(position) = position {
+        if !(replayed && reported.position.get() == Some(position)) {
+            events.push(Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Moved(util::logical_to_physical_position(
+                    position,
+                    scale_factor,
+                )),
+            });
         }
+        reported.position.set(Some(position));
     }
+    if !(replayed && reported.size.get() == Some(size)) {
+        events.push(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::Resized(util::logical_to_physical_size(size, scale_factor)),
+        });
+    }
+    reported.size.set(Some(size));
+    events
+}
+
+/// Returns the events of a window state change, the geometry which was already reported by the
+/// configure events isn't sent again.
+///
+/// A minimized window has no meaningful geometry, the events wait for the window to be restored.
+fn state_change_events<T>(
+    id: WindowId,
+    reported: &ReportedGeometry,
+    iconified: bool,
+    position: Option<(i32, i32)>,
+    size: (i32, i32),
+    scale_factor: f64,
+) -> Vec<Event<'static, T>> {
+    let window_id = RootWindowId(id);
+    let mut events = Vec::new();
+    if iconified {
+        return events;
+    }
+    if let Some(position) = position {
+        if reported.position.replace(Some(position)) != Some(position) {
+            events.push(Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Moved(util::logical_to_physical_position(
+                    position,
+                    scale_factor,
+                )),
+            });
+        }
+    }
+    if reported.size.replace(Some(size)) != Some(size) {
+        events.push(Event::WindowEvent {
+            window_id,
+            event: WindowEvent::Resized(util::logical_to_physical_size(size, scale_factor)),
+        });
+    }
+    events
+}
+
+/// The geometry of a window hidden on close, which it gets back once shown again.
+struct HiddenState {
+    position: (i32, i32),
+    size: (i32, i32),
+    fullscreen: bool,
+}
+
+impl HiddenState {
+    fn new(window: &gtk::Window) -> Self {
+        let fullscreen = window
+            .window()
+            .is_some_and(|gdk_window| gdk_window.state().contains(WindowState::FULLSCREEN));
+        Self {
+            position: window.position(),
+            size: window.size(),
+            fullscreen,
+        }
+    }
+
+    fn restore(self, window: &gtk::Window) {
+        let (x, y) = self.position;
+        window.move_(x, y);
+        if self.fullscreen {
+            window.fullscreen();
+        } else {
+            let (width, height) = self.size;
+            window.resize(width, height);
+        }
+    }
+}
+
+/// Takes the windows waiting for a redraw, each window gets at most one per iteration.
+///
+/// Both `request_redraw` and GTK's draw signal queue a redraw, so the same window is usually
+/// queued more than once.
+fn pending_redraws(draws: &crossbeam_channel::Receiver<WindowId>) -> HashSet<WindowId> {
+    draws.try_iter().collect()
 }
 
 impl<T: 'static> EventLoop<T> {
-    pub(crate) fn new(_attributes: &PlatformSpecificEventLoopAttributes) -> Self {
+    pub(crate) fn new(attributes: &PlatformSpecificEventLoopAttributes) -> Self {
+        if !attributes.any_thread && !util::is_main_thread() {
+            panic!(
+                "Initializing the event loop outside of the main thread is a significant \
+                 cross-platform compatibility hazard. If you absolutely need to create an \
+                 EventLoop on a different thread, you can use the \
+                 `EventLoopBuilderExtUnix::with_any_thread` function."
+            );
+        }
+
+        // GTK attaches its sources to the default main context, off the main thread it has to be
+        // owned by this thread for them to be dispatched here.
         let context = MainContext::default();
-        let app = gtk::Application::new(None, gio::ApplicationFlags::empty());
+        context
+            .with_thread_default(|| Self::new_gtk(&context, attributes.app_id.as_deref()))
+            .expect("The default main context is already owned by another thread")
+    }
+
+    fn new_gtk(context: &MainContext, app_id: Option<&str>) -> Self {
+        let app_id = app_id.filter(|app_id| {
+            let valid = gio::Application::id_is_valid(app_id);
+            if !valid {
+                log::warn!(
+                    "{:?} is not a valid application id, like `org.example.App`, using none",
+                    app_id
+                );
+            }
+            valid
+        });
+        let app = gtk::Application::new(app_id, gio::ApplicationFlags::HANDLES_OPEN);
         let app_ = app.clone();
         let cancellable: Option<&Cancellable> = None;
         app.register(cancellable)
@@ -78,14 +399,10 @@ impl<T: 'static> EventLoop<T> {
         // Create channels for handling events and send StartCause::Init event
         let (event_tx, event_rx) = crossbeam_channel::unbounded();
         let (draw_tx, draw_rx) = crossbeam_channel::unbounded();
-        let event_tx_ = event_tx.clone();
+        let (prioritized_event_tx, prioritized_event_rx) = crossbeam_channel::unbounded();
         let draw_tx_ = draw_tx.clone();
         let user_event_tx = event_tx.clone();
-        app.connect_activate(move |_| {
-            if let Err(e) = event_tx_.send(Event::NewEvents(StartCause::Init)) {
-                log::warn!("Failed to send init event to event channel: {}", e);
-            }
-        });
+        let (wakeup_tx, wakeup_source) = wakeup_channel(context);
 
         // Create event loop window target.
         let (window_requests_tx, window_requests_rx) =
@@ -93,87 +410,220 @@ impl<T: 'static> EventLoop<T> {
         let display = gdk::Display::default()
             .expect("GdkDisplay not found. This usually means `gkt_init` hasn't called yet.");
         let window_target = EventLoopWindowTarget {
-            display,
             app,
             windows: Rc::new(RefCell::new(HashSet::new())),
             window_requests_tx,
             draw_tx: draw_tx_,
+            device_event_filter: Default::default(),
+            system_theme: SystemTheme::new(),
+            idle_sources: Default::default(),
+            futures: Default::default(),
+            wayland_capabilities: util::wayland_capabilities(&display),
+            global_shortcuts: Rc::new(GlobalShortcuts::new(&display)),
+            device_ids: Default::default(),
+            display,
             _marker: std::marker::PhantomData,
         };
 
-        // TODO: Spawn x11/wayland thread to receive Device events.
+        // Assign ids to the devices of the seat and report devices plugged in at runtime.
+        let device_ids = window_target.device_ids.clone();
+        let pressed_inputs: Rc<RefCell<PressedInputs>> = Default::default();
+        if let Some(seat) = window_target.display.default_seat() {
+            let tx_clone = event_tx.clone();
+            let device_ids_ = device_ids.clone();
+            seat.connect_device_added(move |_, device| {
+                let device_id = device_ids_.borrow_mut().insert(device.clone());
+                if let Err(e) = tx_clone.send(Event::DeviceEvent {
+                    device_id,
+                    event: DeviceEvent::Added,
+                }) {
+                    log::warn!("Failed to send device added event to event channel: {}", e);
+                }
+            });
+            let tx_clone = event_tx.clone();
+            let device_ids_ = device_ids.clone();
+            let pressed_inputs_ = pressed_inputs.clone();
+            // A seat losing its pointer or keyboard capability removes the devices too. The device
+            // is forgotten, it gets a new id if it comes back.
+            seat.connect_device_removed(move |_, device| {
+                if let Some(device_id) = device_ids_.borrow_mut().remove(device) {
+                    // The releases of the pressed buttons and keys will never come.
+                    let released = pressed_inputs_.borrow_mut().release_device(device_id);
+                    for event in released {
+                        if let Err(e) = tx_clone.send(event) {
+                            log::warn!("Failed to send release event to event channel: {}", e);
+                        }
+                    }
+                    if let Err(e) = tx_clone.send(Event::DeviceEvent {
+                        device_id,
+                        event: DeviceEvent::Removed,
+                    }) {
+                        log::warn!(
+                            "Failed to send device removed event to event channel: {}",
+                            e
+                        );
+                    }
+                }
+            });
+        }
+
+        let raw_event_filter = device::listen_raw_events(
+            &window_target.display,
+            window_target.app.clone(),
+            window_target.device_event_filter.clone(),
+            device_ids.clone(),
+            event_tx.clone(),
+        );
+
+        // Input method contexts of the windows
+        let ime_contexts: Rc<RefCell<HashMap<WindowId, gtk::IMMulticontext>>> = Default::default();
+
+        // Timeouts clearing the urgency hint of timed user attention requests
+        let attention_timeouts: Rc<RefCell<HashMap<WindowId, glib::SourceId>>> = Default::default();
+
+        // State requests of unmapped windows
+        let pending_state_requests: Rc<RefCell<HashMap<WindowId, Vec<WindowRequest>>>> =
+            Default::default();
+
+        // Geometry of the windows hidden on close, the window manager may place them anew
+        let hidden_states: Rc<RefCell<HashMap<WindowId, HiddenState>>> = Default::default();
+
+        // Video modes to restore once the windows leave exclusive fullscreen
+        let saved_video_modes: Rc<RefCell<SavedVideoModes>> = Default::default();
 
         // Window Request
-        window_requests_rx.attach(Some(&context), move |(id, request)| {
+        //
+        // `Window::new` adds the window to the application before sending any request and the
+        // channel keeps their order, so the requests made before `run` are applied once it starts.
+        let prioritized_tx = prioritized_event_tx.clone();
+        let windows = window_target.windows.clone();
+        let window_requests_source = window_requests_rx.attach(Some(context), move |(id, request)| {
             if let Some(window) = app_.window_by_id(id.0 as u32) {
-                match request {
+                // A panicking request must not take down the dispatch of every later one.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| match request {
                     WindowRequest::Title(title) => window.set_title(&title),
-                    WindowRequest::Position((x, y)) => window.move_(x, y),
-                    WindowRequest::Size((w, h)) => window.resize(w, h),
-                    WindowRequest::SizeConstraints(min, max) => {
-                        util::set_size_constraints(&window, min, max);
+                    WindowRequest::Position((x, y)) => {
+                        // The window is placed there once shown again.
+                        if let Some(hidden) = hidden_states.borrow_mut().get_mut(&id) {
+                            hidden.position = (x, y);
+                        }
+                        window.move_(x, y);
+                    }
+                    WindowRequest::Size((w, h)) => {
+                        if let Some(hidden) = hidden_states.borrow_mut().get_mut(&id) {
+                            hidden.size = (w, h);
+                        }
+                        window.resize(w, h);
+                    }
+                    WindowRequest::SizeConstraints {
+                        min_size,
+                        max_size,
+                        resize_increments,
+                    } => {
+                        util::set_size_constraints(
+                            &window,
+                            min_size,
+                            max_size,
+                            resize_increments,
+                        );
                     }
                     WindowRequest::Visible(visible) => {
                         if visible {
+                            if let Some(hidden) = hidden_states.borrow_mut().remove(&id) {
+                                hidden.restore(&window);
+                            }
                             window.show_all();
                         } else {
                             window.hide();
                         }
                     }
                     WindowRequest::Focus => {
-                        window.present_with_time(gdk_sys::GDK_CURRENT_TIME as _);
+                        if let Some(hidden) = hidden_states.borrow_mut().remove(&id) {
+                            hidden.restore(&window);
+                        }
+                        // Presenting deiconifies the window as well, the children of a window
+                        // created hidden are shown first.
+                        window.show_all();
+                        if window.accepts_focus() {
+                            window.present_with_time(gdk_sys::GDK_CURRENT_TIME as _);
+                        } else {
+                            // Only raised, the window doesn't take the focus.
+                            window.deiconify();
+                            if let Some(gdk_window) = window.window() {
+                                gdk_window.raise();
+                            }
+                        }
                     }
                     WindowRequest::Resizable(resizable) => window.set_resizable(resizable),
                     // WindowRequest::Closable(closable) => window.set_deletable(closable),
-                    WindowRequest::Minimized(minimized) => {
-                        if minimized {
-                            window.iconify();
-                        } else {
-                            window.deiconify();
+                    WindowRequest::EnabledButtons(buttons) => {
+                        window.set_deletable(buttons.contains(WindowButtons::CLOSE));
+
+                        if !window.is_realized() {
+                            window.realize();
+                        }
+                        if let Some(gdk_window) = window.window() {
+                            let mut functions = WMFunction::RESIZE | WMFunction::MOVE;
+                            if buttons.contains(WindowButtons::MINIMIZE) {
+                                functions |= WMFunction::MINIMIZE;
+                            }
+                            if buttons.contains(WindowButtons::MAXIMIZE) {
+                                functions |= WMFunction::MAXIMIZE;
+                            }
+                            if buttons.contains(WindowButtons::CLOSE) {
+                                functions |= WMFunction::CLOSE;
+                            }
+                            gdk_window.set_functions(functions);
                         }
                     }
-                    WindowRequest::Maximized(maximized) => {
-                        if maximized {
-                            window.maximize();
+                    request @ (WindowRequest::Minimized(_)
+                    | WindowRequest::Maximized(_)
+                    | WindowRequest::Fullscreen(_)) => {
+                        // Some window managers drop state changes of unmapped windows, so they are
+                        // applied once the window is mapped.
+                        if window.is_mapped() {
+                            apply_state_request(&window, id, request, &saved_video_modes);
                         } else {
-                            window.unmaximize();
+                            pending_state_requests
+                                .borrow_mut()
+                                .entry(id)
+                                .or_default()
+                                .push(request);
                         }
                     }
+                    // Dragging needs a realized window and a pointer, kiosk or touch-only
+                    // setups might not have one.
                     WindowRequest::DragWindow => {
-                        if let Some(cursor) = window
-                            .display()
-                            .default_seat()
-                            .and_then(|seat| seat.pointer())
-                        {
+                        if let Some(cursor) = util::drag_pointer(&window) {
                             let (_, x, y) = cursor.position();
                             window.begin_move_drag(1, x, y, 0);
                         }
                     }
-                    WindowRequest::Fullscreen(fullscreen) => match fullscreen {
-                        Some(f) => {
-                            let m = match f {
-                                Fullscreen::Exclusive(m) => Some(m.monitor),
-                                Fullscreen::Borderless(Some(m)) => Some(m.monitor),
-                                _ => None,
-                            };
-
-                            if let Some(monitor) = m {
-                                let display = window.display();
-                                let monitors = display.n_monitors();
-                                for i in 0..monitors {
-                                    let m = display.monitor(i).unwrap();
-                                    if m == monitor {
-                                        let screen = display.default_screen();
-                                        window.fullscreen_on_monitor(&screen, i);
-                                    }
-                                }
-                            } else {
-                                window.fullscreen();
-                            }
+                    WindowRequest::DragResizeWindow(direction) => {
+                        if let Some(cursor) = util::drag_pointer(&window) {
+                            let (_, x, y) = cursor.position();
+                            window.begin_resize_drag(direction.into(), 1, x, y, 0);
                         }
-                        None => window.unfullscreen(),
-                    },
-                    WindowRequest::Decorations(decorations) => window.set_decorated(decorations),
+                    }
+                    WindowRequest::Decorations(decorations, position_frozen) => {
+                        // Reparenting window managers move the window while they swap the frame,
+                        // so keep the position from before and restore it once that settled.
+                        if window.display().backend().is_x11()
+                            && window.is_realized()
+                            && decorations != window.is_decorated()
+                            && !position_frozen.swap(true, Ordering::AcqRel)
+                        {
+                            let (x, y) = window.position();
+                            let window = window.clone();
+                            glib::timeout_add_local_once(DECORATIONS_SETTLE_TIME, move || {
+                                position_frozen.store(false, Ordering::Release);
+                                window.move_(x, y);
+                            });
+                        }
+                        // On a realized X11 window this only changes `_MOTIF_WM_HINTS` of the
+                        // XID, the window is not remapped.
+                        window.set_decorated(decorations);
+                    }
                     WindowRequest::AlwaysOnBottom(always_on_bottom) => {
                         window.set_keep_below(always_on_bottom)
                     }
@@ -186,71 +636,65 @@ impl<T: 'static> EventLoop<T> {
                         }
                     }
                     WindowRequest::UserAttention(request_type) => {
+                        if let Some(source) = attention_timeouts.borrow_mut().remove(&id) {
+                            source.remove();
+                        }
                         window.set_urgency_hint(request_type.is_some())
                     }
+                    WindowRequest::UserAttentionFor(duration) => {
+                        window.set_urgency_hint(true);
+                        let attention_timeouts_ = attention_timeouts.clone();
+                        // The timeout must not keep a closed window alive.
+                        let window = window.downgrade();
+                        let source = glib::timeout_add_local_once(duration, move || {
+                            attention_timeouts_.borrow_mut().remove(&id);
+                            if let Some(window) = window.upgrade() {
+                                window.set_urgency_hint(false);
+                            }
+                        });
+                        if let Some(source) = attention_timeouts.borrow_mut().insert(id, source) {
+                            source.remove();
+                        }
+                    }
                     WindowRequest::SetSkipTaskbar(skip) => {
                         window.set_skip_taskbar_hint(skip);
-                        window.set_skip_pager_hint(skip)
-                    }
-                    // WindowRequest::SetVisibleOnAllWorkspaces(visible) => {
-                    //     if visible {
-                    //         window.stick();
-                    //     } else {
-                    //         window.unstick();
-                    //     }
-                    // }
-                    WindowRequest::CursorIcon(cursor) => {
-                        if let Some(gdk_window) = window.window() {
-                            let display = window.display();
-                            match cursor {
-                                Some(cr) => gdk_window.set_cursor(
-                                    Cursor::from_name(
-                                        &display,
-                                        match cr {
-                                            CursorIcon::Crosshair => "crosshair",
-                                            CursorIcon::Hand => "pointer",
-                                            CursorIcon::Arrow => "arrow",
-                                            CursorIcon::Move => "move",
-                                            CursorIcon::Text => "text",
-                                            CursorIcon::Wait => "wait",
-                                            CursorIcon::Help => "help",
-                                            CursorIcon::Progress => "progress",
-                                            CursorIcon::NotAllowed => "not-allowed",
-                                            CursorIcon::ContextMenu => "context-menu",
-                                            CursorIcon::Cell => "cell",
-                                            CursorIcon::VerticalText => "vertical-text",
-                                            CursorIcon::Alias => "alias",
-                                            CursorIcon::Copy => "copy",
-                                            CursorIcon::NoDrop => "no-drop",
-                                            CursorIcon::Grab => "grab",
-                                            CursorIcon::Grabbing => "grabbing",
-                                            CursorIcon::AllScroll => "all-scroll",
-                                            CursorIcon::ZoomIn => "zoom-in",
-                                            CursorIcon::ZoomOut => "zoom-out",
-                                            CursorIcon::EResize => "e-resize",
-                                            CursorIcon::NResize => "n-resize",
-                                            CursorIcon::NeResize => "ne-resize",
-                                            CursorIcon::NwResize => "nw-resize",
-                                            CursorIcon::SResize => "s-resize",
-                                            CursorIcon::SeResize => "se-resize",
-                                            CursorIcon::SwResize => "sw-resize",
-                                            CursorIcon::WResize => "w-resize",
-                                            CursorIcon::EwResize => "ew-resize",
-                                            CursorIcon::NsResize => "ns-resize",
-                                            CursorIcon::NeswResize => "nesw-resize",
-                                            CursorIcon::NwseResize => "nwse-resize",
-                                            CursorIcon::ColResize => "col-resize",
-                                            CursorIcon::RowResize => "row-resize",
-                                            CursorIcon::Default => "default",
-                                        },
-                                    )
-                                    .as_ref(),
-                                ),
-                                None => gdk_window.set_cursor(
-                                    Cursor::for_display(&display, CursorType::BlankCursor).as_ref(),
-                                ),
+                        window.set_skip_pager_hint(skip);
+                    }
+                    WindowRequest::Modal(modal) => window.set_modal(modal),
+                    WindowRequest::SetVisibleOnAllWorkspaces(visible) => {
+                        if visible {
+                            window.stick();
+                        } else {
+                            window.unstick();
+                        }
+                    }
+                    WindowRequest::Transparent {
+                        rgba_visual,
+                        app_paintable,
+                    } => {
+                        window.set_app_paintable(app_paintable);
+                        let visual = GtkWindowExt::screen(&window).and_then(|screen| {
+                            if rgba_visual {
+                                screen.rgba_visual()
+                            } else {
+                                screen.system_visual()
+                            }
+                        });
+                        if visual.is_some() && window.visual() != visual {
+                            // The visual is picked when the window is realized. Switching it
+                            // afterwards would replace the `GdkWindow` and its raw handle.
+                            if window.is_realized() {
+                                log::warn!(
+                                    "The visual of a realized window can't be changed, only its background clearing is"
+                                );
+                            } else {
+                                window.set_visual(visual.as_ref());
                             }
-                        };
+                        }
+                        window.queue_draw();
+                    }
+                    WindowRequest::CursorIcon(cursor) => {
+                        apply_cursor(&window, cursor.cursor(), cursor.size())
                     }
                     WindowRequest::CursorPosition((x, y)) => {
                         if let Some(cursor) = window
@@ -263,22 +707,122 @@ impl<T: 'static> EventLoop<T> {
                             }
                         }
                     }
-                    WindowRequest::CursorIgnoreEvents(ignore) => {
-                        if ignore {
-                            let empty_region =
-                                Region::create_rectangle(&RectangleInt::new(0, 0, 1, 1));
-                            window.window().unwrap().input_shape_combine_region(
-                                &empty_region,
-                                0,
-                                0,
-                            );
-                        } else {
+                    WindowRequest::CursorHitTest(hittest) => {
+                        if hittest {
                             window.input_shape_combine_region(None)
-                        };
+                        } else {
+                            // An empty input shape lets every click through.
+                            let empty_region = Region::create();
+                            window.input_shape_combine_region(Some(&empty_region));
+                        }
+                    }
+                    WindowRequest::CursorGrab(mode) => {
+                        if let Some(seat) = window.display().default_seat() {
+                            match (mode, window.window()) {
+                                (CursorGrabMode::None, _) => seat.ungrab(),
+                                (_, Some(gdk_window)) => {
+                                    let status = seat.grab(
+                                        &gdk_window,
+                                        SeatCapabilities::POINTER,
+                                        true,
+                                        None,
+                                        None,
+                                        None,
+                                    );
+                                    if status != GrabStatus::Success {
+                                        log::warn!("Fail to grab the cursor: {:?}", status);
+                                    }
+                                }
+                                (_, None) => {
+                                    log::warn!("Fail to grab the cursor of an unrealized window")
+                                }
+                            }
+                        }
+                    }
+                    WindowRequest::ImeAllowed(allowed) => {
+                        // Windows without the keyboard handlers have no input method.
+                        if let Some(ime) = ime_contexts.borrow().get(&id) {
+                            if allowed {
+                                ime.focus_in();
+                            } else {
+                                ime.reset();
+                                ime.focus_out();
+                            }
+                            if let Err(e) = event_tx.send(Event::WindowEvent {
+                                window_id: RootWindowId(id),
+                                event: WindowEvent::Ime(if allowed {
+                                    Ime::Enabled
+                                } else {
+                                    Ime::Disabled
+                                }),
+                            }) {
+                                log::warn!("Failed to send IME event to event channel: {}", e);
+                            }
+                        }
+                    }
+                    WindowRequest::ImePosition((x, y)) => {
+                        if let Some(ime) = ime_contexts.borrow().get(&id) {
+                            ime.set_cursor_location(&gdk::Rectangle::new(x, y, 0, 0));
+                        }
+                    }
+                    WindowRequest::ImePurpose(purpose) => {
+                        if let Some(ime) = ime_contexts.borrow().get(&id) {
+                            ime.set_input_purpose(match purpose {
+                                ImePurpose::Normal => InputPurpose::FreeForm,
+                                ImePurpose::Password => InputPurpose::Password,
+                                ImePurpose::Terminal => InputPurpose::Terminal,
+                            });
+                        }
+                    }
+                    WindowRequest::ResizeToContent => {
+                        let (_, natural) = window.preferred_size();
+                        window.resize(natural.width, natural.height);
+                    }
+                    // `GtkWindow::close` would emit `delete-event` again, which only reports
+                    // `CloseRequested`.
+                    WindowRequest::Close => unsafe { window.destroy() },
+                    WindowRequest::Beep => match window.window() {
+                        Some(gdk_window) => gdk_window.beep(),
+                        // Fall back to the display bell until the window is realized.
+                        None => window.display().beep(),
+                    },
+                    WindowRequest::CompositorBypass(bypass, applied) => match window.window() {
+                        Some(gdk_window) => {
+                            let is_set = util::set_compositor_bypass(&gdk_window, bypass);
+                            applied.store(is_set && bypass, Ordering::Release);
+                        }
+                        // Set once the window is realized, which only works on X11.
+                        None => {
+                            let is_x11 = window.display().backend().is_x11();
+                            applied.store(is_x11 && bypass, Ordering::Release);
+                        }
+                    },
+                    WindowRequest::AllowTearing(allow, tearing_control) => {
+                        tearing_control.set_allowed(&window, allow)
+                    }
+                    WindowRequest::ContentProtected(protected) => {
+                        // X11 has no notion of it and no Wayland protocol lets a client keep
+                        // others from capturing its surfaces, so there's nothing to request.
+                        if protected {
+                            static WARN_ONCE: Once = Once::new();
+                            WARN_ONCE.call_once(|| {
+                                log::warn!(
+                                    "Content protection is unsupported on X11 and Wayland, \
+                                     the window can still be captured"
+                                )
+                            });
+                        }
                     }
                     // WindowRequest::ProgressBarState(_) => unreachable!(),
                     WindowRequest::WireUpEvents {
                         transparent,
+                        cursor_locked,
+                        ime_allowed,
+                        position_frozen,
+                        hide_on_close,
+                        cursor,
+                        cursor_moved,
+                        default_handlers,
                     } => {
                         window.add_events(
                             EventMask::POINTER_MOTION_MASK
@@ -287,142 +831,150 @@ impl<T: 'static> EventLoop<T> {
                                 | EventMask::TOUCH_MASK
                                 | EventMask::STRUCTURE_MASK
                                 | EventMask::FOCUS_CHANGE_MASK
-                                | EventMask::SCROLL_MASK,
+                                | EventMask::SCROLL_MASK
+                                | EventMask::VISIBILITY_NOTIFY_MASK
+                                | EventMask::TOUCHPAD_GESTURE_MASK,
                         );
 
-                        // Allow resizing unmaximized borderless window
-                        window.connect_motion_notify_event(|window, event| {
-                            if !window.is_decorated()
-                                && window.is_resizable()
-                                && !window.is_maximized()
-                            {
-                                if let Some(window) = window.window() {
-                                    let (cx, cy) = event.root();
-                                    let edge = hit_test(&window, cx, cy);
-                                    window.set_cursor(
-                                        Cursor::from_name(
-                                            &window.display(),
-                                            match edge {
-                                                WindowEdge::North => "n-resize",
-                                                WindowEdge::South => "s-resize",
-                                                WindowEdge::East => "e-resize",
-                                                WindowEdge::West => "w-resize",
-                                                WindowEdge::NorthWest => "nw-resize",
-                                                WindowEdge::NorthEast => "ne-resize",
-                                                WindowEdge::SouthEast => "se-resize",
-                                                WindowEdge::SouthWest => "sw-resize",
-                                                _ => "default",
-                                            },
-                                        )
-                                        .as_ref(),
-                                    );
-                                }
-                            }
+                        // GTK resets the cursor of a window whose `GdkWindow` is created anew, e.g.
+                        // for another visual, and after some internal grabs.
+                        let cursor_ = cursor.clone();
+                        window.connect_realize(move |window| {
+                            apply_cursor(window, cursor_.get().cursor(), cursor_.get().size());
+                        });
+                        let cursor_ = cursor.clone();
+                        window.connect_map_event(move |window, _| {
+                            apply_cursor(window, cursor_.get().cursor(), cursor_.get().size());
                             glib::Propagation::Proceed
                         });
-                        window.connect_button_press_event(|window, event| {
-                            if !window.is_decorated()
-                                && window.is_resizable()
-                                && event.button() == 1
-                            {
-                                if let Some(window) = window.window() {
-                                    let (cx, cy) = event.root();
-                                    let result = hit_test(&window, cx, cy);
-
-                                    // Ignore the `__Unknown` variant so the window receives the click correctly if it is not on the edges.
-                                    match result {
-                                        WindowEdge::__Unknown(_) => (),
-                                        _ => {
-                                            // FIXME: calling `window.begin_resize_drag` uses the default cursor, it should show a resizing cursor instead
-                                            window.begin_resize_drag(
-                                                result,
-                                                1,
-                                                cx as i32,
-                                                cy as i32,
-                                                event.time(),
-                                            )
-                                        }
-                                    }
-                                }
-                            }
-
+                        let cursor_ = cursor.clone();
+                        window.connect_enter_notify_event(move |window, _| {
+                            apply_cursor(window, cursor_.get().cursor(), cursor_.get().size());
                             glib::Propagation::Proceed
                         });
-                        window.connect_touch_event(|window, event| {
-                            if !window.is_decorated() && window.is_resizable() {
-                                if let Some(window) = window.window() {
-                                    if let Some((cx, cy)) = event.root_coords() {
-                                        if let Some(device) = event.device() {
-                                            let result = hit_test(&window, cx, cy);
-
-                                            // Ignore the `__Unknown` variant so the window receives the click correctly if it is not on the edges.
-                                            match result {
-                                                WindowEdge::__Unknown(_) => (),
-                                                _ => window.begin_resize_drag_for_device(
-                                                    result,
-                                                    &device,
-                                                    0,
-                                                    cx as i32,
-                                                    cy as i32,
-                                                    event.time(),
-                                                ),
-                                            }
-                                        }
-                                    }
+                        // The themed cursors are loaded for the scale factor of the window.
+                        let cursor_ = cursor.clone();
+                        window.connect_scale_factor_notify(move |window| {
+                            apply_cursor(window, cursor_.get().cursor(), cursor_.get().size());
+                        });
+
+                        // Allow resizing unmaximized borderless window by touch
+                        if default_handlers.contains(DefaultHandlers::BORDERLESS_RESIZE) {
+                            let edge_touch = RefCell::new(None);
+                            window.connect_touch_event(move |window, event| {
+                                if touch_resize_edge(window, event, &edge_touch) {
+                                    glib::Propagation::Stop
+                                } else {
+                                    glib::Propagation::Proceed
                                 }
-                            }
+                            });
+                        }
 
+                        let pending_state_requests_ = pending_state_requests.clone();
+                        let hidden_states_ = hidden_states.clone();
+                        let attention_timeouts_ = attention_timeouts.clone();
+                        window.connect_destroy(move |_| {
+                            pending_state_requests_.borrow_mut().remove(&id);
+                            hidden_states_.borrow_mut().remove(&id);
+                            if let Some(source) = attention_timeouts_.borrow_mut().remove(&id) {
+                                source.remove();
+                            }
+                        });
+                        let pending_state_requests = pending_state_requests.clone();
+                        let saved_video_modes_ = saved_video_modes.clone();
+                        window.connect_map_event(move |window, _| {
+                            let requests = pending_state_requests.borrow_mut().remove(&id);
+                            for request in requests.into_iter().flatten() {
+                                apply_state_request(
+                                    window.upcast_ref(),
+                                    id,
+                                    request,
+                                    &saved_video_modes_,
+                                );
+                            }
                             glib::Propagation::Proceed
                         });
 
-                        let tx_clone = event_tx.clone();
-                        window.connect_delete_event(move |_, _| {
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::CloseRequested,
-                            }) {
-                                log::warn!(
-                                    "Failed to send window close event to event channel: {}",
-                                    e
-                                );
+                        // The video mode must not outlive the window, nor its exclusive fullscreen
+                        // when the window manager ends it.
+                        let saved_video_modes_ = saved_video_modes.clone();
+                        window.connect_window_state_event(move |_, event| {
+                            if event.changed_mask().contains(WindowState::FULLSCREEN)
+                                && !event.new_window_state().contains(WindowState::FULLSCREEN)
+                            {
+                                saved_video_modes_.borrow_mut().restore(id);
                             }
-                            glib::Propagation::Stop
+                            glib::Propagation::Proceed
                         });
+                        let saved_video_modes_ = saved_video_modes.clone();
+                        window.connect_destroy(move |_| {
+                            saved_video_modes_.borrow_mut().restore(id);
+                        });
+                        let pressed_inputs_ = pressed_inputs.clone();
+                        window.connect_destroy(move |_| {
+                            pressed_inputs_.borrow_mut().remove_window(id);
+                        });
+
+                        if default_handlers.contains(DefaultHandlers::CLOSE) {
+                            // The app must react to it right away, it jumps ahead of the queued
+                            // events.
+                            let tx_clone = prioritized_tx.clone();
+                            let hidden_states = hidden_states.clone();
+                            window.connect_delete_event(move |window, _| {
+                                if hide_on_close.load(Ordering::Acquire) {
+                                    hidden_states
+                                        .borrow_mut()
+                                        .insert(id, HiddenState::new(window.upcast_ref()));
+                                    window.hide();
+                                    return glib::Propagation::Stop;
+                                }
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::CloseRequested,
+                                }) {
+                                    log::warn!(
+                                        "Failed to send window close event to event channel: {}",
+                                        e
+                                    );
+                                }
+                                glib::Propagation::Stop
+                            });
+                        }
 
+                        let reported_geometry: Rc<ReportedGeometry> = Default::default();
+                        let replay_position_frozen = position_frozen.clone();
+                        let state_position_frozen = position_frozen.clone();
+                        let reported_geometry_ = reported_geometry.clone();
                         let tx_clone = event_tx.clone();
                         window.connect_configure_event(move |window, event| {
-                            let scale_factor = window.scale_factor();
-
-                            let (x, y) = event.position();
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::Moved(
-                                    LogicalPosition::new(x, y).to_physical(scale_factor as f64),
-                                ),
-                            }) {
-                                log::warn!(
-                                    "Failed to send window moved event to event channel: {}",
-                                    e
-                                );
-                            }
-
+                            let position =
+                                (!position_frozen.load(Ordering::Acquire)).then(|| event.position());
                             let (w, h) = event.size();
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::Resized(
-                                    LogicalSize::new(w, h).to_physical(scale_factor as f64),
-                                ),
-                            }) {
-                                log::warn!(
-                                    "Failed to send window resized event to event channel: {}",
-                                    e
-                                );
+                            for event in configure_events(
+                                id,
+                                &reported_geometry_,
+                                position,
+                                (w as i32, h as i32),
+                                window.scale_factor() as f64,
+                            ) {
+                                if let Err(e) = tx_clone.send(event) {
+                                    log::warn!(
+                                        "Failed to send window geometry event to event channel: {}",
+                                        e
+                                    );
+                                }
                             }
                             false
                         });
 
                         let tx_clone = event_tx.clone();
-                        window.connect_focus_in_event(move |_, _| {
+                        let attention_timeouts = attention_timeouts.clone();
+                        window.connect_focus_in_event(move |window, _| {
+                            // The attention of a timed request is no longer needed.
+                            if let Some(source) = attention_timeouts.borrow_mut().remove(&id) {
+                                source.remove();
+                                window.set_urgency_hint(false);
+                            }
                             if let Err(e) = tx_clone.send(Event::WindowEvent {
                                 window_id: RootWindowId(id),
                                 event: WindowEvent::Focused(true),
@@ -449,6 +1001,46 @@ impl<T: 'static> EventLoop<T> {
                             glib::Propagation::Proceed
                         });
 
+                        // Both settings decide the theme, only send actual changes.
+                        let tx_clone = event_tx.clone();
+                        let theme = Cell::new(current_theme());
+                        let send_theme_changed = Rc::new(move || {
+                            let new_theme = current_theme();
+                            if theme.replace(new_theme) != new_theme {
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::ThemeChanged(new_theme),
+                                }) {
+                                    log::warn!(
+                                        "Failed to send theme changed event to event channel: {}",
+                                        e
+                                    );
+                                }
+                            }
+                        });
+                        let theme_handlers = Settings::default().map(|settings| {
+                            let send_theme_changed_ = send_theme_changed.clone();
+                            let handlers = [
+                                settings.connect_gtk_theme_name_notify(move |_| {
+                                    send_theme_changed_()
+                                }),
+                                settings.connect_gtk_application_prefer_dark_theme_notify(
+                                    move |_| send_theme_changed(),
+                                ),
+                            ];
+                            (settings, handlers)
+                        });
+                        // The settings outlive the window, disconnect the handlers with it.
+                        let theme_handlers = RefCell::new(theme_handlers);
+                        window.connect_destroy(move |_| {
+                            if let Some((settings, handlers)) = theme_handlers.take() {
+                                for handler in handlers {
+                                    settings.disconnect(handler);
+                                }
+                            }
+                        });
+
+                        // `Destroyed` comes after the events of the window which are still queued.
                         let tx_clone = event_tx.clone();
                         window.connect_destroy(move |_| {
                             if let Err(e) = tx_clone.send(Event::WindowEvent {
@@ -462,251 +1054,614 @@ impl<T: 'static> EventLoop<T> {
                             }
                         });
 
-                        let tx_clone = event_tx.clone();
-                        window.connect_enter_notify_event(move |_, _| {
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::CursorEntered {
-                                    device_id: DEVICE_ID,
-                                },
-                            }) {
-                                log::warn!(
-                                    "Failed to send cursor entered event to event channel: {}",
-                                    e
-                                );
+                        // Modifiers as last reported, shared by the pointer, key and focus handlers
+                        // so whichever of them sees a change first emits `ModifiersChanged`.
+                        let modifiers = Rc::new(Cell::new(ModifiersState::empty()));
+                        let tx_modifiers = event_tx.clone();
+                        let update_modifiers = Rc::new(move |new_mods: ModifiersState| {
+                            if modifiers.replace(new_mods) != new_mods {
+                                if let Err(e) = tx_modifiers.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::ModifiersChanged(new_mods),
+                                }) {
+                                    log::warn!(
+                                        "Failed to send modifiers changed event to event channel: {}",
+                                        e
+                                    );
+                                }
                             }
-                            glib::Propagation::Proceed
                         });
 
+                        // Each pointer signal has a single handler doing the cursor lock, the
+                        // resizing of borderless windows and the events, in this order.
+                        let borderless_resize =
+                            default_handlers.contains(DefaultHandlers::BORDERLESS_RESIZE);
+                        let pointer = default_handlers.contains(DefaultHandlers::POINTER);
+                        // The resize only starts once the pointer moved past the drag threshold,
+                        // like native GTK widgets do.
+                        let pending_resize: Rc<Cell<Option<(WindowEdge, f64, f64)>>> =
+                            Default::default();
+                        // The last position reported by `CursorMoved`, a click elsewhere reports
+                        // its own position first.
+                        let last_cursor: Rc<Cell<Option<PhysicalPosition<f64>>>> =
+                            Default::default();
+
                         let tx_clone = event_tx.clone();
+                        let device_ids_ = device_ids.clone();
+                        let update_modifiers_ = update_modifiers.clone();
+                        let pending_resize_ = pending_resize.clone();
+                        let last_cursor_ = last_cursor.clone();
+                        // Only report axes whose value changed since the last motion.
+                        let last_axes = RefCell::new([None; MOTION_AXES.len()]);
                         window.connect_motion_notify_event(move |window, motion| {
-                            if let Some(cursor) = motion.device() {
-                              let scale_factor = window.scale_factor();
-                              let (_, x, y) = cursor.window_at_position();
-                              if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::CursorMoved {
-                                  position: LogicalPosition::new(x, y).to_physical(scale_factor as f64),
-                                  device_id: DEVICE_ID,
-                                  // this field is depracted so it is fine to pass empty state
-                                  modifiers: ModifiersState::empty(),
-                                },
-                              }) {
-                                log::warn!("Failed to send cursor moved event to event channel: {}", e);
-                              }
-                          }
-                          glib::Propagation::Proceed
-                        });
+                            if cursor_locked.load(Ordering::Acquire) {
+                                warp_to_center(window, motion);
+                            }
+                            if borderless_resize {
+                                update_resize_cursor(window, motion, &cursor);
+                                start_pending_resize(window, motion, &pending_resize_);
+                            }
+                            if !pointer {
+                                return glib::Propagation::Proceed;
+                            }
 
-                        let tx_clone = event_tx.clone();
-                        window.connect_leave_notify_event(move |_, _| {
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::CursorLeft {
-                                    device_id: DEVICE_ID,
-                                },
-                            }) {
-                                log::warn!(
-                                    "Failed to send cursor left event to event channel: {}",
-                                    e
-                                );
+                            let device_id = device_ids_.borrow_mut().id(motion);
+                            // Modifiers can change while another application has the focus.
+                            let modifiers = keyboard::modifiers_from_state(motion.state());
+                            update_modifiers_(modifiers);
+                            let position = window
+                                .window()
+                                .filter(|_| cursor_moved)
+                                .map(|gdk_window| {
+                                    physical_pointer_position(
+                                        window,
+                                        &gdk_window,
+                                        motion.window(),
+                                        motion.position(),
+                                        motion.root(),
+                                    )
+                                });
+                            if let Some(position) = position {
+                                last_cursor_.set(Some(position));
+                                send_cursor_moved(&tx_clone, id, position, device_id, modifiers);
+                            }
+
+                            let mut last_axes = last_axes.borrow_mut();
+                            for (i, (axis_use, axis)) in MOTION_AXES.iter().enumerate() {
+                                let value = motion.axis(*axis_use);
+                                if value.is_none() || value == last_axes[i] {
+                                    continue;
+                                }
+                                last_axes[i] = value;
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::AxisMotion {
+                                        device_id,
+                                        axis: *axis,
+                                        value: value.unwrap_or_default(),
+                                    },
+                                }) {
+                                    log::warn!(
+                                        "Failed to send axis motion event to event channel: {}",
+                                        e
+                                    );
+                                }
                             }
                             glib::Propagation::Proceed
                         });
 
                         let tx_clone = event_tx.clone();
-                        window.connect_button_press_event(move |_, event| {
-                            let button = event.button();
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::MouseInput {
-                                    button: match button {
-                                        1 => MouseButton::Left,
-                                        2 => MouseButton::Middle,
-                                        3 => MouseButton::Right,
-                                        _ => MouseButton::Other(button as u16),
-                                    },
-                                    state: ElementState::Pressed,
-                                    device_id: DEVICE_ID,
-                                    // this field is depracted so it is fine to pass empty state
-                                    modifiers: ModifiersState::empty(),
-                                },
-                            }) {
-                                log::warn!(
-                                    "Failed to send mouse input preseed event to event channel: {}",
-                                    e
-                                );
+                        let device_ids_ = device_ids.clone();
+                        let update_modifiers_ = update_modifiers.clone();
+                        let pending_resize_ = pending_resize.clone();
+                        let last_cursor_ = last_cursor.clone();
+                        let pressed_inputs_ = pressed_inputs.clone();
+                        window.connect_button_press_event(move |window, event| {
+                            // Presses on the edges belong to the resize border, like they would
+                            // to a decoration, and never reach the app.
+                            if borderless_resize && press_resize_edge(window, event, &pending_resize_)
+                            {
+                                return glib::Propagation::Stop;
                             }
+                            if !pointer {
+                                return glib::Propagation::Proceed;
+                            }
+
+                            let modifiers = keyboard::modifiers_from_state(event.state());
+                            update_modifiers_(modifiers);
+                            let device_id = device_ids_.borrow_mut().id(event);
+                            let button = mouse_button(event.button());
+                            pressed_inputs_.borrow_mut().button(
+                                id,
+                                device_id,
+                                button,
+                                ElementState::Pressed,
+                            );
+                            let position = cursor_moved
+                                .then(|| button_position(window, event))
+                                .flatten();
+                            send_mouse_input(
+                                &tx_clone,
+                                id,
+                                &last_cursor_,
+                                position,
+                                device_id,
+                                button,
+                                ElementState::Pressed,
+                                modifiers,
+                            );
                             glib::Propagation::Proceed
                         });
 
                         let tx_clone = event_tx.clone();
-                        window.connect_button_release_event(move |_, event| {
-                            let button = event.button();
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::MouseInput {
-                                    button: match button {
-                                        1 => MouseButton::Left,
-                                        2 => MouseButton::Middle,
-                                        3 => MouseButton::Right,
-                                        _ => MouseButton::Other(button as u16),
-                                    },
-                                    state: ElementState::Released,
-                                    device_id: DEVICE_ID,
-                                    // this field is depracted so it is fine to pass empty state
-                                    modifiers: ModifiersState::empty(),
-                                },
-                            }) {
-                                log::warn!(
-                                    "Failed to send mouse input released event to event channel: {}",
-                                    e
-                                );
+                        let device_ids_ = device_ids.clone();
+                        let update_modifiers_ = update_modifiers.clone();
+                        let pressed_inputs_ = pressed_inputs.clone();
+                        window.connect_button_release_event(move |window, event| {
+                            // Swallow the release of a press which was consumed above, so the app
+                            // never sees an unmatched `Released`.
+                            if event.button() == 1 && pending_resize.take().is_some() {
+                                return glib::Propagation::Stop;
                             }
+                            if !pointer {
+                                return glib::Propagation::Proceed;
+                            }
+
+                            let modifiers = keyboard::modifiers_from_state(event.state());
+                            update_modifiers_(modifiers);
+                            let device_id = device_ids_.borrow_mut().id(event);
+                            let button = mouse_button(event.button());
+                            pressed_inputs_.borrow_mut().button(
+                                id,
+                                device_id,
+                                button,
+                                ElementState::Released,
+                            );
+                            let position = cursor_moved
+                                .then(|| button_position(window, event))
+                                .flatten();
+                            send_mouse_input(
+                                &tx_clone,
+                                id,
+                                &last_cursor,
+                                position,
+                                device_id,
+                                button,
+                                ElementState::Released,
+                                modifiers,
+                            );
                             glib::Propagation::Proceed
                         });
 
+                        // The releases of a broken grab go to another window or client.
                         let tx_clone = event_tx.clone();
-                        window.connect_scroll_event(move |_, event| {
-                            let (x, y) = event.delta();
-                            if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                window_id: RootWindowId(id),
-                                event: WindowEvent::MouseWheel {
-                                    device_id: DEVICE_ID,
-                                    delta: MouseScrollDelta::LineDelta(-x as f32, -y as f32),
-                                    phase: match event.direction() {
-                                        ScrollDirection::Smooth => TouchPhase::Moved,
-                                        _ => TouchPhase::Ended,
-                                    },
-                                    modifiers: ModifiersState::empty(),
-                                },
-                            }) {
-                                log::warn!("Failed to send scroll event to event channel: {}", e);
+                        let pressed_inputs_ = pressed_inputs.clone();
+                        window.connect_grab_broken_event(move |_, event| {
+                            let released = pressed_inputs_
+                                .borrow_mut()
+                                .release_window(id, event.is_keyboard());
+                            for event in released {
+                                if let Err(e) = tx_clone.send(event) {
+                                    log::warn!(
+                                        "Failed to send release event to event channel: {}",
+                                        e
+                                    );
+                                }
                             }
                             glib::Propagation::Proceed
                         });
 
+                        if pointer {
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            window.connect_enter_notify_event(move |_, event| {
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::CursorEntered {
+                                        device_id: device_ids_.borrow_mut().id(event),
+                                    },
+                                }) {
+                                    log::warn!(
+                                        "Failed to send cursor entered event to event channel: {}",
+                                        e
+                                    );
+                                }
+                                glib::Propagation::Proceed
+                            });
+
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            window.connect_leave_notify_event(move |_, event| {
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::CursorLeft {
+                                        device_id: device_ids_.borrow_mut().id(event),
+                                    },
+                                }) {
+                                    log::warn!(
+                                        "Failed to send cursor left event to event channel: {}",
+                                        e
+                                    );
+                                }
+                                glib::Propagation::Proceed
+                            });
+
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            // Smooth scrolling is a gesture, which ends with a stop event.
+                            let scrolling = Cell::new(false);
+                            let update_modifiers_ = update_modifiers.clone();
+                            window.connect_scroll_event(move |window, event| {
+                                let modifiers = keyboard::modifiers_from_state(event.state());
+                                update_modifiers_(modifiers);
+                                let (delta, phase) = match event.direction() {
+                                    ScrollDirection::Smooth => {
+                                        let phase = if event.is_stop() {
+                                            scrolling.set(false);
+                                            TouchPhase::Ended
+                                        } else if scrolling.replace(true) {
+                                            TouchPhase::Moved
+                                        } else {
+                                            TouchPhase::Started
+                                        };
+                                        let delta = util::smooth_scroll_delta(
+                                            event.delta(),
+                                            window.scale_factor() as f64,
+                                        );
+                                        (MouseScrollDelta::PixelDelta(delta), phase)
+                                    }
+                                    direction => {
+                                        let (x, y) = match direction {
+                                            ScrollDirection::Up => (0.0, 1.0),
+                                            ScrollDirection::Down => (0.0, -1.0),
+                                            ScrollDirection::Left => (1.0, 0.0),
+                                            ScrollDirection::Right => (-1.0, 0.0),
+                                            _ => return glib::Propagation::Proceed,
+                                        };
+                                        (MouseScrollDelta::LineDelta(x, y), TouchPhase::Moved)
+                                    }
+                                };
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::MouseWheel {
+                                        device_id: device_ids_.borrow_mut().id(event),
+                                        delta,
+                                        phase,
+                                        modifiers,
+                                    },
+                                }) {
+                                    log::warn!("Failed to send scroll event to event channel: {}", e);
+                                }
+                                glib::Propagation::Proceed
+                            });
+
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            window.connect_touch_event(move |window, event| {
+                                let phase = match event.event_type() {
+                                    EventType::TouchBegin => TouchPhase::Started,
+                                    EventType::TouchUpdate => TouchPhase::Moved,
+                                    EventType::TouchEnd => TouchPhase::Ended,
+                                    EventType::TouchCancel => TouchPhase::Cancelled,
+                                    _ => return glib::Propagation::Proceed,
+                                };
+                                // Sequences are unique among the ongoing touches.
+                                let touch_id = event.event_sequence().map_or(0, |sequence| {
+                                    let mut hasher = DefaultHasher::new();
+                                    sequence.hash(&mut hasher);
+                                    hasher.finish()
+                                });
+                                let (x, y) = event.coords().unwrap_or_default();
+                                let scale_factor = window.scale_factor() as f64;
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::Touch(Touch {
+                                        device_id: device_ids_.borrow_mut().id(event),
+                                        phase,
+                                        location: LogicalPosition::new(x, y).to_physical(scale_factor),
+                                        force: event.axis(AxisUse::Pressure).map(Force::Normalized),
+                                        id: touch_id,
+                                    }),
+                                }) {
+                                    log::warn!("Failed to send touch event to event channel: {}", e);
+                                }
+                                glib::Propagation::Proceed
+                            });
+
+                            // GDK reports the scale relative to the start of the pinch, winit wants
+                            // the change since the last event.
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            let last_scale = Cell::new(None);
+                            window.connect_event(move |_, event| {
+                                let pinch = match event.downcast_ref::<EventTouchpadPinch>() {
+                                    Some(pinch) => pinch,
+                                    None => return glib::Propagation::Proceed,
+                                };
+                                let raw: *const gdk_sys::GdkEventTouchpadPinch =
+                                    pinch.to_glib_none().0;
+                                let phase = match unsafe { (*raw).phase } as i32 {
+                                    gdk_sys::GDK_TOUCHPAD_GESTURE_PHASE_BEGIN => {
+                                        last_scale.set(Some(1.0));
+                                        TouchPhase::Started
+                                    }
+                                    gdk_sys::GDK_TOUCHPAD_GESTURE_PHASE_UPDATE => TouchPhase::Moved,
+                                    gdk_sys::GDK_TOUCHPAD_GESTURE_PHASE_END => TouchPhase::Ended,
+                                    _ => TouchPhase::Cancelled,
+                                };
+                                // Drop events of a pinch which never began.
+                                let last = match last_scale.get() {
+                                    Some(last) => last,
+                                    None => return glib::Propagation::Proceed,
+                                };
+                                let scale = pinch.scale();
+                                last_scale.set(match phase {
+                                    TouchPhase::Ended | TouchPhase::Cancelled => None,
+                                    _ => Some(scale),
+                                });
+
+                                let device_id = device_ids_.borrow_mut().id(event);
+                                let events = [
+                                    WindowEvent::TouchpadMagnify {
+                                        device_id,
+                                        delta: scale - last,
+                                        phase,
+                                    },
+                                    // GDK measures clockwise in radians, winit counterclockwise in
+                                    // degrees.
+                                    WindowEvent::TouchpadRotate {
+                                        device_id,
+                                        delta: -pinch.angle_delta().to_degrees() as f32,
+                                        phase,
+                                    },
+                                ];
+                                for event in events {
+                                    if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                        window_id: RootWindowId(id),
+                                        event,
+                                    }) {
+                                        log::warn!(
+                                            "Failed to send touchpad gesture event to event channel: {}",
+                                            e
+                                        );
+                                    }
+                                }
+                                glib::Propagation::Proceed
+                            });
+                        }
+
                         // TODO Follwong WindowEvents are missing see #2 for mor info.
-                        // - Touch
-                        // - TouchpadMagnify
-                        // -  TouchpadRotate
                         // -  TouchpadPressure
                         // -  SmartMagnify
-                        // -  ReceivedCharacter
-                        // -  Ime
                         // - ScaleFactorChanged
                         // - DroppedFile
                         // - HoveredFile
                         // - HoveredFileCancelled
-                        // - ThemeChanged
-                        // - AxisMotion
-                        // - Occluded
 
-                        let tx_clone = event_tx.clone();
-                        let modifiers = AtomicU32::new(ModifiersState::empty().bits());
-                        let keyboard_handler =
-                            Rc::new(move |event_key: EventKey, element_state| {
-                                // if we have a modifier lets send it
-                                let new_mods = keyboard::get_modifiers(&event_key);
-                                    if new_mods.bits() != modifiers.load(Ordering::Relaxed) {
-                                    modifiers.store(new_mods.bits(), Ordering::Relaxed);
+                        if default_handlers.contains(DefaultHandlers::KEYBOARD) {
+                            let tx_clone = event_tx.clone();
+                            let device_ids_ = device_ids.clone();
+                            let update_modifiers_ = update_modifiers.clone();
+                            let pressed_inputs_ = pressed_inputs.clone();
+                            let keyboard_handler =
+                                Rc::new(move |event_key: EventKey, element_state| {
+                                    // if we have a modifier lets send it
+                                    let new_mods = keyboard::get_modifiers(&event_key, element_state);
+                                    update_modifiers_(new_mods);
+
+
+                                    let virtual_key = keyboard::gdk_key_to_virtual_key(event_key.keyval());
+                                    let device_id = device_ids_.borrow_mut().id(&event_key);
+                                    let scancode = event_key.scancode() as u32;
+                                    pressed_inputs_.borrow_mut().key(
+                                        id,
+                                        device_id,
+                                        scancode,
+                                        virtual_key,
+                                        element_state,
+                                    );
+                                    #[allow(deprecated)]
                                     if let Err(e) = tx_clone.send(Event::WindowEvent {
                                         window_id: RootWindowId(id),
-                                        event: WindowEvent::ModifiersChanged(new_mods),
+                                        event: WindowEvent::KeyboardInput {
+                                            device_id,
+                                            input: KeyboardInput {
+                                                scancode,
+                                                state: element_state,
+                                                virtual_keycode: virtual_key,
+                                                modifiers: new_mods,
+                                            },
+                                            is_synthetic: false,
+                                        },
                                     }) {
-                                        log::warn!("Failed to send modifiers changed event to event channel: {}",e);
+                                        log::warn!(
+                                            "Failed to send keyboard event to event channel: {}",
+                                            e
+                                        );
                                     }
-                                }
 
+                                    glib::ControlFlow::Continue
+                                });
 
-                                let virtual_key = keyboard::gdk_key_to_virtual_key(event_key.keyval());
-                                #[allow(deprecated)]
+                            // Input method of the window, it only filters key events once IME is
+                            // allowed.
+                            let ime = gtk::IMMulticontext::new();
+                            ime.set_use_preedit(true);
+                            if let Some(gdk_window) = window.window() {
+                                ime.set_client_window(Some(&gdk_window));
+                            }
+                            let ime_ = ime.clone();
+                            window.connect_realize(move |window| {
+                                ime_.set_client_window(window.window().as_ref());
+                            });
+
+                            let tx_clone = event_tx.clone();
+                            ime.connect_commit(move |_, text| {
                                 if let Err(e) = tx_clone.send(Event::WindowEvent {
                                     window_id: RootWindowId(id),
-                                    event: WindowEvent::KeyboardInput {
-                                        device_id: DEVICE_ID,
-                                        input: KeyboardInput {
-                                            scancode: event_key.scancode() as u32,
-                                            state: element_state,
-                                            virtual_keycode: virtual_key,
-                                            modifiers: new_mods,
-                                        },
-                                        is_synthetic: false,
-                                    },
+                                    event: WindowEvent::Ime(Ime::Commit(text.to_string())),
                                 }) {
                                     log::warn!(
-                                        "Failed to send keyboard event to event channel: {}",
+                                        "Failed to send IME commit event to event channel: {}",
                                         e
                                     );
                                 }
+                            });
 
-                                glib::ControlFlow::Continue
+                            let tx_clone = event_tx.clone();
+                            ime.connect_preedit_changed(move |ime| {
+                                let (text, _, cursor) = ime.preedit_string();
+                                // GTK reports the cursor in chars, winit expects bytes.
+                                let cursor = text
+                                    .char_indices()
+                                    .nth(cursor as usize)
+                                    .map(|(i, _)| i)
+                                    .unwrap_or(text.len());
+                                let cursor_range = if text.is_empty() {
+                                    None
+                                } else {
+                                    Some((cursor, cursor))
+                                };
+                                if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                    window_id: RootWindowId(id),
+                                    event: WindowEvent::Ime(Ime::Preedit(
+                                        text.to_string(),
+                                        cursor_range,
+                                    )),
+                                }) {
+                                    log::warn!(
+                                        "Failed to send IME preedit event to event channel: {}",
+                                        e
+                                    );
+                                }
                             });
 
-                            //     let tx_clone = event_tx.clone();
-                            //     // TODO Add actual IME from system
-                            //     let ime = gtk::IMContextSimple::default();
-                            //     ime.set_client_window(window.window().as_ref());
-                            //     ime.focus_in();
-                            //     ime.connect_commit(move |_, s| {
-                            // let c = s.chars().collect::<Vec<char>>();
-                            //         if let Err(e) = tx_clone.send(Event::WindowEvent {
-                            //             window_id: RootWindowId(id),
-                            //             event: WindowEvent::ReceivedCharacter(c[0]),
-                            //         }) {
-                            //             log::warn!(
-                            //                 "Failed to send received IME text event to event channel: {}",
-                            //                 e
-                            //             );
-                            //         }
-                            //     });
+                            let ime_ = ime.clone();
+                            let ime_allowed_ = ime_allowed.clone();
+                            let update_modifiers_ = update_modifiers.clone();
+                            window.connect_focus_in_event(move |window, _| {
+                                if ime_allowed_.load(Ordering::Acquire) {
+                                    ime_.focus_in();
+                                }
+                                // Modifiers might have changed while another window had the focus.
+                                update_modifiers_(keyboard::current_modifiers(&window.display()));
+                                glib::Propagation::Proceed
+                            });
+                            let ime_ = ime.clone();
+                            window.connect_focus_out_event(move |_, _| {
+                                ime_.focus_out();
+                                // Key releases go to the newly focused window from now on.
+                                update_modifiers(ModifiersState::empty());
+                                glib::Propagation::Proceed
+                            });
+
+                            // Key presses which didn't go to the IME are composed into text here.
+                            let text_input = gtk::IMContextSimple::new();
+                            let tx_clone = event_tx.clone();
+                            text_input.connect_commit(move |_, text| {
+                                for c in text.chars() {
+                                    if let Err(e) = tx_clone.send(Event::WindowEvent {
+                                        window_id: RootWindowId(id),
+                                        event: WindowEvent::ReceivedCharacter(c),
+                                    }) {
+                                        log::warn!(
+                                            "Failed to send received character event to event channel: {}",
+                                            e
+                                        );
+                                    }
+                                }
+                            });
 
                             let handler = keyboard_handler.clone();
+                            let ime_ = ime.clone();
+                            let ime_allowed_ = ime_allowed.clone();
                             window.connect_key_press_event(move |_, event_key| {
+                                if ime_allowed_.load(Ordering::Acquire)
+                                    && ime_.filter_keypress(event_key)
+                                {
+                                    return glib::Propagation::Stop;
+                                }
                                 handler(event_key.to_owned(), ElementState::Pressed);
-                                // ime.filter_keypress(event_key);
+                                text_input.filter_keypress(event_key);
 
                                 glib::Propagation::Proceed
                             });
 
                             let handler = keyboard_handler.clone();
+                            let ime_ = ime.clone();
                             window.connect_key_release_event(move |_, event_key| {
+                                if ime_allowed.load(Ordering::Acquire)
+                                    && ime_.filter_keypress(event_key)
+                                {
+                                    return glib::Propagation::Stop;
+                                }
                                 handler(event_key.to_owned(), ElementState::Released);
                                 glib::Propagation::Proceed
                             });
+                            ime_contexts.borrow_mut().insert(id, ime);
+                        }
 
+                        // Both visibility and iconify changes report occlusion, only send actual changes.
                         let tx_clone = event_tx.clone();
-                        window.connect_window_state_event(move |window, event| {
-                            let state = event.changed_mask();
-                            if state.contains(WindowState::ICONIFIED)
-                                || state.contains(WindowState::MAXIMIZED)
-                            {
-                                let scale_factor = window.scale_factor();
-
-                                let (x, y) = window.position();
+                        let occluded = Cell::new(None);
+                        let send_occluded = Rc::new(move |is_occluded| {
+                            if occluded.replace(Some(is_occluded)) != Some(is_occluded) {
                                 if let Err(e) = tx_clone.send(Event::WindowEvent {
                                     window_id: RootWindowId(id),
-                                    event: WindowEvent::Moved(
-                                        LogicalPosition::new(x, y).to_physical(scale_factor as f64),
-                                    ),
+                                    event: WindowEvent::Occluded(is_occluded),
                                 }) {
                                     log::warn!(
-                                        "Failed to send window moved event to event channel: {}",
+                                        "Failed to send window occluded event to event channel: {}",
                                         e
                                     );
                                 }
+                            }
+                        });
 
-                                let (w, h) = window.size();
-                                if let Err(e) = tx_clone.send(Event::WindowEvent {
-                                    window_id: RootWindowId(id),
-                                    event: WindowEvent::Resized(
-                                        LogicalSize::new(w, h).to_physical(scale_factor as f64),
-                                    ),
-                                }) {
-                                    log::warn!(
-                                        "Failed to send window resized event to event channel: {}",
-                                        e
-                                    );
+                        let send_occluded_ = send_occluded.clone();
+                        // gtk-rs doesn't bind `visibility-notify-event` anymore.
+                        window.connect_event(move |_, event| {
+                            if let Some(event) = event.downcast_ref::<EventVisibility>() {
+                                send_occluded_(event.state() == VisibilityState::FullyObscured);
+                            }
+                            glib::Propagation::Proceed
+                        });
+
+                        let tx_clone = event_tx.clone();
+                        let reported_geometry_ = reported_geometry.clone();
+                        window.connect_window_state_event(move |window, event| {
+                            // Hidden windows are withdrawn, minimized ones iconified.
+                            let hidden = WindowState::ICONIFIED | WindowState::WITHDRAWN;
+                            if event.changed_mask().intersects(hidden) {
+                                send_occluded(event.new_window_state().intersects(hidden));
+                            }
+
+                            // The geometry changes along with these states, but the configure
+                            // event may come before the state. The window updates the states
+                            // returned by `is_maximized` and `is_minimized` in an earlier handler,
+                            // they are current when these events are received.
+                            let state = event.changed_mask();
+                            if state.intersects(
+                                WindowState::ICONIFIED
+                                    | WindowState::MAXIMIZED
+                                    | WindowState::FULLSCREEN,
+                            ) {
+                                let position = (!state_position_frozen.load(Ordering::Acquire))
+                                    .then(|| window.position());
+                                for event in state_change_events(
+                                    id,
+                                    &reported_geometry_,
+                                    event.new_window_state().contains(WindowState::ICONIFIED),
+                                    position,
+                                    window.size(),
+                                    window.scale_factor() as f64,
+                                ) {
+                                    if let Err(e) = tx_clone.send(event) {
+                                        log::warn!(
+                                            "Failed to send window state event to event channel: {}",
+                                            e
+                                        );
+                                    }
                                 }
                             }
                             glib::Propagation::Proceed
@@ -719,17 +1674,61 @@ impl<T: 'static> EventLoop<T> {
                                 log::warn!("Failed to send redraw event to event channel: {}", e);
                             }
 
-                            if transparent.load(Ordering::Relaxed) {
+                            if default_handlers.contains(DefaultHandlers::DRAW_CLEAR)
+                                && transparent.load(Ordering::Relaxed)
+                            {
                                 cr.set_source_rgba(0., 0., 0., 0.);
                                 cr.set_operator(cairo::Operator::Source);
                                 let _ = cr.paint();
                                 cr.set_operator(cairo::Operator::Over);
                             }
-
-                            glib::Propagation::Proceed
-                        });
+
+                            glib::Propagation::Proceed
+                        });
+
+                        // A window shown by `Window::new` may have had its configure and focus
+                        // events before the handlers were connected, its state stands in for them.
+                        // The first configure event reports the geometry again if it was still
+                        // pending.
+                        if window.is_visible() {
+                            let position = (!replay_position_frozen.load(Ordering::Acquire))
+                                .then(|| window.position());
+                            let size = window.size();
+                            reported_geometry.position.set(position);
+                            reported_geometry.size.set(Some(size));
+                            reported_geometry.replayed.set(true);
+                            for event in initial_state_events(
+                                id,
+                                size,
+                                position,
+                                window.is_active(),
+                                window.scale_factor() as f64,
+                            ) {
+                                if let Err(e) = event_tx.send(event) {
+                                    log::warn!(
+                                        "Failed to send window state event to event channel: {}",
+                                        e
+                                    );
+                                }
+                            }
+                        }
                     }
+                }));
+                if let Err(payload) = result {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("unknown panic");
+                    log::error!("Window request of {:?} panicked: {}", id, message);
                 }
+            } else if windows.borrow().contains(&id) {
+                log::error!(
+                    "Window request for {:?} is ignored, it was removed from the application",
+                    id
+                );
+            } else {
+                log::warn!("Window request for a destroyed window {:?} is ignored", id);
             }
             glib::ControlFlow::Continue
         });
@@ -741,26 +1740,64 @@ impl<T: 'static> EventLoop<T> {
                 _marker: std::marker::PhantomData,
             },
             user_event_tx,
-            events: event_rx,
+            prioritized_event_tx,
+            wakeup_tx,
+            wakeup_source: Some(wakeup_source),
+            events: EventQueue {
+                prioritized: prioritized_event_rx,
+                events: event_rx,
+                deferred: Default::default(),
+            },
             draws: draw_rx,
+            window_requests_source: Some(window_requests_source),
+            _raw_event_filter: raw_event_filter,
         }
     }
     /// Creates an `EventLoopProxy` that can be used to dispatch user events to the main event loop.
     pub fn create_proxy(&self) -> EventLoopProxy<T> {
         EventLoopProxy {
             user_event_tx: self.user_event_tx.clone(),
+            prioritized_event_tx: self.prioritized_event_tx.clone(),
+            wakeup_tx: self.wakeup_tx.clone(),
         }
     }
 
     #[inline]
-    pub fn run<F>(mut self, callback: F) -> !
+    pub fn run<F>(self, callback: F) -> !
     where
         F: 'static + FnMut(crate::event::Event<'_, T>, &RootELW<T>, &mut ControlFlow),
     {
-        let exit_code = self.run_return(callback);
+        let exit_code = self.run_without_exit(callback);
         process::exit(exit_code)
     }
 
+    /// Runs the event loop like `run`, but tears it down and returns the exit code instead of
+    /// exiting the process.
+    pub fn run_without_exit<F>(mut self, callback: F) -> i32
+    where
+        F: 'static + FnMut(crate::event::Event<'_, T>, &RootELW<T>, &mut ControlFlow),
+    {
+        self.run_inner(callback, true)
+    }
+
+    /// Destroys the windows created through the event loop, once their queued requests are
+    /// applied.
+    fn destroy_windows(&self) {
+        let window_target = &self.window_target.p;
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+        for id in window_target.windows.take() {
+            if let Some(window) = window_target.app.window_by_id(id.0 as u32) {
+                // The `Window`s still referring to it refuse any request once it is destroyed.
+                unsafe { window.destroy() };
+            }
+        }
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+    }
+
     /// This is the core event loop logic. It basically loops on `gtk_main_iteration` and processes one
     /// event along with that iteration. Depends on current control flow and what it should do, an
     /// event state is defined. The whole state flow chart runs like following:
@@ -784,13 +1821,29 @@ impl<T: 'static> EventLoop<T> {
     ///                                    +---------------+
     /// ```
     ///
+    /// Each run starts in `EventQueue` after `NewEvents(StartCause::Init)` was sent.
+    ///
     /// There are a dew notibale event will sent to callback when state is transisted:
     /// - On any state moves to `LoopDestroyed`, a `LoopDestroyed` event is sent.
     /// - On `NewStart` to `EventQueue`, a `NewEvents` with corresponding `StartCause` depends on
     /// current control flow is sent.
     /// - On `EventQueue` to `DrawQueue`, a `MainEventsCleared` event is sent.
     /// - On `DrawQueue` back to `NewStart`, a `RedrawEventsCleared` event is sent.
-    pub(crate) fn run_return<F>(&mut self, mut callback: F) -> i32
+    ///
+    /// With `Wait` and `WaitUntil`, the iteration after `NewStart` blocks until GTK has an event, a
+    /// user event wakes up the main context, or, for `WaitUntil`, a timeout source fires at the
+    /// deadline. With `Poll`, it blocks on an idle source instead. That way the sources of GTK are
+    /// dispatched first instead of being starved by the loop.
+    pub(crate) fn run_return<F>(&mut self, callback: F) -> i32
+    where
+        F: FnMut(Event<'_, T>, &RootELW<T>, &mut ControlFlow),
+    {
+        self.run_inner(callback, false)
+    }
+
+    /// Runs the loop, with `destroy_windows` the windows are destroyed on exit and their
+    /// `Destroyed` events delivered before `LoopDestroyed`.
+    fn run_inner<F>(&mut self, mut callback: F, destroy_windows: bool) -> i32
     where
         F: FnMut(Event<'_, T>, &RootELW<T>, &mut ControlFlow),
     {
@@ -808,17 +1861,38 @@ impl<T: 'static> EventLoop<T> {
                 let events = &self.events;
                 let draws = &self.draws;
 
-                window_target.p.app.activate();
+                // Another launch of a running application hands over to the primary instance and
+                // exits without running, every `activate` and `open` signal the primary instance
+                // receives is a re-activation.
+                if window_target.p.app.is_remote() {
+                    forward_launch(&window_target.p.app);
+                    self.tear_down(&mut callback, &mut control_flow, destroy_windows);
+                    return 0;
+                }
+
+                // Wakes up the blocking iteration once the `WaitUntil` deadline is reached.
+                let mut resume_timeout: Option<(Instant, glib::Source)> = None;
+                // Keeps the blocking iteration of `Poll` going once every source of higher
+                // priority, including the ones of GTK, was dispatched.
+                let mut poll_idle: Option<SourceId> = None;
 
-                let mut state = EventState::NewStart;
+                // Every run starts with `Init`, the events queued meanwhile follow right away.
+                callback(
+                    Event::NewEvents(StartCause::Init),
+                    window_target,
+                    &mut control_flow,
+                );
+                let mut state = EventState::EventQueue;
                 let exit_code = loop {
                     let mut blocking = false;
+                    if !matches!(control_flow, ControlFlow::Poll) {
+                        if let Some(source) = poll_idle.take() {
+                            source.remove();
+                        }
+                    }
                     match state {
                         EventState::NewStart => match control_flow {
-                            ControlFlow::ExitWithCode(code) => {
-                                callback(Event::LoopDestroyed, window_target, &mut control_flow);
-                                break code;
-                            }
+                            ControlFlow::ExitWithCode(code) => break code,
                             ControlFlow::Wait => {
                                 if !events.is_empty() {
                                     callback(
@@ -858,30 +1932,66 @@ impl<T: 'static> EventLoop<T> {
                                     state = EventState::EventQueue;
                                 } else {
                                     blocking = true;
+                                    let is_scheduled = matches!(
+                                        &resume_timeout,
+                                        Some((resume, source))
+                                            if *resume == requested_resume && !source.is_destroyed()
+                                    );
+                                    if !is_scheduled {
+                                        if let Some((_, source)) = resume_timeout.take() {
+                                            source.destroy();
+                                        }
+                                        // GLib timeouts have millisecond precision, round up so
+                                        // the wake up doesn't come before the deadline.
+                                        let timeout = Duration::from_millis(
+                                            ((requested_resume - start).as_micros() as u64 + 999)
+                                                / 1000,
+                                        );
+                                        let source = glib::timeout_source_new(
+                                            timeout,
+                                            None,
+                                            Priority::DEFAULT,
+                                            || glib::ControlFlow::Break,
+                                        );
+                                        source.attach(Some(&context));
+                                        resume_timeout = Some((requested_resume, source));
+                                    }
                                 }
                             }
-                            _ => {
+                            ControlFlow::Poll => {
                                 callback(
                                     Event::NewEvents(StartCause::Poll),
                                     window_target,
                                     &mut control_flow,
                                 );
+                                poll_idle.get_or_insert_with(|| {
+                                    glib::idle_add_local_full(Priority::DEFAULT_IDLE, || {
+                                        glib::ControlFlow::Continue
+                                    })
+                                });
+                                blocking = true;
                                 state = EventState::EventQueue;
                             }
                         },
                         EventState::EventQueue => match control_flow {
-                            ControlFlow::ExitWithCode(code) => {
-                                callback(Event::LoopDestroyed, window_target, &mut control_flow);
-                                break (code);
-                            }
-                            _ => match events.try_recv() {
-                                Ok(event) => match event {
-                                    Event::LoopDestroyed => {
-                                        control_flow = ControlFlow::ExitWithCode(1)
+                            ControlFlow::ExitWithCode(code) => break code,
+                            _ => {
+                                let mut drained = events.drain().into_iter();
+                                for event in drained.by_ref() {
+                                    match event {
+                                        Event::LoopDestroyed => {
+                                            control_flow = ControlFlow::ExitWithCode(1)
+                                        }
+                                        _ => callback(event, window_target, &mut control_flow),
+                                    }
+                                    if let ControlFlow::ExitWithCode(_) = control_flow {
+                                        break;
                                     }
-                                    _ => callback(event, window_target, &mut control_flow),
-                                },
-                                Err(_) => {
+                                }
+                                // The rest is delivered by the next `run_return`.
+                                events.defer(drained);
+                                // On exit, `LoopDestroyed` is sent on the next pass instead.
+                                if !matches!(control_flow, ControlFlow::ExitWithCode(_)) {
                                     callback(
                                         Event::MainEventsCleared,
                                         window_target,
@@ -889,15 +1999,12 @@ impl<T: 'static> EventLoop<T> {
                                     );
                                     state = EventState::DrawQueue;
                                 }
-                            },
+                            }
                         },
                         EventState::DrawQueue => match control_flow {
-                            ControlFlow::ExitWithCode(code) => {
-                                callback(Event::LoopDestroyed, window_target, &mut control_flow);
-                                break code;
-                            }
+                            ControlFlow::ExitWithCode(code) => break code,
                             _ => {
-                                if let Ok(id) = draws.try_recv() {
+                                for id in pending_redraws(draws) {
                                     callback(
                                         Event::RedrawRequested(RootWindowId(id)),
                                         window_target,
@@ -913,22 +2020,102 @@ impl<T: 'static> EventLoop<T> {
                             }
                         },
                     }
+                    if !blocking {
+                        if let Some((_, source)) = resume_timeout.take() {
+                            source.destroy();
+                        }
+                    }
                     gtk::main_iteration_do(blocking);
                 };
+
+                if let Some((_, source)) = resume_timeout {
+                    source.destroy();
+                }
+                if let Some(source) = poll_idle {
+                    source.remove();
+                }
+
+                self.tear_down(&mut callback, &mut control_flow, destroy_windows);
                 exit_code
             })
             .unwrap_or(1)
     }
 
+    /// Sends `LoopDestroyed` at the end of a run, with `destroy_windows` after destroying the
+    /// windows and delivering their `Destroyed` events.
+    fn tear_down<F>(&self, callback: &mut F, control_flow: &mut ControlFlow, destroy_windows: bool)
+    where
+        F: FnMut(Event<'_, T>, &RootELW<T>, &mut ControlFlow),
+    {
+        let window_target = &self.window_target;
+        if destroy_windows {
+            self.destroy_windows();
+            // The events still queued, user events included, come before the `Destroyed` events
+            // and nothing is delivered after `LoopDestroyed`.
+            for event in self.events.drain() {
+                callback(event, window_target, control_flow);
+            }
+        }
+        callback(Event::LoopDestroyed, window_target, control_flow);
+
+        // Idle callbacks must not outlive `LoopDestroyed`.
+        for source in window_target.p.idle_sources.take() {
+            if let Some(id) = source.take() {
+                id.remove();
+            }
+        }
+        // Destroying the source drops the future.
+        for source in window_target.p.futures.take() {
+            source.destroy();
+        }
+    }
+
     pub fn window_target(&self) -> &crate::event_loop::EventLoopWindowTarget<T> {
         &self.window_target
     }
 }
 
+impl<T: 'static> Drop for EventLoop<T> {
+    fn drop(&mut self) {
+        let context = MainContext::default();
+        let result = context.with_thread_default(|| {
+            self.destroy_windows();
+            self.window_target.p.global_shortcuts.clear();
+            self.window_target.p.app.quit();
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to tear down the event loop: {}", e);
+        }
+        // Requests of the `Window`s that are kept around fail from now on.
+        if let Some(source) = self.window_requests_source.take() {
+            source.remove();
+        }
+        // Wake ups sent by the proxies after this are dropped.
+        if let Some(source) = self.wakeup_source.take() {
+            source.remove();
+        }
+        // There is no callback left to deliver the queued events to.
+        self.events.drain();
+        pending_redraws(&self.draws);
+        EVENT_LOOP_CREATED.store(false, Ordering::Release);
+    }
+}
+
 impl<T: 'static> EventLoopProxy<T> {
     pub fn send_event(&self, event: T) -> Result<(), EventLoopClosed<T>> {
-        self.user_event_tx
-            .send(Event::UserEvent(event))
+        self.send(&self.user_event_tx, event)
+    }
+
+    pub fn send_event_prioritized(&self, event: T) -> Result<(), EventLoopClosed<T>> {
+        self.send(&self.prioritized_event_tx, event)
+    }
+
+    fn send(
+        &self,
+        tx: &crossbeam_channel::Sender<Event<'static, T>>,
+        event: T,
+    ) -> Result<(), EventLoopClosed<T>> {
+        tx.send(Event::UserEvent(event))
             .map_err(|SendError(event)| {
                 if let Event::UserEvent(error) = event {
                     EventLoopClosed(error)
@@ -937,13 +2124,16 @@ impl<T: 'static> EventLoopProxy<T> {
                 }
             })?;
 
-        let context = MainContext::default();
-        context.wakeup();
+        // The loop might be gone meanwhile, the event is then dropped along with the queue.
+        let _ = self.wakeup_tx.send(());
 
         Ok(())
     }
 }
 
+/// The source of an idle callback, taken once the callback finished.
+type IdleSource = Rc<RefCell<Option<SourceId>>>;
+
 #[derive(Clone)]
 pub struct EventLoopWindowTarget<T> {
     /// Gdk display
@@ -956,6 +2146,20 @@ pub struct EventLoopWindowTarget<T> {
     pub(crate) window_requests_tx: glib::Sender<(WindowId, WindowRequest)>,
     /// Draw event sender
     pub(crate) draw_tx: crossbeam_channel::Sender<WindowId>,
+    /// Filter of the raw device events
+    pub(crate) device_event_filter: Rc<Cell<DeviceEventFilter>>,
+    /// Theme of the session before any window changed it
+    pub(crate) system_theme: SystemTheme,
+    /// Idle callbacks, removed once the loop is destroyed
+    pub(crate) idle_sources: Rc<RefCell<Vec<IdleSource>>>,
+    /// Sources of the spawned futures, destroyed once the loop is destroyed
+    pub(crate) futures: Rc<RefCell<Vec<glib::Source>>>,
+    /// Optional protocols of the Wayland compositor
+    pub(crate) wayland_capabilities: WaylandCapabilities,
+    /// Key grabs of the global shortcuts
+    pub(crate) global_shortcuts: Rc<GlobalShortcuts>,
+    /// Ids of the physical input devices
+    pub(crate) device_ids: Rc<RefCell<DeviceIds>>,
     _marker: std::marker::PhantomData<T>,
 }
 impl<T> EventLoopWindowTarget<T> {
@@ -966,27 +2170,106 @@ impl<T> EventLoopWindowTarget<T> {
 
     #[inline]
     pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
-        let mut handles = VecDeque::new();
-        let display = &self.display;
-        let numbers = display.n_monitors();
+        monitor::available_monitors(&self.display)
+    }
+
+    #[inline]
+    pub fn primary_monitor(&self) -> Option<MonitorHandle> {
+        monitor::primary_monitor(&self.display)
+    }
+
+    #[inline]
+    pub fn connect_reactivated<F: Fn(&[String]) + 'static>(&self, f: F) {
+        let f = Rc::new(f);
+        let f_ = f.clone();
+        self.app.connect_activate(move |_| f_(&[]));
+        self.app.connect_open(move |_, files, _| {
+            let uris: Vec<String> = files.iter().map(|file| file.uri().to_string()).collect();
+            f(&uris)
+        });
+    }
+
+    #[inline]
+    pub fn input_settings(&self) -> InputSettings {
+        util::input_settings()
+    }
 
-        for i in 0..numbers {
-            let monitor = MonitorHandle::new(display, i);
-            handles.push_back(monitor);
+    #[inline]
+    pub fn connect_input_settings_changed<F: Fn(InputSettings) + 'static>(&self, f: F) {
+        if let Some(settings) = Settings::default() {
+            let f = Rc::new(f);
+            for property in util::INPUT_SETTINGS_PROPERTIES {
+                let f = f.clone();
+                settings
+                    .connect_notify_local(Some(property), move |_, _| f(util::input_settings()));
+            }
         }
+    }
+
+    #[inline]
+    pub fn connect_idle<F: FnMut() -> bool + 'static>(&self, mut f: F) {
+        let source: IdleSource = Default::default();
+        let source_ = source.clone();
+        // GTK redraws at `GDK_PRIORITY_REDRAW`, which comes before the default idle priority.
+        let id = glib::idle_add_local_full(Priority::DEFAULT_IDLE, move || {
+            if f() {
+                glib::ControlFlow::Continue
+            } else {
+                // glib removes the source itself.
+                source_.take();
+                glib::ControlFlow::Break
+            }
+        });
+        source.replace(Some(id));
+        let mut idle_sources = self.idle_sources.borrow_mut();
+        // The sources of finished callbacks are gone already.
+        idle_sources.retain(|source| source.borrow().is_some());
+        idle_sources.push(source);
+    }
 
-        handles
+    pub fn spawn_local<F: Future<Output = ()> + 'static>(&self, f: F) {
+        // glib wakes up the context when the future is woken, which ends a blocking iteration.
+        let handle = MainContext::default().spawn_local(f);
+        let mut futures = self.futures.borrow_mut();
+        futures.retain(|source| !source.is_destroyed());
+        futures.push(handle.source().clone());
     }
 
     #[inline]
-    pub fn primary_monitor(&self) -> Option<MonitorHandle> {
-        let monitor = self.display.primary_monitor();
-        monitor.map(|monitor| MonitorHandle { monitor })
+    pub fn window_ids(&self) -> Vec<RootWindowId> {
+        let mut ids: Vec<_> = self
+            .windows
+            .borrow()
+            .iter()
+            .copied()
+            .map(RootWindowId)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[inline]
+    pub fn register_global_shortcut(
+        &self,
+        modifiers: ModifiersState,
+        scancode: ScanCode,
+    ) -> Result<ShortcutId, ExternalError> {
+        self.global_shortcuts.register(modifiers, scancode)
+    }
+
+    #[inline]
+    pub fn unregister_global_shortcut(&self, id: ShortcutId) {
+        self.global_shortcuts.unregister(id)
+    }
+
+    #[inline]
+    pub fn connect_global_shortcut<F: Fn(ShortcutId) + 'static>(&self, f: F) {
+        self.global_shortcuts.connect(f)
     }
 
     #[inline]
-    pub fn set_device_event_filter(&self, _filter: DeviceEventFilter) {
-        // TODO implement this
+    pub fn set_device_event_filter(&self, filter: DeviceEventFilter) {
+        self.device_event_filter.set(filter);
     }
 
     pub fn raw_display_handle(&self) -> raw_window_handle::RawDisplayHandle {
@@ -1009,3 +2292,729 @@ impl<T> EventLoopWindowTarget<T> {
         }
     }
 }
+
+/// Shows `cursor` over the window at `size` logical pixels if given, `None` hides it.
+fn apply_cursor(window: &gtk::Window, cursor: Option<CursorIcon>, size: Option<u32>) {
+    if let Some(gdk_window) = window.window() {
+        let display = window.display();
+        let scale_factor = window.scale_factor();
+        // GDK loads the cursors of X11 at the unscaled size of the theme, Wayland scales them.
+        let size = size.or_else(|| {
+            (display.backend().is_x11() && scale_factor > 1).then(util::cursor_theme_size)
+        });
+        let cursor = match cursor {
+            Some(icon) => size
+                .and_then(|size| {
+                    util::themed_cursor(&display, cursor_name(icon), size, scale_factor)
+                })
+                .or_else(|| Cursor::from_name(&display, cursor_name(icon))),
+            None => Cursor::for_display(&display, CursorType::BlankCursor),
+        };
+        gdk_window.set_cursor(cursor.as_ref());
+    }
+}
+
+/// Warps the pointer back to the center of the window, which emulates a locked cursor.
+fn warp_to_center(window: &gtk::Window, motion: &EventMotion) {
+    if let (Some(gdk_window), Some(device), Some(screen)) = (
+        window.window(),
+        motion.device(),
+        GtkWindowExt::screen(window),
+    ) {
+        let (_, x, y) = gdk_window.origin();
+        let center = (x + gdk_window.width() / 2, y + gdk_window.height() / 2);
+        let (root_x, root_y) = motion.root();
+        if (root_x as i32, root_y as i32) != center {
+            device.warp(&screen, center.0, center.1);
+        }
+    }
+}
+
+/// Shows the resize cursor of the edge of an unmaximized borderless window under the pointer,
+/// and the cursor of the application elsewhere.
+fn update_resize_cursor(window: &gtk::Window, motion: &EventMotion, cursor: &Cell<CursorState>) {
+    if window.is_decorated() || !window.is_resizable() || window.is_maximized() {
+        return;
+    }
+    if let Some(gdk_window) = window.window() {
+        let (x, y) = util::toplevel_position(
+            &gdk_window,
+            motion.window(),
+            motion.position(),
+            motion.root(),
+        );
+        let icon = match hit_test_relative(&gdk_window, x, y) {
+            WindowEdge::North => Some(CursorIcon::NResize),
+            WindowEdge::South => Some(CursorIcon::SResize),
+            WindowEdge::East => Some(CursorIcon::EResize),
+            WindowEdge::West => Some(CursorIcon::WResize),
+            WindowEdge::NorthWest => Some(CursorIcon::NwResize),
+            WindowEdge::NorthEast => Some(CursorIcon::NeResize),
+            WindowEdge::SouthEast => Some(CursorIcon::SeResize),
+            WindowEdge::SouthWest => Some(CursorIcon::SwResize),
+            // Back to the cursor of the application.
+            _ => cursor.get().cursor(),
+        };
+        apply_cursor(window, icon, cursor.get().size());
+    }
+}
+
+/// Remembers a press on an edge of a borderless window, returns whether there was one.
+fn press_resize_edge(
+    window: &gtk::Window,
+    event: &EventButton,
+    pending_resize: &Cell<Option<(WindowEdge, f64, f64)>>,
+) -> bool {
+    if window.is_decorated() || !window.is_resizable() || event.button() != 1 {
+        return false;
+    }
+    if let Some(gdk_window) = window.window() {
+        let (x, y) =
+            util::toplevel_position(&gdk_window, event.window(), event.position(), event.root());
+        let result = hit_test_relative(&gdk_window, x, y);
+        // The resize itself is started from the root coordinates.
+        let (cx, cy) = event.root();
+        // Ignore the `__Unknown` variant so the window receives the click correctly if it is not
+        // on the edges.
+        if !matches!(result, WindowEdge::__Unknown(_)) {
+            pending_resize.set(Some((result, cx, cy)));
+            return true;
+        }
+    }
+    false
+}
+
+/// Starts the resize of a pending press once the pointer moved past the drag threshold.
+fn start_pending_resize(
+    window: &gtk::Window,
+    motion: &EventMotion,
+    pending_resize: &Cell<Option<(WindowEdge, f64, f64)>>,
+) {
+    if let Some((edge, x, y)) = pending_resize.get() {
+        let (cx, cy) = motion.root();
+        let threshold = util::input_settings().drag_threshold as f64;
+        if (cx - x).abs() > threshold || (cy - y).abs() > threshold {
+            pending_resize.set(None);
+            // FIXME: calling `window.begin_resize_drag` uses the default cursor, it should show a resizing cursor instead
+            window.begin_resize_drag(edge, 1, x as i32, y as i32, motion.time());
+        }
+    }
+}
+
+/// A touch which began on an edge of a borderless window, the application doesn't get its events.
+struct EdgeTouch {
+    sequence: Option<gdk::EventSequence>,
+    device: gdk::Device,
+    edge: WindowEdge,
+    /// The root coordinates of the touch begin.
+    start: (f64, f64),
+    /// Whether the resize was started, the window manager handles the touch from then on.
+    resizing: bool,
+}
+
+/// Resizes a borderless window by a touch on an edge once it moved past the drag threshold,
+/// returns whether `event` belongs to such a touch.
+fn touch_resize_edge(
+    window: &gtk::Window,
+    event: &gdk::Event,
+    edge_touch: &RefCell<Option<EdgeTouch>>,
+) -> bool {
+    let mut edge_touch = edge_touch.borrow_mut();
+    if event.event_type() == EventType::TouchBegin {
+        // Other fingers don't interrupt the touch on the edge.
+        if edge_touch.is_none() {
+            *edge_touch = touch_on_edge(window, event);
+            return edge_touch.is_some();
+        }
+        return false;
+    }
+    let touch = match &mut *edge_touch {
+        Some(touch) if touch.sequence == event.event_sequence() => touch,
+        _ => return false,
+    };
+    match event.event_type() {
+        EventType::TouchUpdate if !touch.resizing => {
+            if let (Some((cx, cy)), Some(gdk_window)) = (event.root_coords(), window.window()) {
+                let (x, y) = touch.start;
+                let threshold = util::input_settings().drag_threshold as f64;
+                if (cx - x).abs() > threshold || (cy - y).abs() > threshold {
+                    touch.resizing = true;
+                    gdk_window.begin_resize_drag_for_device(
+                        touch.edge,
+                        &touch.device,
+                        0,
+                        x as i32,
+                        y as i32,
+                        event.time(),
+                    );
+                }
+            }
+        }
+        EventType::TouchEnd | EventType::TouchCancel => *edge_touch = None,
+        _ => (),
+    }
+    true
+}
+
+/// Returns the touch of a `TouchBegin` on an edge of a borderless window.
+fn touch_on_edge(window: &gtk::Window, event: &gdk::Event) -> Option<EdgeTouch> {
+    if window.is_decorated() || !window.is_resizable() {
+        return None;
+    }
+    let gdk_window = window.window()?;
+    let (position, root) = (event.coords()?, event.root_coords()?);
+    let (x, y) = util::toplevel_position(&gdk_window, event.window(), position, root);
+    // Touches which aren't on the edges belong to the application.
+    match hit_test_relative(&gdk_window, x, y) {
+        WindowEdge::__Unknown(_) => None,
+        edge => Some(EdgeTouch {
+            sequence: event.event_sequence(),
+            device: event.device()?,
+            edge,
+            start: root,
+            resizing: false,
+        }),
+    }
+}
+
+/// The physical position of a pointer event relative to `window`.
+fn physical_pointer_position(
+    window: &gtk::Window,
+    gdk_window: &gdk::Window,
+    event_window: Option<gdk::Window>,
+    position: (f64, f64),
+    root: (f64, f64),
+) -> PhysicalPosition<f64> {
+    let position = util::toplevel_position(gdk_window, event_window, position, root);
+    util::logical_to_physical_pointer(position, window.scale_factor() as f64)
+}
+
+/// The physical position of a button event relative to `window`.
+fn button_position(window: &gtk::Window, event: &EventButton) -> Option<PhysicalPosition<f64>> {
+    let gdk_window = window.window()?;
+    Some(physical_pointer_position(
+        window,
+        &gdk_window,
+        event.window(),
+        event.position(),
+        event.root(),
+    ))
+}
+
+/// Sends a `MouseInput`, preceded by a `CursorMoved` to `position` when the cursor was last
+/// reported elsewhere.
+///
+/// No motion precedes a tap on a touchscreen or a click after the pointer was warped, the app
+/// would place the click at a stale position otherwise.
+#[allow(clippy::too_many_arguments)]
+fn send_mouse_input<T>(
+    tx: &crossbeam_channel::Sender<Event<'static, T>>,
+    id: WindowId,
+    last_cursor: &Cell<Option<PhysicalPosition<f64>>>,
+    position: Option<PhysicalPosition<f64>>,
+    device_id: RootDeviceId,
+    button: MouseButton,
+    state: ElementState,
+    modifiers: ModifiersState,
+) {
+    if let Some(position) = position {
+        if last_cursor.replace(Some(position)) != Some(position) {
+            send_cursor_moved(tx, id, position, device_id, modifiers);
+        }
+    }
+    if let Err(e) = tx.send(Event::WindowEvent {
+        window_id: RootWindowId(id),
+        event: WindowEvent::MouseInput {
+            button,
+            state,
+            device_id,
+            modifiers,
+        },
+    }) {
+        log::warn!("Failed to send mouse input event to event channel: {}", e);
+    }
+}
+
+fn send_cursor_moved<T>(
+    tx: &crossbeam_channel::Sender<Event<'static, T>>,
+    id: WindowId,
+    position: PhysicalPosition<f64>,
+    device_id: RootDeviceId,
+    modifiers: ModifiersState,
+) {
+    if let Err(e) = tx.send(Event::WindowEvent {
+        window_id: RootWindowId(id),
+        event: WindowEvent::CursorMoved {
+            position,
+            device_id,
+            modifiers,
+        },
+    }) {
+        log::warn!("Failed to send cursor moved event to event channel: {}", e);
+    }
+}
+
+/// Maps the button number of GDK to a `MouseButton`.
+fn mouse_button(button: u32) -> MouseButton {
+    match button {
+        1 => MouseButton::Left,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        _ => MouseButton::Other(button as u16),
+    }
+}
+
+/// Returns the name of a cursor icon in the cursor themes.
+fn cursor_name(icon: CursorIcon) -> &'static str {
+    match icon {
+        CursorIcon::Crosshair => "crosshair",
+        CursorIcon::Hand => "pointer",
+        CursorIcon::Arrow => "arrow",
+        CursorIcon::Move => "move",
+        CursorIcon::Text => "text",
+        CursorIcon::Wait => "wait",
+        CursorIcon::Help => "help",
+        CursorIcon::Progress => "progress",
+        CursorIcon::NotAllowed => "not-allowed",
+        CursorIcon::ContextMenu => "context-menu",
+        CursorIcon::Cell => "cell",
+        CursorIcon::VerticalText => "vertical-text",
+        CursorIcon::Alias => "alias",
+        CursorIcon::Copy => "copy",
+        CursorIcon::NoDrop => "no-drop",
+        CursorIcon::Grab => "grab",
+        CursorIcon::Grabbing => "grabbing",
+        CursorIcon::AllScroll => "all-scroll",
+        CursorIcon::ZoomIn => "zoom-in",
+        CursorIcon::ZoomOut => "zoom-out",
+        CursorIcon::EResize => "e-resize",
+        CursorIcon::NResize => "n-resize",
+        CursorIcon::NeResize => "ne-resize",
+        CursorIcon::NwResize => "nw-resize",
+        CursorIcon::SResize => "s-resize",
+        CursorIcon::SeResize => "se-resize",
+        CursorIcon::SwResize => "sw-resize",
+        CursorIcon::WResize => "w-resize",
+        CursorIcon::EwResize => "ew-resize",
+        CursorIcon::NsResize => "ns-resize",
+        CursorIcon::NeswResize => "nesw-resize",
+        CursorIcon::NwseResize => "nwse-resize",
+        CursorIcon::ColResize => "col-resize",
+        CursorIcon::RowResize => "row-resize",
+        CursorIcon::Default => "default",
+    }
+}
+
+/// Apply a window state request, which must be one of `Minimized`, `Maximized` or `Fullscreen`.
+fn apply_state_request(
+    window: &gtk::Window,
+    id: WindowId,
+    request: WindowRequest,
+    saved_video_modes: &RefCell<SavedVideoModes>,
+) {
+    match request {
+        WindowRequest::Minimized(minimized) => {
+            if minimized {
+                window.iconify();
+            } else {
+                window.deiconify();
+            }
+        }
+        WindowRequest::Maximized(maximized) => {
+            if maximized {
+                window.maximize();
+            } else {
+                window.unmaximize();
+            }
+        }
+        WindowRequest::Fullscreen(fullscreen) => {
+            // Any change of fullscreen, including another video mode, starts from the mode the
+            // monitor had before.
+            saved_video_modes.borrow_mut().restore(id);
+            match fullscreen {
+                Some(f) => {
+                    // Exclusive fullscreen without a video mode of XRandR was turned into
+                    // borderless by the `Window`.
+                    let m = match f {
+                        Fullscreen::Borderless(Some(m)) => Some(m.monitor),
+                        Fullscreen::Exclusive(video_mode) => {
+                            // The window manager fits the window to the new size of the monitor.
+                            if !saved_video_modes.borrow_mut().switch(id, &video_mode) {
+                                log::warn!(
+                                    "Failed to switch the video mode, using borderless fullscreen"
+                                );
+                            }
+                            Some(video_mode.monitor)
+                        }
+                        _ => None,
+                    };
+
+                    if let Some(monitor) = m {
+                        let display = window.display();
+                        let monitors = display.n_monitors();
+                        for i in 0..monitors {
+                            let m = display.monitor(i).unwrap();
+                            if m == monitor {
+                                let screen = display.default_screen();
+                                window.fullscreen_on_monitor(&screen, i);
+                            }
+                        }
+                    } else {
+                        window.fullscreen();
+                    }
+                }
+                None => window.unfullscreen(),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dpi::PhysicalSize, platform_impl::platform::DEVICE_ID};
+
+    #[test]
+    fn prioritized_events_are_drained_first() {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (prioritized_tx, prioritized_rx) = crossbeam_channel::unbounded();
+        let queue = EventQueue {
+            prioritized: prioritized_rx,
+            events: event_rx,
+            deferred: Default::default(),
+        };
+
+        for i in 0..10_000 {
+            event_tx.send(Event::UserEvent(i)).unwrap();
+        }
+        prioritized_tx.send(Event::UserEvent(-1)).unwrap();
+        prioritized_tx.send(Event::UserEvent(-2)).unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(event) = queue.try_recv() {
+            match event {
+                Event::UserEvent(i) => received.push(i),
+                _ => unreachable!(),
+            }
+        }
+
+        assert!(queue.is_empty());
+        assert_eq!(&received[..2], &[-1, -2]);
+        assert!(received[2..].iter().copied().eq(0..10_000));
+    }
+
+    #[test]
+    fn redraws_are_deduplicated() {
+        let (draw_tx, draw_rx) = crossbeam_channel::unbounded();
+        for id in [1, 2, 1, 1, 3, 2] {
+            draw_tx.send(WindowId(id)).unwrap();
+        }
+
+        let ids = pending_redraws(&draw_rx);
+        assert!(draw_rx.is_empty());
+        assert_eq!(ids, [WindowId(1), WindowId(2), WindowId(3)].into());
+        assert!(pending_redraws(&draw_rx).is_empty());
+    }
+
+    #[test]
+    fn queued_events_are_drained_at_once() {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (_prioritized_tx, prioritized_rx) = crossbeam_channel::unbounded();
+        let queue = EventQueue {
+            prioritized: prioritized_rx,
+            events: event_rx,
+            deferred: Default::default(),
+        };
+        #[allow(deprecated)]
+        let cursor_moved = |window: u64, x: f64| Event::WindowEvent {
+            window_id: RootWindowId(WindowId(window)),
+            event: WindowEvent::CursorMoved {
+                device_id: DEVICE_ID,
+                position: (x, 0.0).into(),
+                modifiers: Default::default(),
+            },
+        };
+
+        for i in 0..1_000 {
+            event_tx.send(Event::UserEvent(i)).unwrap();
+        }
+        event_tx.send(cursor_moved(1, 1.0)).unwrap();
+        event_tx.send(cursor_moved(1, 2.0)).unwrap();
+        event_tx.send(cursor_moved(2, 3.0)).unwrap();
+        event_tx.send(cursor_moved(1, 4.0)).unwrap();
+        event_tx.send(cursor_moved(1, 5.0)).unwrap();
+
+        let events = queue.drain();
+        assert!(queue.is_empty());
+        assert_eq!(events.len(), 1_003);
+        assert!(events[..1_000]
+            .iter()
+            .zip(0..)
+            .all(|(event, i)| matches!(event, Event::UserEvent(j) if *j == i)));
+        assert_eq!(events[1_000], cursor_moved(1, 2.0));
+        assert_eq!(events[1_001], cursor_moved(2, 3.0));
+        assert_eq!(events[1_002], cursor_moved(1, 5.0));
+    }
+
+    #[test]
+    fn proxy_wakes_up_a_blocking_iteration() {
+        let context = MainContext::new();
+        context
+            .with_thread_default(|| {
+                let (wakeup_tx, _source) = wakeup_channel(&context);
+                let sender = std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(50));
+                    wakeup_tx.send(()).unwrap();
+                });
+                let start = Instant::now();
+                // Would block forever without the wake up, there is no other source.
+                context.iteration(true);
+                assert!(start.elapsed() >= Duration::from_millis(50));
+                sender.join().unwrap();
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn close_requested_jumps_the_queue() {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (prioritized_tx, prioritized_rx) = crossbeam_channel::unbounded();
+        let queue = EventQueue {
+            prioritized: prioritized_rx,
+            events: event_rx,
+            deferred: Default::default(),
+        };
+        let close_requested = Event::WindowEvent {
+            window_id: RootWindowId(WindowId(1)),
+            event: WindowEvent::CloseRequested,
+        };
+        let destroyed = Event::WindowEvent {
+            window_id: RootWindowId(WindowId(1)),
+            event: WindowEvent::Destroyed,
+        };
+
+        for i in 0..100 {
+            event_tx.send(Event::UserEvent(i)).unwrap();
+        }
+        event_tx.send(destroyed.clone()).unwrap();
+        prioritized_tx.send(close_requested.clone()).unwrap();
+
+        let events = queue.drain();
+        assert_eq!(events.len(), 102);
+        assert_eq!(events[0], close_requested);
+        // Nothing of the window is delivered after its `Destroyed`.
+        assert_eq!(events[101], destroyed);
+    }
+
+    #[test]
+    fn undelivered_events_come_first() {
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (prioritized_tx, prioritized_rx) = crossbeam_channel::unbounded();
+        let queue = EventQueue {
+            prioritized: prioritized_rx,
+            events: event_rx,
+            deferred: Default::default(),
+        };
+
+        for i in 0..5 {
+            event_tx.send(Event::UserEvent(i)).unwrap();
+        }
+        let mut drained = queue.drain().into_iter();
+        assert_eq!(drained.next(), Some(Event::UserEvent(0)));
+        queue.defer(drained);
+        assert!(!queue.is_empty());
+
+        event_tx.send(Event::UserEvent(5)).unwrap();
+        prioritized_tx.send(Event::UserEvent(-1)).unwrap();
+        let received: Vec<_> = queue
+            .drain()
+            .into_iter()
+            .map(|event| match event {
+                Event::UserEvent(i) => i,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert!(queue.is_empty());
+        assert_eq!(received, [1, 2, 3, 4, -1, 5]);
+    }
+
+    #[test]
+    fn initial_state_follows_the_startup_order() {
+        let window_id = RootWindowId(WindowId(1));
+        let events = initial_state_events::<()>(WindowId(1), (400, 300), Some((10, 20)), true, 2.0);
+        assert_eq!(
+            events,
+            [
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Resized(PhysicalSize::new(800, 600)),
+                },
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Moved(PhysicalPosition::new(20, 40)),
+                },
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Focused(true),
+                },
+            ]
+        );
+
+        // Unfocused windows and unknown positions are left out.
+        let events = initial_state_events::<()>(WindowId(1), (400, 300), None, false, 1.0);
+        assert_eq!(
+            events,
+            [Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(PhysicalSize::new(400, 300)),
+            }]
+        );
+    }
+
+    fn cursor_moved(x: f64, y: f64) -> Event<'static, ()> {
+        Event::WindowEvent {
+            window_id: RootWindowId(WindowId(1)),
+            event: WindowEvent::CursorMoved {
+                position: PhysicalPosition::new(x, y),
+                device_id: DEVICE_ID,
+                modifiers: ModifiersState::empty(),
+            },
+        }
+    }
+
+    fn mouse_input(state: ElementState) -> Event<'static, ()> {
+        Event::WindowEvent {
+            window_id: RootWindowId(WindowId(1)),
+            event: WindowEvent::MouseInput {
+                device_id: DEVICE_ID,
+                state,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::empty(),
+            },
+        }
+    }
+
+    fn click(
+        tx: &crossbeam_channel::Sender<Event<'static, ()>>,
+        last_cursor: &Cell<Option<PhysicalPosition<f64>>>,
+        (x, y): (f64, f64),
+    ) {
+        for state in [ElementState::Pressed, ElementState::Released] {
+            send_mouse_input(
+                tx,
+                WindowId(1),
+                last_cursor,
+                Some(PhysicalPosition::new(x, y)),
+                DEVICE_ID,
+                MouseButton::Left,
+                state,
+                ModifiersState::empty(),
+            );
+        }
+    }
+
+    #[test]
+    fn tap_without_motion_reports_its_position() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let last_cursor = Cell::new(None);
+        click(&tx, &last_cursor, (30.0, 45.0));
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                cursor_moved(30.0, 45.0),
+                mouse_input(ElementState::Pressed),
+                mouse_input(ElementState::Released),
+            ]
+        );
+    }
+
+    #[test]
+    fn click_after_warp_reports_its_position() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        // The last motion was reported at (10, 10).
+        let last_cursor = Cell::new(Some(PhysicalPosition::new(10.0, 10.0)));
+        click(&tx, &last_cursor, (10.0, 10.0));
+        // No motion is reported for the warp, the press finds the cursor elsewhere.
+        click(&tx, &last_cursor, (200.0, 150.0));
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                mouse_input(ElementState::Pressed),
+                mouse_input(ElementState::Released),
+                cursor_moved(200.0, 150.0),
+                mouse_input(ElementState::Pressed),
+                mouse_input(ElementState::Released),
+            ]
+        );
+    }
+
+    #[test]
+    fn configure_after_the_startup_replay_is_not_reported_twice() {
+        let id = WindowId(1);
+        let reported = ReportedGeometry::default();
+        // The replay of a window shown before its handlers were connected.
+        reported.position.set(Some((10, 20)));
+        reported.size.set(Some((400, 300)));
+        reported.replayed.set(true);
+
+        // The configure event which was still pending reports the same geometry.
+        let events = configure_events::<()>(id, &reported, Some((10, 20)), (400, 300), 1.0);
+        assert!(events.is_empty());
+
+        // Later configure events are all reported.
+        let events = configure_events::<()>(id, &reported, Some((10, 20)), (400, 300), 1.0);
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn configure_after_the_startup_replay_reports_the_changes() {
+        let id = WindowId(1);
+        let window_id = RootWindowId(id);
+        let reported = ReportedGeometry::default();
+        reported.position.set(Some((10, 20)));
+        reported.size.set(Some((400, 300)));
+        reported.replayed.set(true);
+
+        // The window manager placed the window elsewhere before mapping it.
+        let events = configure_events::<()>(id, &reported, Some((50, 60)), (400, 300), 2.0);
+        assert_eq!(
+            events,
+            [Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Moved(PhysicalPosition::new(100, 120)),
+            }]
+        );
+        assert_eq!(reported.position.get(), Some((50, 60)));
+    }
+
+    #[test]
+    fn minimizing_reports_no_geometry() {
+        let reported = ReportedGeometry::default();
+        let events =
+            state_change_events::<()>(WindowId(1), &reported, true, Some((0, 0)), (1, 1), 1.0);
+        assert!(events.is_empty());
+        assert_eq!(reported.size.get(), None);
+    }
+
+    #[test]
+    fn maximizing_reports_only_changed_geometry() {
+        let id = WindowId(1);
+        let reported = ReportedGeometry::default();
+        reported.position.set(Some((0, 0)));
+        reported.size.set(Some((200, 150)));
+
+        let events = state_change_events::<()>(id, &reported, false, Some((0, 0)), (800, 600), 2.0);
+        assert_eq!(
+            events,
+            vec![Event::WindowEvent {
+                window_id: RootWindowId(id),
+                event: WindowEvent::Resized(PhysicalSize::new(1600, 1200)),
+            }]
+        );
+
+        // The configure event already reported the new size.
+        let events = state_change_events::<()>(id, &reported, false, Some((0, 0)), (800, 600), 2.0);
+        assert!(events.is_empty());
+    }
+}
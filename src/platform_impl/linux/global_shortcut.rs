@@ -0,0 +1,308 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    os::raw::{c_int, c_uint},
+    ptr,
+    rc::Rc,
+};
+
+use gdk::prelude::DisplayExtManual;
+use glib::ObjectType;
+use x11_dl::{keysym, xlib};
+
+use crate::{
+    error::{ExternalError, NotSupportedError},
+    event::{ModifiersState, ScanCode},
+    platform::unix::ShortcutId,
+    platform_impl::OsError,
+};
+
+/// Key grabs on the X11 root window, which report the key wherever the focus is.
+pub(crate) struct GlobalShortcuts {
+    display: gdk::Display,
+    grabber: RefCell<Option<Rc<Grabber>>>,
+}
+
+struct Grabber {
+    xlib: xlib::Xlib,
+    xdisplay: *mut xlib::Display,
+    root: xlib::Window,
+    /// The modifier masks of the locks, which are part of the state of the key events.
+    lock_masks: [c_uint; 4],
+    grabs: RefCell<HashMap<ShortcutId, (c_int, c_uint)>>,
+    next_id: Cell<u32>,
+    callbacks: RefCell<Vec<ShortcutCallback>>,
+}
+
+type ShortcutCallback = Rc<dyn Fn(ShortcutId)>;
+
+const MODIFIER_MAP: &[(ModifiersState, c_uint)] = &[
+    (ModifiersState::SHIFT, xlib::ShiftMask),
+    (ModifiersState::CTRL, xlib::ControlMask),
+    (ModifiersState::ALT, xlib::Mod1Mask),
+    (ModifiersState::LOGO, xlib::Mod4Mask),
+];
+
+fn modifiers_to_mask(modifiers: ModifiersState) -> c_uint {
+    MODIFIER_MAP
+        .iter()
+        .filter(|(modifier, _)| modifiers.contains(*modifier))
+        .fold(0, |mask, (_, x_mask)| mask | x_mask)
+}
+
+/// Keeps the modifiers of a key event's `state` which a grab can contain, the state also has e.g.
+/// the locks and the pressed pointer buttons.
+fn grabbed_modifiers(state: c_uint) -> c_uint {
+    state & modifiers_to_mask(ModifiersState::all())
+}
+
+/// Returns every combination of Caps Lock and `num_lock`, the grab of a key only matches the
+/// exact modifier state.
+fn lock_masks(num_lock: c_uint) -> [c_uint; 4] {
+    [0, xlib::LockMask, num_lock, xlib::LockMask | num_lock]
+}
+
+impl GlobalShortcuts {
+    pub(crate) fn new(display: &gdk::Display) -> Self {
+        Self {
+            display: display.clone(),
+            grabber: Default::default(),
+        }
+    }
+
+    pub(crate) fn register(
+        &self,
+        modifiers: ModifiersState,
+        scancode: ScanCode,
+    ) -> Result<ShortcutId, ExternalError> {
+        let grabber = self.grabber()?;
+        let keycode = match u8::try_from(scancode) {
+            Ok(keycode) if keycode >= 8 => keycode as c_int,
+            _ => {
+                return Err(ExternalError::Os(os_error!(OsError::Misc(
+                    "The scancode is not an X11 keycode"
+                ))))
+            }
+        };
+        let mask = modifiers_to_mask(modifiers);
+
+        let xdisplay = self.display.as_ptr() as *mut _;
+        unsafe {
+            // Another client holding the grab is reported as an asynchronous `BadAccess`.
+            gdk_x11_sys::gdk_x11_display_error_trap_push(xdisplay);
+            grabber.grab(keycode, mask);
+            if gdk_x11_sys::gdk_x11_display_error_trap_pop(xdisplay) != 0 {
+                gdk_x11_sys::gdk_x11_display_error_trap_push(xdisplay);
+                grabber.ungrab(keycode, mask);
+                gdk_x11_sys::gdk_x11_display_error_trap_pop_ignored(xdisplay);
+                return Err(ExternalError::Os(os_error!(OsError::Misc(
+                    "The shortcut is already grabbed by another client"
+                ))));
+            }
+        }
+
+        let id = ShortcutId(grabber.next_id.get());
+        grabber.next_id.set(id.0 + 1);
+        grabber.grabs.borrow_mut().insert(id, (keycode, mask));
+        Ok(id)
+    }
+
+    pub(crate) fn unregister(&self, id: ShortcutId) {
+        let grabber = match &*self.grabber.borrow() {
+            Some(grabber) => grabber.clone(),
+            None => return,
+        };
+        let grab = grabber.grabs.borrow_mut().remove(&id);
+        if let Some((keycode, mask)) = grab {
+            let xdisplay = self.display.as_ptr() as *mut _;
+            unsafe {
+                gdk_x11_sys::gdk_x11_display_error_trap_push(xdisplay);
+                grabber.ungrab(keycode, mask);
+                gdk_x11_sys::gdk_x11_display_error_trap_pop_ignored(xdisplay);
+            }
+        }
+    }
+
+    /// Releases all the grabs, callbacks and the event filter, they would outlive the event loop
+    /// otherwise.
+    pub(crate) fn clear(&self) {
+        let ids: Vec<_> = match &*self.grabber.borrow() {
+            Some(grabber) => {
+                grabber.callbacks.borrow_mut().clear();
+                grabber.grabs.borrow().keys().copied().collect()
+            }
+            None => return,
+        };
+        for id in ids {
+            self.unregister(id);
+        }
+        if let Some(grabber) = self.grabber.take() {
+            let data = Rc::as_ptr(&grabber);
+            unsafe {
+                gdk_sys::gdk_window_remove_filter(
+                    ptr::null_mut(),
+                    Some(key_filter),
+                    data as *mut _,
+                );
+                // The reference the filter held.
+                drop(Rc::from_raw(data));
+            }
+        }
+    }
+
+    pub(crate) fn connect<F: Fn(ShortcutId) + 'static>(&self, f: F) {
+        if let Ok(grabber) = self.grabber() {
+            grabber.callbacks.borrow_mut().push(Rc::new(f));
+        }
+    }
+
+    /// Returns the grabber, set up on first use.
+    fn grabber(&self) -> Result<Rc<Grabber>, ExternalError> {
+        if let Some(grabber) = &*self.grabber.borrow() {
+            return Ok(grabber.clone());
+        }
+        // Wayland leaves global shortcuts to the `org.freedesktop.portal.GlobalShortcuts` portal.
+        if !self.display.backend().is_x11() {
+            return Err(ExternalError::NotSupported(NotSupportedError::new()));
+        }
+        let xlib = xlib::Xlib::open()
+            .map_err(|_| ExternalError::Os(os_error!(OsError::Misc("Failed to load Xlib"))))?;
+
+        let grabber = unsafe {
+            let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(self.display.as_ptr() as *mut _)
+                as *mut xlib::Display;
+            let num_lock = num_lock_mask(&xlib, xdisplay);
+            let grabber = Rc::new(Grabber {
+                xlib,
+                xdisplay,
+                root: gdk_x11_sys::gdk_x11_get_default_root_xwindow(),
+                lock_masks: lock_masks(num_lock),
+                grabs: Default::default(),
+                next_id: Cell::new(0),
+                callbacks: Default::default(),
+            });
+            // The filter holds a reference until `clear` removes it.
+            gdk_sys::gdk_window_add_filter(
+                ptr::null_mut(),
+                Some(key_filter),
+                Rc::into_raw(grabber.clone()) as *mut _,
+            );
+            grabber
+        };
+        self.grabber.replace(Some(grabber.clone()));
+        Ok(grabber)
+    }
+}
+
+/// Looks up the modifier Num Lock is mapped to, usually `Mod2Mask`.
+unsafe fn num_lock_mask(xlib: &xlib::Xlib, xdisplay: *mut xlib::Display) -> c_uint {
+    let keycode = (xlib.XKeysymToKeycode)(xdisplay, keysym::XK_Num_Lock as _);
+    let map = (xlib.XGetModifierMapping)(xdisplay);
+    if map.is_null() {
+        return xlib::Mod2Mask;
+    }
+    let keys_per_modifier = (*map).max_keypermod as usize;
+    let mut mask = xlib::Mod2Mask;
+    for modifier in (0..8).filter(|_| keycode != 0) {
+        let keycodes = std::slice::from_raw_parts(
+            (*map).modifiermap.add(modifier * keys_per_modifier),
+            keys_per_modifier,
+        );
+        if keycodes.contains(&keycode) {
+            mask = 1 << modifier;
+            break;
+        }
+    }
+    (xlib.XFreeModifiermap)(map);
+    mask
+}
+
+impl Grabber {
+    unsafe fn grab(&self, keycode: c_int, mask: c_uint) {
+        for lock_mask in self.lock_masks {
+            (self.xlib.XGrabKey)(
+                self.xdisplay,
+                keycode,
+                mask | lock_mask,
+                self.root,
+                xlib::False,
+                xlib::GrabModeAsync,
+                xlib::GrabModeAsync,
+            );
+        }
+    }
+
+    unsafe fn ungrab(&self, keycode: c_int, mask: c_uint) {
+        for lock_mask in self.lock_masks {
+            (self.xlib.XUngrabKey)(self.xdisplay, keycode, mask | lock_mask, self.root);
+        }
+    }
+
+    /// Returns the shortcut matching a key press.
+    fn shortcut(&self, keycode: c_int, state: c_uint) -> Option<ShortcutId> {
+        let mask = grabbed_modifiers(state);
+        self.grabs
+            .borrow()
+            .iter()
+            .find(|(_, &grab)| grab == (keycode, mask))
+            .map(|(id, _)| *id)
+    }
+}
+
+unsafe extern "C" fn key_filter(
+    xevent: *mut gdk_sys::GdkXEvent,
+    _event: *mut gdk_sys::GdkEvent,
+    data: glib::ffi::gpointer,
+) -> gdk_sys::GdkFilterReturn {
+    let grabber = &*(data as *const Grabber);
+    let xevent = &*(xevent as *const xlib::XEvent);
+    if xevent.get_type() != xlib::KeyPress {
+        return gdk_sys::GDK_FILTER_CONTINUE;
+    }
+    let key: &xlib::XKeyEvent = xevent.as_ref();
+    if key.window != grabber.root {
+        return gdk_sys::GDK_FILTER_CONTINUE;
+    }
+    match grabber.shortcut(key.keycode as c_int, key.state) {
+        Some(id) => {
+            // A callback may register further shortcuts or callbacks.
+            let callbacks = grabber.callbacks.borrow().clone();
+            for callback in callbacks {
+                callback(id);
+            }
+            gdk_sys::GDK_FILTER_REMOVE
+        }
+        None => gdk_sys::GDK_FILTER_CONTINUE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifiers_are_mapped_to_x11_masks() {
+        assert_eq!(modifiers_to_mask(ModifiersState::empty()), 0);
+        assert_eq!(
+            modifiers_to_mask(ModifiersState::CTRL | ModifiersState::ALT),
+            xlib::ControlMask | xlib::Mod1Mask
+        );
+        assert_eq!(modifiers_to_mask(ModifiersState::LOGO), xlib::Mod4Mask);
+    }
+
+    #[test]
+    fn key_state_is_reduced_to_the_grabbed_modifiers() {
+        let state = xlib::ControlMask | xlib::LockMask | xlib::Mod2Mask | xlib::Button1Mask;
+        assert_eq!(grabbed_modifiers(state), xlib::ControlMask);
+        assert_eq!(grabbed_modifiers(xlib::Mod5Mask), 0);
+    }
+
+    #[test]
+    fn all_lock_combinations_are_grabbed() {
+        let masks = lock_masks(xlib::Mod2Mask);
+        assert!(masks.contains(&0));
+        assert!(masks.contains(&xlib::LockMask));
+        assert!(masks.contains(&xlib::Mod2Mask));
+        assert!(masks.contains(&(xlib::LockMask | xlib::Mod2Mask)));
+    }
+}
@@ -3,39 +3,74 @@ use gdk::{
     EventKey, ModifierType,
 };
 
-use crate::event::{ModifiersState, VirtualKeyCode};
+use crate::event::{ElementState, ModifiersState, VirtualKeyCode};
 
 const MODIFIER_MAP: &[(ModifierType, ModifiersState)] = &[
     (ModifierType::SHIFT_MASK, ModifiersState::SHIFT),
     (ModifierType::MOD1_MASK, ModifiersState::ALT),
+    // Some keymaps only report Alt through the virtual Meta modifier.
+    (ModifierType::META_MASK, ModifiersState::ALT),
     (ModifierType::CONTROL_MASK, ModifiersState::CTRL),
     (ModifierType::SUPER_MASK, ModifiersState::LOGO),
+    // `LOCK_MASK` (Caps Lock) has no `ModifiersState` counterpart, so it's ignored.
 ];
 
-// we use the EventKey to extract the modifier mainly because
-// we need to have the modifier before the second key is entered to follow
-// other os' logic -- this way we can emit the new `ModifiersState` before
-// we receive the next key, if needed the developer can update his local state.
-pub(crate) fn get_modifiers(key: &EventKey) -> ModifiersState {
-    let state = key.state();
-
-    // start with empty state
-    let mut result = ModifiersState::empty();
+/// Returns the modifiers once `key` is handled.
+///
+/// The state of a GDK key event is the one from before the event, so a modifier key changing it
+/// is applied on top. This way the new `ModifiersState` is emitted along with the modifier key
+/// instead of lingering until the next key.
+pub(crate) fn get_modifiers(key: &EventKey, element_state: ElementState) -> ModifiersState {
+    modifiers_after(key.state(), key.keyval(), element_state)
+}
 
-    // loop trough our modifier map
-    for (gdk_mod, modifier) in MODIFIER_MAP {
-        if state == *gdk_mod {
-            result |= *modifier;
-        }
+fn modifiers_after(state: ModifierType, key: Key, element_state: ElementState) -> ModifiersState {
+    let mut result = modifiers_from_state(state);
+    if let Some(modifier) = key_modifier(key) {
+        result.set(modifier, element_state == ElementState::Pressed);
     }
     result
 }
 
+/// Queries the modifiers currently held on the keyboard of `display`.
+///
+/// Unlike the state of key events this includes modifiers pressed or released while none of our
+/// windows had the focus.
+pub(crate) fn current_modifiers(display: &gdk::Display) -> ModifiersState {
+    gdk::Keymap::for_display(display)
+        .map(|keymap| {
+            modifiers_from_state(ModifierType::from_bits_truncate(keymap.modifier_state()))
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the modifiers held according to the state of a GDK event.
+pub(crate) fn modifiers_from_state(state: ModifierType) -> ModifiersState {
+    // Latched states like Num Lock (`MOD2_MASK`) are set alongside and don't matter.
+    MODIFIER_MAP
+        .iter()
+        .filter(|(gdk_mod, _)| state.contains(*gdk_mod))
+        .fold(ModifiersState::empty(), |result, (_, modifier)| {
+            result | *modifier
+        })
+}
+
+#[allow(non_upper_case_globals)]
+fn key_modifier(key: Key) -> Option<ModifiersState> {
+    match key {
+        Shift_L | Shift_R => Some(ModifiersState::SHIFT),
+        Control_L | Control_R => Some(ModifiersState::CTRL),
+        Alt_L | Alt_R | Meta_L | Meta_R => Some(ModifiersState::ALT),
+        Super_L | Super_R => Some(ModifiersState::LOGO),
+        _ => None,
+    }
+}
+
 #[allow(clippy::just_underscores_and_digits, non_upper_case_globals)]
 pub(crate) fn gdk_key_to_virtual_key(gdk_key: Key) -> Option<VirtualKeyCode> {
     match gdk_key {
         Escape => Some(VirtualKeyCode::Escape),
-        BackSpace => Some(VirtualKeyCode::Backslash),
+        BackSpace => Some(VirtualKeyCode::Back),
         Tab | ISO_Left_Tab => Some(VirtualKeyCode::Tab),
         Return => Some(VirtualKeyCode::Return),
         Control_L => Some(VirtualKeyCode::LControl),
@@ -60,6 +95,69 @@ pub(crate) fn gdk_key_to_virtual_key(gdk_key: Key) -> Option<VirtualKeyCode> {
         F10 => Some(VirtualKeyCode::F10),
         F11 => Some(VirtualKeyCode::F11),
         F12 => Some(VirtualKeyCode::F12),
+        F13 => Some(VirtualKeyCode::F13),
+        F14 => Some(VirtualKeyCode::F14),
+        F15 => Some(VirtualKeyCode::F15),
+        F16 => Some(VirtualKeyCode::F16),
+        F17 => Some(VirtualKeyCode::F17),
+        F18 => Some(VirtualKeyCode::F18),
+        F19 => Some(VirtualKeyCode::F19),
+        F20 => Some(VirtualKeyCode::F20),
+        F21 => Some(VirtualKeyCode::F21),
+        F22 => Some(VirtualKeyCode::F22),
+        F23 => Some(VirtualKeyCode::F23),
+        F24 => Some(VirtualKeyCode::F24),
+
+        a | A => Some(VirtualKeyCode::A),
+        b | B => Some(VirtualKeyCode::B),
+        c | C => Some(VirtualKeyCode::C),
+        d | D => Some(VirtualKeyCode::D),
+        e | E => Some(VirtualKeyCode::E),
+        f | F => Some(VirtualKeyCode::F),
+        g | G => Some(VirtualKeyCode::G),
+        h | H => Some(VirtualKeyCode::H),
+        i | I => Some(VirtualKeyCode::I),
+        j | J => Some(VirtualKeyCode::J),
+        k | K => Some(VirtualKeyCode::K),
+        l | L => Some(VirtualKeyCode::L),
+        m | M => Some(VirtualKeyCode::M),
+        n | N => Some(VirtualKeyCode::N),
+        o | O => Some(VirtualKeyCode::O),
+        p | P => Some(VirtualKeyCode::P),
+        q | Q => Some(VirtualKeyCode::Q),
+        r | R => Some(VirtualKeyCode::R),
+        s | S => Some(VirtualKeyCode::S),
+        t | T => Some(VirtualKeyCode::T),
+        u | U => Some(VirtualKeyCode::U),
+        v | V => Some(VirtualKeyCode::V),
+        w | W => Some(VirtualKeyCode::W),
+        x | X => Some(VirtualKeyCode::X),
+        y | Y => Some(VirtualKeyCode::Y),
+        z | Z => Some(VirtualKeyCode::Z),
+
+        _0 | parenright => Some(VirtualKeyCode::Key0),
+        _1 | exclam => Some(VirtualKeyCode::Key1),
+        _2 | at => Some(VirtualKeyCode::Key2),
+        _3 | numbersign => Some(VirtualKeyCode::Key3),
+        _4 | dollar => Some(VirtualKeyCode::Key4),
+        _5 | percent => Some(VirtualKeyCode::Key5),
+        _6 | asciicircum => Some(VirtualKeyCode::Key6),
+        _7 | ampersand => Some(VirtualKeyCode::Key7),
+        _8 | asterisk => Some(VirtualKeyCode::Key8),
+        _9 | parenleft => Some(VirtualKeyCode::Key9),
+
+        space => Some(VirtualKeyCode::Space),
+        minus | underscore => Some(VirtualKeyCode::Minus),
+        equal | plus => Some(VirtualKeyCode::Equals),
+        bracketleft | braceleft => Some(VirtualKeyCode::LBracket),
+        bracketright | braceright => Some(VirtualKeyCode::RBracket),
+        backslash | bar => Some(VirtualKeyCode::Backslash),
+        semicolon | colon => Some(VirtualKeyCode::Semicolon),
+        apostrophe | quotedbl => Some(VirtualKeyCode::Apostrophe),
+        comma | less => Some(VirtualKeyCode::Comma),
+        period | greater => Some(VirtualKeyCode::Period),
+        slash | question => Some(VirtualKeyCode::Slash),
+        grave | asciitilde => Some(VirtualKeyCode::Grave),
 
         Print => Some(VirtualKeyCode::Snapshot),
         Scroll_Lock => Some(VirtualKeyCode::Scroll),
@@ -79,32 +177,156 @@ pub(crate) fn gdk_key_to_virtual_key(gdk_key: Key) -> Option<VirtualKeyCode> {
         Left => Some(VirtualKeyCode::Left),
         Right => Some(VirtualKeyCode::Right),
         // Clear => Some(VirtualKeyCode::Clear),
-
-        // Menu => Some(VirtualKeyCode::ContextMenu),
-        // WakeUp => Some(VirtualKeyCode::WakeUp),
+        Menu => Some(VirtualKeyCode::Apps),
         // Launch0 => Some(VirtualKeyCode::LaunchApplication1),
         // Launch1 => Some(VirtualKeyCode::LaunchApplication2),
         // ISO_Level3_Shift => Some(VirtualKeyCode::AltGraph),
-
+        KP_0 => Some(VirtualKeyCode::Numpad0),
+        KP_1 => Some(VirtualKeyCode::Numpad1),
+        KP_2 => Some(VirtualKeyCode::Numpad2),
+        KP_3 => Some(VirtualKeyCode::Numpad3),
+        KP_4 => Some(VirtualKeyCode::Numpad4),
+        KP_5 => Some(VirtualKeyCode::Numpad5),
+        KP_6 => Some(VirtualKeyCode::Numpad6),
+        KP_7 => Some(VirtualKeyCode::Numpad7),
+        KP_8 => Some(VirtualKeyCode::Numpad8),
+        KP_9 => Some(VirtualKeyCode::Numpad9),
+        KP_Enter => Some(VirtualKeyCode::NumpadEnter),
+        KP_Add => Some(VirtualKeyCode::NumpadAdd),
+        KP_Subtract => Some(VirtualKeyCode::NumpadSubtract),
+        KP_Multiply => Some(VirtualKeyCode::NumpadMultiply),
+        KP_Divide => Some(VirtualKeyCode::NumpadDivide),
+        KP_Decimal => Some(VirtualKeyCode::NumpadDecimal),
+        KP_Separator => Some(VirtualKeyCode::NumpadComma),
+        KP_Equal => Some(VirtualKeyCode::NumpadEquals),
+        // Keypad keys with num lock off.
         // KP_Begin => Some(VirtualKeyCode::Clear),
-        // KP_Delete => Some(VirtualKeyCode::Delete),
-        // KP_Down => Some(VirtualKeyCode::ArrowDown),
-        // KP_End => Some(VirtualKeyCode::End),
-        // KP_Enter => Some(VirtualKeyCode::NumpadEnter),
-        // KP_F1 => Some(VirtualKeyCode::F1),
-        // KP_F2 => Some(VirtualKeyCode::F2),
-        // KP_F3 => Some(VirtualKeyCode::F3),
-        // KP_F4 => Some(VirtualKeyCode::F4),
-        // KP_Home => Some(VirtualKeyCode::Home),
-        // KP_Insert => Some(VirtualKeyCode::Insert),
-        // KP_Left => Some(VirtualKeyCode::ArrowLeft),
-        // KP_Page_Down => Some(VirtualKeyCode::PageDown),
-        // KP_Page_Up => Some(VirtualKeyCode::PageUp),
-        // KP_Right => Some(VirtualKeyCode::ArrowRight),
-        // // KP_Separator? What does it map to?
-        // KP_Tab => Some(VirtualKeyCode::Tab),
-        // KP_Up => Some(VirtualKeyCode::ArrowUp),
-        // TODO: more mappings (media etc)
+        KP_Delete => Some(VirtualKeyCode::Delete),
+        KP_Down => Some(VirtualKeyCode::Down),
+        KP_End => Some(VirtualKeyCode::End),
+        KP_F1 => Some(VirtualKeyCode::F1),
+        KP_F2 => Some(VirtualKeyCode::F2),
+        KP_F3 => Some(VirtualKeyCode::F3),
+        KP_F4 => Some(VirtualKeyCode::F4),
+        KP_Home => Some(VirtualKeyCode::Home),
+        KP_Insert => Some(VirtualKeyCode::Insert),
+        KP_Left => Some(VirtualKeyCode::Left),
+        KP_Page_Down => Some(VirtualKeyCode::PageDown),
+        KP_Page_Up => Some(VirtualKeyCode::PageUp),
+        KP_Right => Some(VirtualKeyCode::Right),
+        KP_Tab => Some(VirtualKeyCode::Tab),
+        KP_Up => Some(VirtualKeyCode::Up),
+
+        // XF86 media and launch keys.
+        AudioPlay | AudioPause => Some(VirtualKeyCode::PlayPause),
+        AudioStop => Some(VirtualKeyCode::MediaStop),
+        AudioNext => Some(VirtualKeyCode::NextTrack),
+        AudioPrev => Some(VirtualKeyCode::PrevTrack),
+        AudioMute => Some(VirtualKeyCode::Mute),
+        AudioRaiseVolume => Some(VirtualKeyCode::VolumeUp),
+        AudioLowerVolume => Some(VirtualKeyCode::VolumeDown),
+        AudioMedia => Some(VirtualKeyCode::MediaSelect),
+        Mail => Some(VirtualKeyCode::Mail),
+        Calculator => Some(VirtualKeyCode::Calculator),
+        MyComputer => Some(VirtualKeyCode::MyComputer),
+        HomePage => Some(VirtualKeyCode::WebHome),
+        Search => Some(VirtualKeyCode::WebSearch),
+        Back => Some(VirtualKeyCode::WebBack),
+        Forward => Some(VirtualKeyCode::WebForward),
+        Stop => Some(VirtualKeyCode::WebStop),
+        Refresh => Some(VirtualKeyCode::WebRefresh),
+        Favorites => Some(VirtualKeyCode::WebFavorites),
+        Sleep => Some(VirtualKeyCode::Sleep),
+        PowerOff => Some(VirtualKeyCode::Power),
+        WakeUp => Some(VirtualKeyCode::Wake),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_key_mapping() {
+        let cases = [
+            (BackSpace, VirtualKeyCode::Back),
+            (backslash, VirtualKeyCode::Backslash),
+            (a, VirtualKeyCode::A),
+            (Z, VirtualKeyCode::Z),
+            (_0, VirtualKeyCode::Key0),
+            (parenleft, VirtualKeyCode::Key9),
+            (minus, VirtualKeyCode::Minus),
+            (equal, VirtualKeyCode::Equals),
+            (bracketleft, VirtualKeyCode::LBracket),
+            (bracketright, VirtualKeyCode::RBracket),
+            (semicolon, VirtualKeyCode::Semicolon),
+            (apostrophe, VirtualKeyCode::Apostrophe),
+            (comma, VirtualKeyCode::Comma),
+            (period, VirtualKeyCode::Period),
+            (slash, VirtualKeyCode::Slash),
+            (grave, VirtualKeyCode::Grave),
+            (KP_5, VirtualKeyCode::Numpad5),
+            (KP_Enter, VirtualKeyCode::NumpadEnter),
+            (KP_Decimal, VirtualKeyCode::NumpadDecimal),
+            (KP_Add, VirtualKeyCode::NumpadAdd),
+            (F24, VirtualKeyCode::F24),
+            (AudioPlay, VirtualKeyCode::PlayPause),
+            (AudioMute, VirtualKeyCode::Mute),
+            (AudioRaiseVolume, VirtualKeyCode::VolumeUp),
+            (AudioLowerVolume, VirtualKeyCode::VolumeDown),
+            (Back, VirtualKeyCode::WebBack),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(gdk_key_to_virtual_key(key), Some(expected), "{:?}", key);
+        }
+        assert_eq!(gdk_key_to_virtual_key(VoidSymbol), None);
+    }
+
+    #[test]
+    fn combined_modifier_masks() {
+        assert_eq!(
+            modifiers_from_state(ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK),
+            ModifiersState::CTRL | ModifiersState::SHIFT
+        );
+        assert_eq!(
+            modifiers_from_state(ModifierType::CONTROL_MASK | ModifierType::MOD2_MASK),
+            ModifiersState::CTRL
+        );
+        assert_eq!(
+            modifiers_from_state(ModifierType::MOD2_MASK | ModifierType::LOCK_MASK),
+            ModifiersState::empty()
+        );
+    }
+
+    #[test]
+    fn modifier_key_events() {
+        // Ctrl+Shift+A, the states are the ones from before each event.
+        assert_eq!(
+            modifiers_after(ModifierType::empty(), Control_L, ElementState::Pressed),
+            ModifiersState::CTRL
+        );
+        assert_eq!(
+            modifiers_after(ModifierType::CONTROL_MASK, Shift_L, ElementState::Pressed),
+            ModifiersState::CTRL | ModifiersState::SHIFT
+        );
+        assert_eq!(
+            modifiers_after(
+                ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK,
+                a,
+                ElementState::Pressed
+            ),
+            ModifiersState::CTRL | ModifiersState::SHIFT
+        );
+
+        // Releasing the last modifier leaves nothing behind.
+        assert_eq!(
+            modifiers_after(
+                ModifierType::CONTROL_MASK,
+                Control_R,
+                ElementState::Released
+            ),
+            ModifiersState::empty()
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Layer shell surfaces through the `gtk-layer-shell` library.
+
+use std::os::raw::c_int;
+
+use glib::{translate::ToGlibPtr, Cast};
+
+use crate::platform::unix::{Anchors, KeyboardInteractivity, Layer, LayerShellConfig};
+
+#[link(name = "gtk-layer-shell")]
+extern "C" {
+    fn gtk_layer_is_supported() -> glib::ffi::gboolean;
+    fn gtk_layer_init_for_window(window: *mut gtk::ffi::GtkWindow);
+    fn gtk_layer_set_layer(window: *mut gtk::ffi::GtkWindow, layer: c_int);
+    fn gtk_layer_set_anchor(
+        window: *mut gtk::ffi::GtkWindow,
+        edge: c_int,
+        anchor_to_edge: glib::ffi::gboolean,
+    );
+    fn gtk_layer_set_exclusive_zone(window: *mut gtk::ffi::GtkWindow, exclusive_zone: c_int);
+    fn gtk_layer_set_keyboard_mode(window: *mut gtk::ffi::GtkWindow, mode: c_int);
+}
+
+// Values of `GtkLayerShellEdge`.
+const EDGES: [(Anchors, c_int); 4] = [
+    (Anchors::LEFT, 0),
+    (Anchors::RIGHT, 1),
+    (Anchors::TOP, 2),
+    (Anchors::BOTTOM, 3),
+];
+
+/// Whether the compositor supports the layer shell protocol.
+pub fn is_supported() -> bool {
+    unsafe { gtk_layer_is_supported() != glib::ffi::GFALSE }
+}
+
+/// Turns `window` into a layer surface, it must not be realized yet.
+pub fn init(window: &gtk::ApplicationWindow, config: &LayerShellConfig) {
+    let window: *mut gtk::ffi::GtkWindow = window.upcast_ref::<gtk::Window>().to_glib_none().0;
+    let layer = match config.layer {
+        Layer::Background => 0,
+        Layer::Bottom => 1,
+        Layer::Top => 2,
+        Layer::Overlay => 3,
+    };
+    let keyboard_mode = match config.keyboard_interactivity {
+        KeyboardInteractivity::None => 0,
+        KeyboardInteractivity::Exclusive => 1,
+        KeyboardInteractivity::OnDemand => 2,
+    };
+    unsafe {
+        gtk_layer_init_for_window(window);
+        gtk_layer_set_layer(window, layer);
+        for (anchor, edge) in EDGES {
+            gtk_layer_set_anchor(window, edge, config.anchors.contains(anchor).into());
+        }
+        gtk_layer_set_exclusive_zone(window, config.exclusive_zone);
+        gtk_layer_set_keyboard_mode(window, keyboard_mode);
+    }
+}
@@ -3,13 +3,21 @@
 use std::fmt;
 
 use crate::event::DeviceId as RootDeviceId;
+use crate::platform::unix::DefaultHandlers;
+#[cfg(feature = "layer-shell")]
+use crate::platform::unix::LayerShellConfig;
 
 pub(crate) use crate::icon::RgbaIcon as PlatformIcon;
 use crate::platform_impl::Fullscreen;
 
+mod device;
 mod eventloop;
+mod global_shortcut;
 mod keyboard;
+#[cfg(feature = "layer-shell")]
+mod layer_shell;
 mod monitor;
+mod tearing_control;
 mod util;
 mod window;
 
@@ -26,10 +34,11 @@ pub(crate) enum Backend {
     // Wayland,
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct PlatformSpecificEventLoopAttributes {
     pub(crate) forced_backend: Option<Backend>,
     pub(crate) any_thread: bool,
+    pub(crate) app_id: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,11 +58,17 @@ pub struct PlatformSpecificWindowBuilderAttributes {
     pub name: Option<ApplicationName>,
     pub parent: Option<gtk::Window>,
     pub skip_taskbar: bool,
+    pub modal: bool,
     pub auto_transparent: bool,
     pub double_buffered: bool,
     pub app_paintable: bool,
     pub rgba_visual: bool,
     pub default_vbox: bool,
+    pub size_from_content: bool,
+    pub default_handlers: DefaultHandlers,
+    pub cursor_moved: bool,
+    #[cfg(feature = "layer-shell")]
+    pub layer_shell: Option<LayerShellConfig>,
 }
 
 impl Default for PlatformSpecificWindowBuilderAttributes {
@@ -62,26 +77,31 @@ impl Default for PlatformSpecificWindowBuilderAttributes {
             name: None,
             parent: None,
             skip_taskbar: Default::default(),
+            modal: false,
             auto_transparent: true,
             double_buffered: true,
             app_paintable: false,
             rgba_visual: false,
             default_vbox: true,
+            size_from_content: false,
+            default_handlers: DefaultHandlers::all(),
+            cursor_moved: true,
+            #[cfg(feature = "layer-shell")]
+            layer_shell: None,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum OsError {
-    // Misc(&'static str),
+    Misc(&'static str),
 }
 
 impl fmt::Display for OsError {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        // match *self {
-        //     OsError::Misc(e) => _f.pad(e),
-        // }
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            OsError::Misc(e) => f.pad(e),
+        }
     }
 }
 
@@ -132,5 +152,5 @@ impl DeviceId {
     }
 }
 
-// TODO: currently we use a dummy device id, find if we can get device id from gtk
+/// Id of events whose physical device is unknown, the others get one from `device::DeviceIds`.
 pub(crate) const DEVICE_ID: RootDeviceId = RootDeviceId(DeviceId(0));
@@ -1,15 +1,116 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    os::raw::c_int,
+    ptr, slice,
+    sync::Arc,
+};
+
 use crate::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
-use gdk::prelude::MonitorExt;
+use gdk::prelude::{DisplayExtManual, MonitorExt};
+use glib::ObjectType;
+use once_cell::sync::OnceCell;
+use x11_dl::{xlib, xrandr};
+
+use super::{util, WindowId};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone)]
 pub struct MonitorHandle {
     pub(crate) monitor: gdk::Monitor,
+    /// Shared by the clones of the handle, so the display server is asked at most once.
+    info: Arc<OnceCell<MonitorInfo>>,
+}
+
+/// Identification of a monitor, queried the first time it is needed.
+#[derive(Debug, Default)]
+struct MonitorInfo {
+    manufacturer: Option<String>,
+    model: Option<String>,
+    connector: Option<String>,
+}
+
+impl MonitorInfo {
+    fn query(monitor: &gdk::Monitor) -> Self {
+        let mut info = MonitorInfo {
+            manufacturer: monitor.manufacturer().map(|s| s.to_string()),
+            model: monitor.model().map(|s| s.to_string()),
+            connector: None,
+        };
+        let is_x11 = monitor
+            .display()
+            .map(|display| display.backend().is_x11())
+            .unwrap_or(false);
+        if is_x11 {
+            // GDK reports the output name as the model on X11, the EDID has the actual one.
+            if let Some((connector, edid)) = xrandr_output_info(monitor) {
+                let (manufacturer, model) = edid.as_deref().map(parse_edid).unwrap_or_default();
+                info.manufacturer = manufacturer.or(info.manufacturer);
+                info.model = model;
+                info.connector = Some(connector);
+            }
+        }
+        info
+    }
+}
+
+impl PartialEq for MonitorHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.monitor == other.monitor
+    }
+}
+
+impl Eq for MonitorHandle {}
+
+impl PartialOrd for MonitorHandle {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MonitorHandle {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.monitor.cmp(&other.monitor)
+    }
 }
 
 impl MonitorHandle {
-    pub fn new(display: &gdk::Display, number: i32) -> Self {
-        let monitor = display.monitor(number).unwrap();
-        Self { monitor }
+    pub(crate) fn new(monitor: gdk::Monitor) -> Self {
+        Self {
+            monitor,
+            info: Default::default(),
+        }
+    }
+
+    fn info(&self) -> &MonitorInfo {
+        self.info.get_or_init(|| MonitorInfo::query(&self.monitor))
+    }
+
+    #[inline]
+    pub fn manufacturer(&self) -> Option<String> {
+        self.info().manufacturer.clone()
+    }
+
+    #[inline]
+    pub fn model(&self) -> Option<String> {
+        self.info().model.clone()
+    }
+
+    #[inline]
+    pub fn connector(&self) -> Option<String> {
+        self.info().connector.clone()
+    }
+
+    #[inline]
+    pub fn is_builtin(&self) -> bool {
+        self.info()
+            .connector
+            .as_deref()
+            .map(|connector| {
+                ["eDP", "LVDS", "DSI"]
+                    .iter()
+                    .any(|prefix| connector.starts_with(prefix))
+            })
+            .unwrap_or(false)
     }
 
     #[inline]
@@ -49,13 +150,221 @@ impl MonitorHandle {
 
     #[inline]
     pub fn video_modes(&self) -> Box<dyn Iterator<Item = VideoMode>> {
-        Box::new(
+        let is_x11 = self
+            .monitor
+            .display()
+            .map(|display| display.backend().is_x11())
+            .unwrap_or(false);
+        let modes = if is_x11 {
+            self.xrandr_video_modes()
+        } else {
+            None
+        };
+
+        // Wayland doesn't let clients enumerate modes, so we only report the current one.
+        let modes = modes.unwrap_or_else(|| {
+            let rect = self.monitor.geometry();
             vec![VideoMode {
                 monitor: self.monitor.clone(),
+                size: LogicalSize {
+                    width: rect.width() as u32,
+                    height: rect.height() as u32,
+                }
+                .to_physical(self.scale_factor()),
+                bit_depth: 32,
+                refresh_rate_millihertz: self.monitor.refresh_rate() as u32,
+                xrandr_mode: None,
             }]
-            .into_iter(),
-        )
+        });
+
+        Box::new(modes.into_iter())
     }
+
+    /// Query XRandR for every mode the output of this monitor supports.
+    fn xrandr_video_modes(&self) -> Option<Vec<VideoMode>> {
+        let display = self.monitor.display()?;
+        let bit_depth = display
+            .default_screen()
+            .system_visual()
+            .map(|visual| visual.depth() as u16)
+            .unwrap_or(24);
+        let xrandr = util::xrandr()?;
+
+        unsafe {
+            let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(display.as_ptr() as *mut _)
+                as *mut xlib::Display;
+            let output = gdk_x11_sys::gdk_x11_monitor_get_output(self.monitor.as_ptr());
+            if xdisplay.is_null() || output == 0 {
+                return None;
+            }
+
+            let root = gdk_x11_sys::gdk_x11_get_default_root_xwindow();
+            let resources = (xrandr.XRRGetScreenResourcesCurrent)(xdisplay, root);
+            if resources.is_null() {
+                return None;
+            }
+            let output_info = (xrandr.XRRGetOutputInfo)(xdisplay, resources, output);
+            if output_info.is_null() {
+                (xrandr.XRRFreeScreenResources)(resources);
+                return None;
+            }
+
+            let all_modes = slice::from_raw_parts((*resources).modes, (*resources).nmode as usize);
+            let output_modes =
+                slice::from_raw_parts((*output_info).modes, (*output_info).nmode as usize);
+            let modes = output_modes
+                .iter()
+                .filter_map(|id| all_modes.iter().find(|mode| mode.id == *id))
+                .map(|mode| VideoMode {
+                    monitor: self.monitor.clone(),
+                    size: PhysicalSize::new(mode.width, mode.height),
+                    bit_depth,
+                    refresh_rate_millihertz: mode_refresh_rate_millihertz(mode),
+                    xrandr_mode: Some(mode.id),
+                })
+                .collect::<Vec<_>>();
+
+            (xrandr.XRRFreeOutputInfo)(output_info);
+            (xrandr.XRRFreeScreenResources)(resources);
+
+            if modes.is_empty() {
+                None
+            } else {
+                Some(modes)
+            }
+        }
+    }
+}
+
+fn mode_refresh_rate_millihertz(mode: &xrandr::XRRModeInfo) -> u32 {
+    let mut v_total = mode.vTotal as u64;
+    if mode.modeFlags & xrandr::RR_DoubleScan as xrandr::XRRModeFlags != 0 {
+        v_total *= 2;
+    }
+    if mode.modeFlags & xrandr::RR_Interlace as xrandr::XRRModeFlags != 0 {
+        v_total /= 2;
+    }
+
+    let total = mode.hTotal as u64 * v_total;
+    (mode.dotClock * 1000).checked_div(total).unwrap_or(0) as u32
+}
+
+/// Query XRandR for the name and the EDID of the output of `monitor`.
+fn xrandr_output_info(monitor: &gdk::Monitor) -> Option<(String, Option<Vec<u8>>)> {
+    let display = monitor.display()?;
+    let xlib = util::xlib()?;
+    let xrandr = util::xrandr()?;
+
+    unsafe {
+        let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(display.as_ptr() as *mut _)
+            as *mut xlib::Display;
+        let output = gdk_x11_sys::gdk_x11_monitor_get_output(monitor.as_ptr());
+        if xdisplay.is_null() || output == 0 {
+            return None;
+        }
+
+        let root = gdk_x11_sys::gdk_x11_get_default_root_xwindow();
+        let resources = (xrandr.XRRGetScreenResourcesCurrent)(xdisplay, root);
+        if resources.is_null() {
+            return None;
+        }
+        let output_info = (xrandr.XRRGetOutputInfo)(xdisplay, resources, output);
+        (xrandr.XRRFreeScreenResources)(resources);
+        if output_info.is_null() {
+            return None;
+        }
+        let name = slice::from_raw_parts(
+            (*output_info).name as *const u8,
+            (*output_info).nameLen as usize,
+        );
+        let connector = String::from_utf8_lossy(name).into_owned();
+        (xrandr.XRRFreeOutputInfo)(output_info);
+
+        let edid_atom = (xlib.XInternAtom)(xdisplay, b"EDID\0".as_ptr() as *const _, xlib::True);
+        if edid_atom == 0 {
+            return Some((connector, None));
+        }
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut nitems = 0;
+        let mut bytes_after = 0;
+        let mut data = ptr::null_mut();
+        let status = (xrandr.XRRGetOutputProperty)(
+            xdisplay,
+            output,
+            edid_atom,
+            0,
+            // In 32-bit units, enough for the base block and extension blocks.
+            256,
+            xlib::False,
+            xlib::False,
+            xlib::AnyPropertyType as xlib::Atom,
+            &mut actual_type,
+            &mut actual_format,
+            &mut nitems,
+            &mut bytes_after,
+            &mut data,
+        );
+        let edid = if status == xlib::Success as i32 && actual_format == 8 && !data.is_null() {
+            Some(slice::from_raw_parts(data, nitems as usize).to_vec())
+        } else {
+            None
+        };
+        if !data.is_null() {
+            (xlib.XFree)(data as *mut _);
+        }
+
+        Some((connector, edid))
+    }
+}
+
+/// Returns the manufacturer id and the display product name from the base block of an EDID.
+fn parse_edid(edid: &[u8]) -> (Option<String>, Option<String>) {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER {
+        return (None, None);
+    }
+
+    // Three letters of five bits each, 1 is 'A'.
+    let id = u16::from_be_bytes([edid[8], edid[9]]);
+    let manufacturer = [10, 5, 0]
+        .iter()
+        .map(|shift| ((id >> shift) & 0x1f) as u8)
+        .map(|c| (b'A' + c.wrapping_sub(1)) as char)
+        .collect::<String>();
+    let manufacturer = manufacturer
+        .chars()
+        .all(|c| c.is_ascii_uppercase())
+        .then_some(manufacturer);
+
+    // The product name is in one of the four 18 byte descriptors, terminated by a newline.
+    let model = edid[54..126]
+        .chunks(18)
+        .find(|descriptor| descriptor[..3] == [0, 0, 0] && descriptor[3] == 0xfc)
+        .map(|descriptor| {
+            let name = &descriptor[5..];
+            let end = name.iter().position(|c| *c == b'\n').unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..end]).trim().to_string()
+        })
+        .filter(|model| !model.is_empty());
+
+    (manufacturer, model)
+}
+
+pub(crate) fn available_monitors(display: &gdk::Display) -> VecDeque<MonitorHandle> {
+    (0..display.n_monitors())
+        .filter_map(|i| display.monitor(i))
+        .map(MonitorHandle::new)
+        .collect()
+}
+
+/// Some setups (e.g. X11 without a configured primary output, or Wayland) don't report a
+/// primary monitor, so the first one is used instead.
+pub(crate) fn primary_monitor(display: &gdk::Display) -> Option<MonitorHandle> {
+    display
+        .primary_monitor()
+        .or_else(|| display.monitor(0))
+        .map(MonitorHandle::new)
 }
 
 unsafe impl Send for MonitorHandle {}
@@ -63,40 +372,224 @@ unsafe impl Sync for MonitorHandle {}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct VideoMode {
-    /// gdk::Screen is deprecated. We make VideoMode and MonitorHandle
-    /// being the same type. If we want to enrich this feature. We will
-    /// need to look for x11/wayland implementations.
     pub(crate) monitor: gdk::Monitor,
+    pub(crate) size: PhysicalSize<u32>,
+    pub(crate) bit_depth: u16,
+    pub(crate) refresh_rate_millihertz: u32,
+    /// The XRandR mode, only known on X11.
+    pub(crate) xrandr_mode: Option<xrandr::RRMode>,
 }
 
 impl VideoMode {
     #[inline]
     pub fn size(&self) -> PhysicalSize<u32> {
-        let rect = self.monitor.geometry();
-        LogicalSize {
-            width: rect.width() as u32,
-            height: rect.height() as u32,
-        }
-        .to_physical(self.monitor.scale_factor() as f64)
+        self.size
     }
 
     #[inline]
     pub fn bit_depth(&self) -> u16 {
-        32
+        self.bit_depth
     }
 
     #[inline]
     pub fn refresh_rate_millihertz(&self) -> u32 {
-        self.monitor.refresh_rate() as u32
+        self.refresh_rate_millihertz
     }
 
     #[inline]
     pub fn monitor(&self) -> MonitorHandle {
-        MonitorHandle {
-            monitor: self.monitor.clone(),
-        }
+        MonitorHandle::new(self.monitor.clone())
     }
 }
 
 unsafe impl Send for VideoMode {}
 unsafe impl Sync for VideoMode {}
+
+/// The video modes the monitors had before exclusive fullscreen switched them.
+///
+/// Each monitor keeps the mode from before the first switch, along with the window which switched
+/// it last. That window restores it, so windows taking turns on a monitor don't lose its mode.
+#[derive(Debug, Default)]
+pub(crate) struct SavedVideoModes {
+    modes: HashMap<gdk::Monitor, (xrandr::RRMode, WindowId)>,
+}
+
+impl SavedVideoModes {
+    /// Switches the monitor of `video_mode` to it for the window `id`.
+    ///
+    /// Returns `false` if the video mode doesn't come from XRandR or the switch failed.
+    pub(crate) fn switch(&mut self, id: WindowId, video_mode: &VideoMode) -> bool {
+        let previous = match video_mode
+            .xrandr_mode
+            .and_then(|mode| set_xrandr_mode(&video_mode.monitor, mode))
+        {
+            Some(previous) => previous,
+            None => return false,
+        };
+        self.modes
+            .entry(video_mode.monitor.clone())
+            .or_insert((previous, id))
+            .1 = id;
+        true
+    }
+
+    /// Switches the monitors the window `id` switched last back to their saved mode.
+    pub(crate) fn restore(&mut self, id: WindowId) {
+        self.modes.retain(|monitor, (mode, switched_by)| {
+            if *switched_by != id {
+                return true;
+            }
+            if set_xrandr_mode(monitor, *mode).is_none() {
+                log::warn!("Failed to restore the video mode of the monitor");
+            }
+            false
+        });
+    }
+}
+
+/// Sets the mode of the CRTC driving the output of `monitor`, returns the mode it had.
+fn set_xrandr_mode(monitor: &gdk::Monitor, mode: xrandr::RRMode) -> Option<xrandr::RRMode> {
+    let display = monitor.display()?;
+    if !display.backend().is_x11() {
+        return None;
+    }
+    let xrandr = util::xrandr()?;
+
+    unsafe {
+        let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(display.as_ptr() as *mut _)
+            as *mut xlib::Display;
+        let output = gdk_x11_sys::gdk_x11_monitor_get_output(monitor.as_ptr());
+        if xdisplay.is_null() || output == 0 {
+            return None;
+        }
+
+        let root = gdk_x11_sys::gdk_x11_get_default_root_xwindow();
+        let resources = (xrandr.XRRGetScreenResourcesCurrent)(xdisplay, root);
+        if resources.is_null() {
+            return None;
+        }
+        let output_info = (xrandr.XRRGetOutputInfo)(xdisplay, resources, output);
+        if output_info.is_null() {
+            (xrandr.XRRFreeScreenResources)(resources);
+            return None;
+        }
+        let crtc = (*output_info).crtc;
+        (xrandr.XRRFreeOutputInfo)(output_info);
+        let crtc_info = if crtc == 0 {
+            ptr::null_mut()
+        } else {
+            (xrandr.XRRGetCrtcInfo)(xdisplay, resources, crtc)
+        };
+        if crtc_info.is_null() {
+            (xrandr.XRRFreeScreenResources)(resources);
+            return None;
+        }
+
+        let previous = (*crtc_info).mode;
+        // A mode larger than the screen is refused with `BadMatch`, so the screen grows first.
+        gdk_x11_sys::gdk_x11_display_error_trap_push(display.as_ptr() as *mut _);
+        let modes = slice::from_raw_parts((*resources).modes, (*resources).nmode as usize);
+        if let (Some(mode_info), Some(xlib)) = (modes.iter().find(|m| m.id == mode), util::xlib()) {
+            let screen = (xlib.XDefaultScreen)(xdisplay);
+            let size = (
+                (xlib.XDisplayWidth)(xdisplay, screen),
+                (xlib.XDisplayHeight)(xdisplay, screen),
+            );
+            let crtc_info = &*crtc_info;
+            if let Some((width, height)) = grown_screen_size(
+                size,
+                (crtc_info.x, crtc_info.y),
+                (mode_info.width, mode_info.height),
+                crtc_info.rotation,
+            ) {
+                // Keep the DPI the screen reports.
+                let width_mm = (xlib.XDisplayWidthMM)(xdisplay, screen) * width / size.0;
+                let height_mm = (xlib.XDisplayHeightMM)(xdisplay, screen) * height / size.1;
+                (xrandr.XRRSetScreenSize)(xdisplay, root, width, height, width_mm, height_mm);
+            }
+        }
+        let status = (xrandr.XRRSetCrtcConfig)(
+            xdisplay,
+            resources,
+            crtc,
+            xlib::CurrentTime,
+            (*crtc_info).x,
+            (*crtc_info).y,
+            mode,
+            (*crtc_info).rotation,
+            (*crtc_info).outputs,
+            (*crtc_info).noutput,
+        );
+        let error = gdk_x11_sys::gdk_x11_display_error_trap_pop(display.as_ptr() as *mut _);
+        (xrandr.XRRFreeCrtcInfo)(crtc_info);
+        (xrandr.XRRFreeScreenResources)(resources);
+
+        if status == xrandr::RRSetConfigSuccess as _ && error == 0 {
+            Some(previous)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the screen size fitting a CRTC at `position` with a mode of `mode_size`, if the screen
+/// of `size` has to grow for it.
+fn grown_screen_size(
+    size: (c_int, c_int),
+    position: (c_int, c_int),
+    mode_size: (u32, u32),
+    rotation: xrandr::Rotation,
+) -> Option<(c_int, c_int)> {
+    let (mut width, mut height) = (mode_size.0 as c_int, mode_size.1 as c_int);
+    if rotation as c_int & (xrandr::RR_Rotate_90 | xrandr::RR_Rotate_270) != 0 {
+        (width, height) = (height, width);
+    }
+    let grown = (
+        size.0.max(position.0 + width),
+        size.1.max(position.1 + height),
+    );
+    (grown != size).then_some(grown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_grows_to_fit_a_larger_mode() {
+        let normal = xrandr::RR_Rotate_0 as xrandr::Rotation;
+        // A second monitor right of a 1920x1080 one, switched from 1280x1024 to 1920x1200.
+        assert_eq!(
+            grown_screen_size((3200, 1080), (1920, 0), (1920, 1200), normal),
+            Some((3840, 1200))
+        );
+        assert_eq!(
+            grown_screen_size((3200, 1080), (1920, 0), (1024, 768), normal),
+            None
+        );
+        // Rotated monitors take the mode sideways.
+        let rotated = xrandr::RR_Rotate_90 as xrandr::Rotation;
+        assert_eq!(
+            grown_screen_size((1080, 1920), (0, 0), (1920, 1200), rotated),
+            Some((1200, 1920))
+        );
+    }
+
+    #[test]
+    fn edid_manufacturer_and_model() {
+        let mut edid = [0; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        // "DEL"
+        edid[8..10].copy_from_slice(&[0x10, 0xac]);
+        // Serial number descriptor before the product name.
+        edid[72..77].copy_from_slice(&[0, 0, 0, 0xff, 0]);
+        edid[90..95].copy_from_slice(&[0, 0, 0, 0xfc, 0]);
+        edid[95..108].copy_from_slice(b"DELL U2720Q\n ");
+
+        assert_eq!(
+            parse_edid(&edid),
+            (Some("DEL".to_string()), Some("DELL U2720Q".to_string()))
+        );
+        assert_eq!(parse_edid(&edid[..64]), (None, None));
+    }
+}
@@ -0,0 +1,306 @@
+//! Tearing presentation hints through the `wp_tearing_control_v1` Wayland protocol, which GTK
+//! doesn't bind itself.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use gdk::prelude::DisplayExtManual;
+use glib::ObjectType;
+use gtk::traits::WidgetExt;
+
+#[repr(C)]
+struct WlMessage {
+    name: *const c_char,
+    signature: *const c_char,
+    types: *const Option<&'static WlInterface>,
+}
+
+#[repr(C)]
+struct WlInterface {
+    name: *const c_char,
+    version: c_int,
+    method_count: c_int,
+    methods: *const WlMessage,
+    event_count: c_int,
+    events: *const WlMessage,
+}
+
+// The protocol descriptions are never written to.
+unsafe impl Sync for WlMessage {}
+unsafe impl Sync for WlInterface {}
+
+#[repr(C)]
+struct WlRegistryListener {
+    global: unsafe extern "C" fn(*mut c_void, *mut WlProxy, u32, *const c_char, u32),
+    global_remove: unsafe extern "C" fn(*mut c_void, *mut WlProxy, u32),
+}
+
+enum WlProxy {}
+enum WlEventQueue {}
+
+#[link(name = "wayland-client")]
+extern "C" {
+    static wl_registry_interface: WlInterface;
+    static wl_surface_interface: WlInterface;
+
+    fn wl_display_create_queue(display: *mut WlProxy) -> *mut WlEventQueue;
+    fn wl_display_roundtrip_queue(display: *mut WlProxy, queue: *mut WlEventQueue) -> c_int;
+    fn wl_proxy_create_wrapper(proxy: *mut WlProxy) -> *mut WlProxy;
+    fn wl_proxy_wrapper_destroy(proxy_wrapper: *mut WlProxy);
+    fn wl_proxy_set_queue(proxy: *mut WlProxy, queue: *mut WlEventQueue);
+    fn wl_proxy_add_listener(
+        proxy: *mut WlProxy,
+        implementation: *const c_void,
+        data: *mut c_void,
+    ) -> c_int;
+    fn wl_proxy_marshal(proxy: *mut WlProxy, opcode: u32, ...);
+    fn wl_proxy_marshal_constructor(
+        proxy: *mut WlProxy,
+        opcode: u32,
+        interface: *const WlInterface,
+        ...
+    ) -> *mut WlProxy;
+    fn wl_proxy_marshal_constructor_versioned(
+        proxy: *mut WlProxy,
+        opcode: u32,
+        interface: *const WlInterface,
+        version: u32,
+        ...
+    ) -> *mut WlProxy;
+    fn wl_proxy_destroy(proxy: *mut WlProxy);
+}
+
+// Request opcodes.
+const WL_DISPLAY_GET_REGISTRY: u32 = 1;
+const WL_REGISTRY_BIND: u32 = 0;
+const MANAGER_GET_TEARING_CONTROL: u32 = 1;
+const TEARING_CONTROL_SET_PRESENTATION_HINT: u32 = 0;
+const TEARING_CONTROL_DESTROY: u32 = 1;
+
+// Values of `wp_tearing_control_v1.presentation_hint`.
+const PRESENTATION_HINT_VSYNC: u32 = 0;
+const PRESENTATION_HINT_ASYNC: u32 = 1;
+
+const MANAGER_NAME: &[u8] = b"wp_tearing_control_manager_v1\0";
+
+static NO_TYPES: [Option<&WlInterface>; 1] = [None];
+
+static GET_TEARING_CONTROL_TYPES: [Option<&WlInterface>; 2] = [
+    Some(&TEARING_CONTROL_INTERFACE),
+    Some(unsafe { &wl_surface_interface }),
+];
+
+static MANAGER_REQUESTS: [WlMessage; 2] = [
+    WlMessage {
+        name: b"destroy\0".as_ptr() as *const c_char,
+        signature: b"\0".as_ptr() as *const c_char,
+        types: NO_TYPES.as_ptr(),
+    },
+    WlMessage {
+        name: b"get_tearing_control\0".as_ptr() as *const c_char,
+        signature: b"no\0".as_ptr() as *const c_char,
+        types: GET_TEARING_CONTROL_TYPES.as_ptr(),
+    },
+];
+
+static MANAGER_INTERFACE: WlInterface = WlInterface {
+    name: MANAGER_NAME.as_ptr() as *const c_char,
+    version: 1,
+    method_count: 2,
+    methods: MANAGER_REQUESTS.as_ptr(),
+    event_count: 0,
+    events: ptr::null(),
+};
+
+static TEARING_CONTROL_REQUESTS: [WlMessage; 2] = [
+    WlMessage {
+        name: b"set_presentation_hint\0".as_ptr() as *const c_char,
+        signature: b"u\0".as_ptr() as *const c_char,
+        types: NO_TYPES.as_ptr(),
+    },
+    WlMessage {
+        name: b"destroy\0".as_ptr() as *const c_char,
+        signature: b"\0".as_ptr() as *const c_char,
+        types: NO_TYPES.as_ptr(),
+    },
+];
+
+static TEARING_CONTROL_INTERFACE: WlInterface = WlInterface {
+    name: b"wp_tearing_control_v1\0".as_ptr() as *const c_char,
+    version: 1,
+    method_count: 2,
+    methods: TEARING_CONTROL_REQUESTS.as_ptr(),
+    event_count: 0,
+    events: ptr::null(),
+};
+
+static REGISTRY_LISTENER: WlRegistryListener = WlRegistryListener {
+    global: registry_global,
+    global_remove: registry_global_remove,
+};
+
+unsafe extern "C" fn registry_global(
+    data: *mut c_void,
+    registry: *mut WlProxy,
+    name: u32,
+    interface: *const c_char,
+    _version: u32,
+) {
+    if CStr::from_ptr(interface).to_bytes_with_nul() == MANAGER_NAME {
+        *(data as *mut *mut WlProxy) = wl_proxy_marshal_constructor_versioned(
+            registry,
+            WL_REGISTRY_BIND,
+            &MANAGER_INTERFACE,
+            1,
+            name,
+            MANAGER_INTERFACE.name,
+            1u32,
+            ptr::null_mut::<c_void>(),
+        );
+    }
+}
+
+unsafe extern "C" fn registry_global_remove(_: *mut c_void, _: *mut WlProxy, _: u32) {}
+
+thread_local! {
+    /// The tearing control manager of each Wayland display, null if the compositor doesn't offer
+    /// it.
+    static MANAGERS: RefCell<HashMap<gdk::Display, usize>> = RefCell::new(HashMap::new());
+}
+
+/// Binds the tearing control manager of `display` the first time it's needed.
+fn manager(display: &gdk::Display) -> *mut WlProxy {
+    MANAGERS.with(|managers| {
+        *managers
+            .borrow_mut()
+            .entry(display.clone())
+            .or_insert_with(|| unsafe {
+                let wl_display =
+                    gdk_wayland_sys::gdk_wayland_display_get_wl_display(display.as_ptr() as *mut _)
+                        as *mut WlProxy;
+                bind_manager(wl_display) as usize
+            })
+    }) as *mut WlProxy
+}
+
+unsafe fn bind_manager(wl_display: *mut WlProxy) -> *mut WlProxy {
+    // The registry goes on a queue of our own to keep its events away from GDK. The queue is kept
+    // for the manager and the objects created from it, though none of them has events.
+    let queue = wl_display_create_queue(wl_display);
+    let wrapper = wl_proxy_create_wrapper(wl_display);
+    wl_proxy_set_queue(wrapper, queue);
+    let registry = wl_proxy_marshal_constructor(
+        wrapper,
+        WL_DISPLAY_GET_REGISTRY,
+        &wl_registry_interface,
+        ptr::null_mut::<c_void>(),
+    );
+    wl_proxy_wrapper_destroy(wrapper);
+
+    let mut manager: *mut WlProxy = ptr::null_mut();
+    wl_proxy_add_listener(
+        registry,
+        &REGISTRY_LISTENER as *const _ as *const c_void,
+        &mut manager as *mut _ as *mut c_void,
+    );
+    wl_display_roundtrip_queue(wl_display, queue);
+    wl_proxy_destroy(registry);
+    manager
+}
+
+/// The `wp_tearing_control_v1` of a surface.
+struct SurfaceControl(*mut WlProxy);
+
+impl Drop for SurfaceControl {
+    fn drop(&mut self) {
+        unsafe {
+            wl_proxy_marshal(self.0, TEARING_CONTROL_DESTROY);
+            wl_proxy_destroy(self.0);
+        }
+    }
+}
+
+/// The tearing presentation hint of a window.
+///
+/// GDK creates a new surface each time the window is shown, the hint is sent again for each.
+#[derive(Default)]
+pub struct TearingControl {
+    /// Whether the application allows tearing.
+    allowed: AtomicBool,
+    /// Whether the compositor was told that tearing is fine for the current surface.
+    applied: AtomicBool,
+    surface_control: RefCell<Option<SurfaceControl>>,
+}
+
+impl TearingControl {
+    pub fn set_allowed(&self, window: &gtk::Window, allowed: bool) {
+        self.allowed.store(allowed, Ordering::Release);
+        // Otherwise it's sent once the window is mapped.
+        if window.is_mapped() {
+            self.apply(window);
+        }
+    }
+
+    /// Sends the hint for the surface of `window`, it must be mapped.
+    pub fn apply(&self, window: &gtk::Window) {
+        let allowed = self.allowed.load(Ordering::Acquire);
+        let gdk_window = match window.window() {
+            Some(gdk_window) if window.display().backend().is_wayland() => gdk_window,
+            _ => return,
+        };
+        let mut surface_control = self.surface_control.borrow_mut();
+        if surface_control.is_none() {
+            // Vsync is what the surface does without a tearing control.
+            if !allowed {
+                return;
+            }
+            let manager = manager(&window.display());
+            if manager.is_null() {
+                return;
+            }
+            let proxy = unsafe {
+                let surface = gdk_wayland_sys::gdk_wayland_window_get_wl_surface(
+                    gdk_window.as_ptr() as *mut _,
+                );
+                wl_proxy_marshal_constructor(
+                    manager,
+                    MANAGER_GET_TEARING_CONTROL,
+                    &TEARING_CONTROL_INTERFACE,
+                    ptr::null_mut::<c_void>(),
+                    surface,
+                )
+            };
+            *surface_control = Some(SurfaceControl(proxy));
+        }
+        let hint = if allowed {
+            PRESENTATION_HINT_ASYNC
+        } else {
+            PRESENTATION_HINT_VSYNC
+        };
+        // Like any surface state, it takes effect with the next commit of GDK.
+        unsafe {
+            wl_proxy_marshal(
+                surface_control.as_ref().unwrap().0,
+                TEARING_CONTROL_SET_PRESENTATION_HINT,
+                hint,
+            )
+        };
+        self.applied.store(allowed, Ordering::Release);
+    }
+
+    /// Drops the tearing control of the surface GDK destroys when the window is hidden.
+    pub fn reset(&self) {
+        self.surface_control.borrow_mut().take();
+        self.applied.store(false, Ordering::Release);
+    }
+
+    pub fn is_applied(&self) -> bool {
+        self.applied.load(Ordering::Acquire)
+    }
+}
@@ -1,11 +1,339 @@
-use gtk::traits::{GtkWindowExt, WidgetExt};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CString,
+    os::raw::c_long,
+    time::{Duration, Instant},
+};
 
-use crate::dpi::{LogicalSize, Size};
+use gdk::prelude::{DisplayExtManual, SeatExt};
+use glib::{ObjectExt, ObjectType};
+use gtk::{
+    prelude::GtkSettingsExt,
+    traits::{GtkWindowExt, WidgetExt},
+    Settings,
+};
+use once_cell::sync::Lazy;
+use x11_dl::{xcursor, xlib, xrandr};
+
+use crate::{
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize, Position, Size},
+    event::DeviceId,
+    platform::unix::{InputSettings, RawInputEvent, RawTimestamp, WaylandCapabilities},
+};
+
+/// Event times older than this are assumed to come from a clock other than the monotonic one.
+const MAX_EVENT_AGE: Duration = Duration::from_secs(10);
+
+/// Converts the time of a GDK event into a [`RawTimestamp`].
+pub fn raw_timestamp(time: u32, event: RawInputEvent, device_id: DeviceId) -> RawTimestamp {
+    let now = Instant::now();
+    // The display server's milliseconds wrap around like the truncated monotonic time.
+    let now_ms = (glib::monotonic_time() / 1000) as u32;
+    let instant = event_age(now_ms, time)
+        .and_then(|age| now.checked_sub(age))
+        .unwrap_or(now);
+    RawTimestamp {
+        time,
+        instant,
+        event,
+        device_id,
+    }
+}
+
+fn event_age(now_ms: u32, time: u32) -> Option<Duration> {
+    let age = Duration::from_millis(now_ms.wrapping_sub(time) as u64);
+    (age <= MAX_EVENT_AGE).then_some(age)
+}
+
+/// Whether the current thread is the main thread of the process.
+#[cfg(target_os = "linux")]
+pub fn is_main_thread() -> bool {
+    // The main thread's id is the id of the process.
+    unsafe { libc::syscall(libc::SYS_gettid) == libc::getpid() as c_long }
+}
+
+/// Whether the current thread is the main thread of the process.
+#[cfg(any(target_os = "dragonfly", target_os = "freebsd", target_os = "openbsd"))]
+pub fn is_main_thread() -> bool {
+    unsafe { libc::pthread_main_np() == 1 }
+}
+
+/// Whether the current thread is the main thread of the process.
+#[cfg(target_os = "netbsd")]
+pub fn is_main_thread() -> bool {
+    unsafe { libc::_lwp_self() == 1 }
+}
+
+/// Looks up the optional protocols among the globals the Wayland registry of GDK announced.
+pub fn wayland_capabilities(display: &gdk::Display) -> WaylandCapabilities {
+    if !display.backend().is_wayland() {
+        return WaylandCapabilities::default();
+    }
+    // The names are nul-terminated for GDK.
+    let has_global = |name: &[u8]| unsafe {
+        gdk_wayland_sys::gdk_wayland_display_query_registry(
+            display.as_ptr() as *mut _,
+            name.as_ptr() as *const _,
+        ) != glib::ffi::GFALSE
+    };
+    WaylandCapabilities {
+        server_side_decorations: has_global(b"zxdg_decoration_manager_v1\0")
+            || has_global(b"org_kde_kwin_server_decoration_manager\0"),
+        pointer_constraints: has_global(b"zwp_pointer_constraints_v1\0"),
+        relative_pointer: has_global(b"zwp_relative_pointer_manager_v1\0"),
+        tearing_control: has_global(b"wp_tearing_control_manager_v1\0"),
+        activation: has_global(b"xdg_activation_v1\0"),
+        layer_shell: has_global(b"zwlr_layer_shell_v1\0"),
+    }
+}
+
+/// The `GtkSettings` properties that make up [`InputSettings`].
+pub const INPUT_SETTINGS_PROPERTIES: [&str; 3] = [
+    "gtk-enable-primary-paste",
+    "gtk-overlay-scrolling",
+    "gtk-dnd-drag-threshold",
+];
+
+pub fn input_settings() -> InputSettings {
+    let mut input_settings = InputSettings::default();
+    if let Some(settings) = Settings::default() {
+        input_settings.primary_paste = settings.is_gtk_enable_primary_paste();
+        input_settings.drag_threshold = settings.gtk_dnd_drag_threshold().max(0) as u32;
+        // `gtk-overlay-scrolling` only exists since GTK 3.24.9.
+        if settings.find_property("gtk-overlay-scrolling").is_some() {
+            input_settings.overlay_scrolling = settings.property("gtk-overlay-scrolling");
+        }
+    }
+    input_settings
+}
+
+/// Sets `_NET_WM_BYPASS_COMPOSITOR` of an X11 window, returns whether the property was set.
+pub fn set_compositor_bypass(gdk_window: &gdk::Window, bypass: bool) -> bool {
+    let display = gdk_window.display();
+    if !display.backend().is_x11() {
+        return false;
+    }
+    let xlib = match xlib() {
+        Some(xlib) => xlib,
+        None => return false,
+    };
+    unsafe {
+        let xdisplay = gdk_x11_sys::gdk_x11_display_get_xdisplay(display.as_ptr() as *mut _)
+            as *mut x11_dl::xlib::Display;
+        let xid = gdk_x11_sys::gdk_x11_window_get_xid(gdk_window.as_ptr() as *mut _);
+        let atom = gdk_x11_sys::gdk_x11_get_xatom_by_name_for_display(
+            display.as_ptr() as *mut _,
+            b"_NET_WM_BYPASS_COMPOSITOR\0".as_ptr() as *const _,
+        );
+        // 1 asks to bypass the compositor, 0 leaves it up to the compositor.
+        let value = bypass as c_long;
+        (xlib.XChangeProperty)(
+            xdisplay,
+            xid,
+            atom,
+            x11_dl::xlib::XA_CARDINAL,
+            32,
+            x11_dl::xlib::PropModeReplace,
+            &value as *const c_long as *const u8,
+            1,
+        );
+    }
+    true
+}
+
+/// The size of the cursor theme in logical pixels.
+pub fn cursor_theme_size() -> u32 {
+    let size = Settings::default()
+        .map(|settings| settings.gtk_cursor_theme_size())
+        .unwrap_or(0);
+    // 0 leaves the size to the display server, whose default is 24 on most desktops.
+    if size > 0 {
+        size as u32
+    } else {
+        24
+    }
+}
+
+/// libXcursor, loaded on first use.
+fn xcursor() -> Option<&'static xcursor::Xcursor> {
+    static XCURSOR: Lazy<Option<xcursor::Xcursor>> = Lazy::new(|| xcursor::Xcursor::open().ok());
+    XCURSOR.as_ref()
+}
+
+/// Xlib, loaded on first use.
+pub fn xlib() -> Option<&'static xlib::Xlib> {
+    static XLIB: Lazy<Option<xlib::Xlib>> = Lazy::new(|| xlib::Xlib::open().ok());
+    XLIB.as_ref()
+}
+
+/// libXrandr, loaded on first use.
+pub fn xrandr() -> Option<&'static xrandr::Xrandr> {
+    static XRANDR: Lazy<Option<xrandr::Xrandr>> = Lazy::new(|| xrandr::Xrandr::open().ok());
+    XRANDR.as_ref()
+}
+
+/// A themed cursor of a display by theme, name, logical size and scale factor.
+type ThemedCursorKey = (gdk::Display, Option<String>, String, u32, i32);
+
+thread_local! {
+    /// The themed cursors loaded so far, including the ones the theme doesn't have.
+    static THEMED_CURSORS: RefCell<HashMap<ThemedCursorKey, Option<gdk::Cursor>>> =
+        Default::default();
+}
+
+/// Loads the cursor `name` of the cursor theme, `size` logical pixels large on a window of
+/// `scale_factor`.
+///
+/// Returns `None` if libXcursor is missing or the theme has no such cursor. The cursors are
+/// cached, the pointer moving over the resize borders asks for them on every motion.
+pub fn themed_cursor(
+    display: &gdk::Display,
+    name: &str,
+    size: u32,
+    scale_factor: i32,
+) -> Option<gdk::Cursor> {
+    let theme = Settings::default()
+        .and_then(|settings| settings.gtk_cursor_theme_name())
+        .map(|theme| theme.as_str().to_owned());
+    let scale_factor = scale_factor.max(1);
+    let key = (display.clone(), theme, name.to_owned(), size, scale_factor);
+    if let Some(cursor) = THEMED_CURSORS.with(|cursors| cursors.borrow().get(&key).cloned()) {
+        return cursor;
+    }
+    let cursor = load_themed_cursor(display, key.1.as_deref(), name, size, scale_factor);
+    THEMED_CURSORS.with(|cursors| cursors.borrow_mut().insert(key, cursor.clone()));
+    cursor
+}
+
+fn load_themed_cursor(
+    display: &gdk::Display,
+    theme: Option<&str>,
+    name: &str,
+    size: u32,
+    scale_factor: i32,
+) -> Option<gdk::Cursor> {
+    let xcursor = xcursor()?;
+    let theme = theme.and_then(|theme| CString::new(theme).ok());
+    let name = CString::new(name).ok()?;
+
+    unsafe {
+        // The theme picks its nearest size, which is the one the image actually has.
+        let image = (xcursor.XcursorLibraryLoadImage)(
+            name.as_ptr(),
+            theme
+                .as_ref()
+                .map_or(std::ptr::null(), |theme| theme.as_ptr()),
+            (size as i32).saturating_mul(scale_factor),
+        );
+        if image.is_null() {
+            return None;
+        }
+        let (width, height) = ((*image).width as usize, (*image).height as usize);
+        let hotspot = ((*image).xhot as f64, (*image).yhot as f64);
+        // Xcursor pixels are premultiplied ARGB in native byte order, like `Format::ARgb32`.
+        let pixels = std::slice::from_raw_parts((*image).pixels, width * height).to_vec();
+        (xcursor.XcursorImageDestroy)(image);
+
+        let mut surface =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, width as i32, height as i32).ok()?;
+        let stride = surface.stride() as usize;
+        {
+            let mut data = surface.data().ok()?;
+            for (row, line) in pixels.chunks(width.max(1)).enumerate() {
+                for (column, pixel) in line.iter().enumerate() {
+                    let offset = row * stride + column * 4;
+                    data[offset..offset + 4].copy_from_slice(&pixel.to_ne_bytes());
+                }
+            }
+        }
+        surface.set_device_scale(scale_factor as f64, scale_factor as f64);
+        let scale_factor = scale_factor as f64;
+        Some(gdk::Cursor::from_surface(
+            display,
+            &surface,
+            hotspot.0 / scale_factor,
+            hotspot.1 / scale_factor,
+        ))
+    }
+}
+
+// GTK works in logical pixels while winit exposes physical ones. Every conversion goes through
+// these helpers, so the rounding of `dpi` is applied once with the scale factor given.
+
+/// Converts a size of GTK into physical pixels.
+pub fn logical_to_physical_size(
+    (width, height): (i32, i32),
+    scale_factor: f64,
+) -> PhysicalSize<u32> {
+    LogicalSize::new(width.max(0), height.max(0)).to_physical(scale_factor)
+}
+
+/// Converts a position of GTK into physical pixels.
+pub fn logical_to_physical_position(
+    (x, y): (i32, i32),
+    scale_factor: f64,
+) -> PhysicalPosition<i32> {
+    LogicalPosition::new(x, y).to_physical(scale_factor)
+}
+
+/// Converts a size, if physical, into the logical pixels of GTK.
+pub fn physical_to_logical_size(size: Size, scale_factor: f64) -> (i32, i32) {
+    size.to_logical::<i32>(scale_factor).into()
+}
+
+/// Converts a position, if physical, into the logical pixels of GTK.
+pub fn physical_to_logical_position(position: Position, scale_factor: f64) -> (i32, i32) {
+    position.to_logical::<i32>(scale_factor).into()
+}
+
+/// Converts the position of a pointer event into physical pixels.
+pub fn logical_to_physical_pointer((x, y): (f64, f64), scale_factor: f64) -> PhysicalPosition<f64> {
+    LogicalPosition::new(x, y).to_physical(scale_factor)
+}
+
+/// The position of a pointer event relative to the `toplevel` window, in logical pixels.
+///
+/// Events are relative to the `GdkWindow` they happened in, which may belong to a child widget.
+/// Events of other windows, like popups, are placed from their root coordinates instead.
+pub fn toplevel_position(
+    toplevel: &gdk::Window,
+    event_window: Option<gdk::Window>,
+    position: (f64, f64),
+    root: (f64, f64),
+) -> (f64, f64) {
+    let (mut x, mut y) = position;
+    let mut window = event_window;
+    while let Some(current) = window {
+        if &current == toplevel {
+            return (x, y);
+        }
+        let (parent_x, parent_y) = current.coords_to_parent(x, y);
+        x = parent_x;
+        y = parent_y;
+        window = current.parent();
+    }
+    let (_, left, top) = toplevel.origin();
+    (root.0 - left as f64, root.1 - top as f64)
+}
+
+/// Logical pixels of one scroll step, the unit of the smooth scroll deltas of GDK.
+///
+/// GDK divides the pixel distances of Wayland by this, the X11 input drivers use a similar one.
+const SCROLL_STEP_PIXELS: f64 = 10.0;
+
+/// Converts the delta of a smooth scroll event into the physical pixels the content should move.
+///
+/// GDK reports how far the pointer moved, the content moves the other way.
+pub fn smooth_scroll_delta((x, y): (f64, f64), scale_factor: f64) -> PhysicalPosition<f64> {
+    LogicalPosition::new(-x * SCROLL_STEP_PIXELS, -y * SCROLL_STEP_PIXELS).to_physical(scale_factor)
+}
 
 pub fn set_size_constraints<W: GtkWindowExt + WidgetExt>(
     window: &W,
     min_size: Option<Size>,
     max_size: Option<Size>,
+    resize_increments: Option<Size>,
 ) {
     let mut geom_mask = gdk::WindowHints::empty();
     if min_size.is_some() {
@@ -14,28 +342,34 @@ pub fn set_size_constraints<W: GtkWindowExt + WidgetExt>(
     if max_size.is_some() {
         geom_mask |= gdk::WindowHints::MAX_SIZE;
     }
+    if resize_increments.is_some() {
+        geom_mask |= gdk::WindowHints::RESIZE_INC;
+    }
 
     let scale_factor = window.scale_factor() as f64;
 
-    let min_size: LogicalSize<i32> = min_size
-        .map(|s| s.to_logical(scale_factor))
-        .unwrap_or(LogicalSize::new(0, 0));
-    let max_size: LogicalSize<i32> = max_size
-        .map(|s| s.to_logical(scale_factor))
-        .unwrap_or(LogicalSize::new(i32::MAX, i32::MAX));
+    let min_size = min_size
+        .map(|s| physical_to_logical_size(s, scale_factor))
+        .unwrap_or((0, 0));
+    let max_size = max_size
+        .map(|s| physical_to_logical_size(s, scale_factor))
+        .unwrap_or((i32::MAX, i32::MAX));
+    let resize_increments = resize_increments
+        .map(|s| physical_to_logical_size(s, scale_factor))
+        .unwrap_or((0, 0));
 
     let picky_none: Option<&gtk::Window> = None;
     window.set_geometry_hints(
         picky_none,
         Some(&gdk::Geometry::new(
-            min_size.width,
-            min_size.height,
-            max_size.width,
-            max_size.height,
-            0,
-            0,
+            min_size.0,
+            min_size.1,
+            max_size.0,
+            max_size.1,
             0,
             0,
+            resize_increments.0,
+            resize_increments.1,
             0f64,
             0f64,
             gdk::Gravity::Center,
@@ -43,3 +377,87 @@ pub fn set_size_constraints<W: GtkWindowExt + WidgetExt>(
         geom_mask,
     )
 }
+
+/// The pointer dragging `window`, `None` if the window isn't realized or the seat has no pointer.
+///
+/// This is checked before a drag request is sent, so the failure is reported without waiting for
+/// the event loop.
+pub fn drag_pointer(window: &gtk::Window) -> Option<gdk::Device> {
+    window
+        .window()
+        .and(window.display().default_seat())
+        .and_then(|seat| seat.pointer())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logical_round_trip() {
+        for scale_factor in [1.0, 1.25, 1.5, 1.75, 2.0, 3.0] {
+            for width in 0..500 {
+                let size = (width, width * 3 / 2);
+                let physical = logical_to_physical_size(size, scale_factor);
+                assert_eq!(
+                    physical_to_logical_size(physical.into(), scale_factor),
+                    size,
+                    "scale factor {}",
+                    scale_factor
+                );
+
+                let position = (width - 250, 250 - width);
+                let physical = logical_to_physical_position(position, scale_factor);
+                assert_eq!(
+                    physical_to_logical_position(physical.into(), scale_factor),
+                    position,
+                    "scale factor {}",
+                    scale_factor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn logical_sizes_are_kept() {
+        let size = LogicalSize::new(801, 599).into();
+        assert_eq!(physical_to_logical_size(size, 1.25), (801, 599));
+        assert_eq!(
+            logical_to_physical_size((801, 599), 1.25),
+            PhysicalSize::new(1001, 749)
+        );
+    }
+
+    #[test]
+    fn pointer_positions_are_scaled() {
+        assert_eq!(
+            logical_to_physical_pointer((10.5, 20.25), 2.0),
+            PhysicalPosition::new(21.0, 40.5)
+        );
+        assert_eq!(
+            logical_to_physical_pointer((0.0, 599.5), 2.0),
+            PhysicalPosition::new(0.0, 1199.0)
+        );
+    }
+
+    #[test]
+    fn smooth_scroll_is_scaled_to_pixels() {
+        assert_eq!(
+            smooth_scroll_delta((0.5, -1.0), 2.0),
+            PhysicalPosition::new(-10.0, 20.0)
+        );
+        assert_eq!(
+            smooth_scroll_delta((0.0, 0.25), 1.0),
+            PhysicalPosition::new(0.0, -2.5)
+        );
+    }
+
+    #[test]
+    fn event_age_wraps_around() {
+        assert_eq!(event_age(1_000, 984), Some(Duration::from_millis(16)));
+        assert_eq!(event_age(5, u32::MAX - 4), Some(Duration::from_millis(10)));
+        // Events from a clock ahead of ours, or from another clock altogether.
+        assert_eq!(event_age(1_000, 1_001), None);
+        assert_eq!(event_age(1_000_000, 1_000), None);
+    }
+}
@@ -1,14 +1,18 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::VecDeque,
     rc::Rc,
-    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        mpsc,
+    },
+    time::Duration,
 };
 
 use gdk::{prelude::DisplayExtManual, WindowEdge, WindowState};
-use glib::{translate::ToGlibPtr, Cast, ObjectType};
+use glib::{translate::ToGlibPtr, Cast, ObjectExt, ObjectType};
 use gtk::{
-    prelude::GtkSettingsExt,
+    prelude::{GtkSettingsExt, StyleContextExt, WidgetExtManual},
     traits::{ApplicationWindowExt, ContainerExt, GtkWindowExt, WidgetExt},
     Settings,
 };
@@ -18,50 +22,205 @@ use raw_window_handle::{
 };
 
 use crate::{
-    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize, Position, Size},
+    dpi::{PhysicalPosition, PhysicalSize, Position, Size},
     error::{ExternalError, NotSupportedError, OsError as RootOsError},
-    platform_impl::WindowId,
+    platform::unix::{DefaultHandlers, RawInputEvent, RawTimestamp},
+    platform_impl::{OsError, WindowId},
     window::{
         CursorGrabMode, CursorIcon, Icon, ImePurpose, ResizeDirection, Theme, UserAttentionType,
         WindowAttributes, WindowButtons, WindowLevel,
     },
 };
 
+#[cfg(feature = "layer-shell")]
+use super::layer_shell;
 use super::{
-    util, EventLoopWindowTarget, Fullscreen, MonitorHandle, PlatformSpecificWindowBuilderAttributes,
+    device::DeviceIds, monitor, tearing_control::TearingControl, util, ApplicationName,
+    EventLoopWindowTarget, Fullscreen, MonitorHandle, PlatformSpecificWindowBuilderAttributes,
 };
 
 // Currently GTK doesn't provide feature for detect theme, so we need to check theme manually.
 // ref: https://github.com/WebKit/WebKit/blob/e44ffaa0d999a9807f76f1805943eea204cfdfbc/Source/WebKit/UIProcess/API/gtk/PageClientImpl.cpp#L587
 const GTK_THEME_SUFFIX_LIST: [&str; 3] = ["-dark", "-Dark", "-Darker"];
 
+/// The theme settings of the session before any window changed them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SystemTheme {
+    name: Option<glib::GString>,
+    prefer_dark: bool,
+}
+
+impl SystemTheme {
+    pub(crate) fn new() -> Self {
+        Settings::default()
+            .map(|settings| Self {
+                name: settings.gtk_theme_name(),
+                prefer_dark: settings.is_gtk_application_prefer_dark_theme(),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Apply `theme`, or restore the system theme for `None`.
+    ///
+    /// GTK settings are shared by the whole application, so this affects every window.
+    pub(crate) fn apply(&self, theme: Option<Theme>) {
+        let settings = match Settings::default() {
+            Some(settings) => settings,
+            None => return,
+        };
+        match theme {
+            Some(Theme::Dark) => settings.set_gtk_application_prefer_dark_theme(true),
+            Some(Theme::Light) => {
+                settings.set_gtk_application_prefer_dark_theme(false);
+                let theme_name = settings.gtk_theme_name().map(|t| t.as_str().to_owned());
+                if let Some(theme) = theme_name {
+                    // Remove dark variant.
+                    if let Some(theme) = GTK_THEME_SUFFIX_LIST
+                        .iter()
+                        .find(|t| theme.ends_with(*t))
+                        .map(|v| theme.strip_suffix(v))
+                    {
+                        settings.set_gtk_theme_name(theme);
+                    }
+                }
+            }
+            None => {
+                settings.set_gtk_application_prefer_dark_theme(self.prefer_dark);
+                settings.set_gtk_theme_name(self.name.as_deref());
+            }
+        }
+    }
+}
+
+/// Returns the theme the GTK settings currently result in.
+pub(crate) fn current_theme() -> Theme {
+    if let Some(settings) = Settings::default() {
+        if settings.is_gtk_application_prefer_dark_theme() {
+            return Theme::Dark;
+        }
+        let theme_name = settings.gtk_theme_name().map(|s| s.as_str().to_owned());
+        if let Some(theme) = theme_name {
+            if GTK_THEME_SUFFIX_LIST.iter().any(|t| theme.ends_with(t)) {
+                return Theme::Dark;
+            }
+        }
+    }
+    Theme::Light
+}
+
 pub(crate) enum WindowRequest {
     Title(String),
     Position((i32, i32)),
     Size((i32, i32)),
-    SizeConstraints(Option<Size>, Option<Size>),
+    SizeConstraints {
+        min_size: Option<Size>,
+        max_size: Option<Size>,
+        resize_increments: Option<Size>,
+    },
     Visible(bool),
     Focus,
     Resizable(bool),
     // Closable(bool),
+    EnabledButtons(WindowButtons),
     Minimized(bool),
     Maximized(bool),
     DragWindow,
+    DragResizeWindow(ResizeDirection),
     Fullscreen(Option<Fullscreen>),
-    Decorations(bool),
+    /// Carries the flag freezing the position cache while the frame changes.
+    Decorations(bool, Rc<AtomicBool>),
     AlwaysOnBottom(bool),
     AlwaysOnTop(bool),
     WindowIcon(Option<Icon>),
     UserAttention(Option<UserAttentionType>),
+    UserAttentionFor(Duration),
     SetSkipTaskbar(bool),
-    CursorIcon(Option<CursorIcon>),
+    Modal(bool),
+    SetVisibleOnAllWorkspaces(bool),
+    Transparent {
+        rgba_visual: bool,
+        app_paintable: bool,
+    },
+    CursorIcon(CursorState),
     CursorPosition((i32, i32)),
-    CursorIgnoreEvents(bool),
-    WireUpEvents { transparent: Rc<AtomicBool> },
-    // SetVisibleOnAllWorkspaces(bool),
+    CursorHitTest(bool),
+    CursorGrab(CursorGrabMode),
+    ImeAllowed(bool),
+    ImePosition((i32, i32)),
+    ImePurpose(ImePurpose),
+    Beep,
+    Close,
+    /// Carries the flag telling whether the hint was applied.
+    CompositorBypass(bool, Rc<AtomicBool>),
+    AllowTearing(bool, Rc<TearingControl>),
+    ContentProtected(bool),
+    ResizeToContent,
+    WireUpEvents {
+        transparent: Rc<AtomicBool>,
+        cursor_locked: Rc<AtomicBool>,
+        ime_allowed: Rc<AtomicBool>,
+        position_frozen: Rc<AtomicBool>,
+        hide_on_close: Rc<AtomicBool>,
+        cursor: Rc<Cell<CursorState>>,
+        /// Whether `CursorMoved` is emitted.
+        cursor_moved: bool,
+        default_handlers: DefaultHandlers,
+    },
     // ProgressBarState(ProgressBarState),
 }
 
+/// The cursor asked for by the application, applied again whenever GTK may have reset it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CursorState {
+    icon: CursorIcon,
+    visible: bool,
+    /// The size of the cursor in logical pixels, instead of the one of the cursor theme.
+    size: Option<u32>,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            icon: CursorIcon::Default,
+            visible: true,
+            size: None,
+        }
+    }
+}
+
+impl CursorState {
+    /// Returns the cursor to show, `None` hides it.
+    pub(crate) fn cursor(self) -> Option<CursorIcon> {
+        self.visible.then_some(self.icon)
+    }
+
+    pub(crate) fn size(self) -> Option<u32> {
+        self.size
+    }
+}
+
+/// Sender of the requests of a window, which refuses them once the window is destroyed.
+///
+/// This way requests of a destroyed window fail like the ones of a closed event loop, instead of
+/// being silently dropped by the event loop.
+#[derive(Clone)]
+pub(crate) struct WindowRequestSender {
+    tx: glib::Sender<(WindowId, WindowRequest)>,
+    destroyed: Rc<AtomicBool>,
+}
+
+impl WindowRequestSender {
+    pub(crate) fn send(
+        &self,
+        request: (WindowId, WindowRequest),
+    ) -> Result<(), mpsc::SendError<(WindowId, WindowRequest)>> {
+        if self.destroyed.load(Ordering::Acquire) {
+            return Err(mpsc::SendError(request));
+        }
+        self.tx.send(request)
+    }
+}
+
 pub struct Window {
     /// Window id.
     pub(crate) window_id: WindowId,
@@ -69,18 +228,43 @@ pub struct Window {
     pub(crate) window: gtk::ApplicationWindow,
     pub(crate) default_vbox: Option<gtk::Box>,
     /// Window requests sender
-    pub(crate) window_requests_tx: glib::Sender<(WindowId, WindowRequest)>,
+    pub(crate) window_requests_tx: WindowRequestSender,
+    /// Whether the window was destroyed, the `Window` is only a husk afterwards.
+    destroyed: Rc<AtomicBool>,
     scale_factor: Rc<AtomicI32>,
     position: Rc<(AtomicI32, AtomicI32)>,
+    /// Whether `position` ignores configure events, as they are transient.
+    position_frozen: Rc<AtomicBool>,
     size: Rc<(AtomicI32, AtomicI32)>,
     maximized: Rc<AtomicBool>,
     minimized: Rc<AtomicBool>,
-    fullscreen: RefCell<Option<Fullscreen>>,
+    sticky: Rc<AtomicBool>,
+    /// Follows the window state, which the window manager may change on its own.
+    fullscreen: Rc<RefCell<Option<Fullscreen>>>,
     min_size: RefCell<Option<Size>>,
     max_size: RefCell<Option<Size>>,
+    resize_increments: RefCell<Option<Size>>,
+    enabled_buttons: RefCell<WindowButtons>,
+    window_level: Cell<WindowLevel>,
+    skip_taskbar: Cell<bool>,
     transparent: Rc<AtomicBool>,
+    /// The builder options of transparency, which `set_transparent(false)` keeps.
+    rgba_visual: bool,
+    app_paintable: bool,
+    auto_transparent: bool,
+    cursor_locked: Rc<AtomicBool>,
+    cursor: Rc<Cell<CursorState>>,
+    ime_allowed: Rc<AtomicBool>,
+    compositor_bypass: Rc<AtomicBool>,
+    tearing_control: Rc<TearingControl>,
+    /// Whether closing the window hides it instead of emitting `CloseRequested`.
+    hide_on_close: Rc<AtomicBool>,
+    /// Whether the window is a layer surface, which the compositor places on its own.
+    layer_shell: bool,
+    system_theme: SystemTheme,
     /// Draw event Sender
     draw_tx: crossbeam_channel::Sender<WindowId>,
+    device_ids: Rc<RefCell<DeviceIds>>,
 }
 impl Window {
     #[inline]
@@ -90,36 +274,85 @@ impl Window {
         pl_attribs: PlatformSpecificWindowBuilderAttributes,
     ) -> Result<Self, RootOsError> {
         let app = &window_target.app;
-        let window_requests_tx = window_target.window_requests_tx.clone();
         let draw_tx = window_target.draw_tx.clone();
         let window = gtk::ApplicationWindow::builder()
             .application(app)
             .accept_focus(attribs.active)
             .build();
         let window_id = WindowId(window.id() as u64);
+        let destroyed = Rc::new(AtomicBool::new(false));
+        let destroyed_clone = destroyed.clone();
+        window.connect_destroy(move |_| destroyed_clone.store(true, Ordering::Release));
+        let window_requests_tx = WindowRequestSender {
+            tx: window_target.window_requests_tx.clone(),
+            destroyed: destroyed.clone(),
+        };
         window_target.windows.borrow_mut().insert(window_id);
+        let windows = Rc::downgrade(&window_target.windows);
+        window.connect_destroy(move |_| {
+            if let Some(windows) = windows.upgrade() {
+                windows.borrow_mut().remove(&window_id);
+            }
+        });
+
+        if let Some(name) = &pl_attribs.name {
+            set_application_name(&window, name, window_target.is_wayland());
+        }
+
+        // The layer surface role must be given before the window is realized.
+        #[cfg(feature = "layer-shell")]
+        let layer_shell = match pl_attribs.layer_shell {
+            Some(config) if window_target.is_wayland() && layer_shell::is_supported() => {
+                layer_shell::init(&window, &config);
+                true
+            }
+            Some(_) => {
+                log::warn!("The layer shell is not supported, creating a regular window");
+                false
+            }
+            None => false,
+        };
+        #[cfg(not(feature = "layer-shell"))]
+        let layer_shell = false;
 
         // Set Width/Height & Resizable
         let win_scale_factor = window.scale_factor();
-        let (width, height) = attribs
-            .inner_size
-            .map(|size| size.to_logical::<f64>(win_scale_factor as f64).into())
-            .unwrap_or((800, 600));
-        window.set_default_size(1, 1);
-        window.resize(width, height);
+        // Without an explicit size, a content-sized window lets GTK's size negotiation pick its
+        // natural size.
+        if !pl_attribs.size_from_content || attribs.inner_size.is_some() {
+            let (width, height) = attribs
+                .inner_size
+                .map(|size| util::physical_to_logical_size(size, win_scale_factor as f64))
+                .unwrap_or((800, 600));
+            window.set_default_size(1, 1);
+            window.resize(width, height);
+        }
 
         if attribs.maximized {
             window.maximize();
         }
         window.set_resizable(attribs.resizable);
         // window.set_deletable(attribs.closable);
+        window.set_deletable(attribs.enabled_buttons.contains(WindowButtons::CLOSE));
 
         // Set Min/Max Size
-        util::set_size_constraints(&window, attribs.min_inner_size, attribs.max_inner_size);
+        util::set_size_constraints(
+            &window,
+            attribs.min_inner_size,
+            attribs.max_inner_size,
+            attribs.resize_increments,
+        );
+
+        // The parent is read when the window is mapped, dialogs are centered on it unless placed.
+        if let Some(parent) = &pl_attribs.parent {
+            window.set_transient_for(Some(parent));
+            window.set_position(gtk::WindowPosition::CenterOnParent);
+        }
+        window.set_modal(pl_attribs.modal);
 
         // Set Position
-        if let Some(position) = attribs.position {
-            let (x, y): (i32, i32) = position.to_logical::<i32>(win_scale_factor as f64).into();
+        if let Some(position) = attribs.position.filter(|_| !layer_shell) {
+            let (x, y) = util::physical_to_logical_position(position, win_scale_factor as f64);
             window.move_(x, y);
         }
 
@@ -159,10 +392,17 @@ impl Window {
 
         // Rest attributes
         window.set_title(&attribs.title);
-        let fullscreen = attribs.fullscreen.map(|f| f.into());
-        if fullscreen != None {
+        let fullscreen = attribs
+            .fullscreen
+            .filter(|_| !layer_shell)
+            .map(|f| borderless(f.into()));
+        // The video mode of exclusive fullscreen is switched by the event loop.
+        let exclusive_fullscreen = match fullscreen {
+            Some(Fullscreen::Exclusive(_)) => fullscreen.clone(),
+            _ => None,
+        };
+        if fullscreen.is_some() && exclusive_fullscreen.is_none() {
             let m = match fullscreen {
-                Some(Fullscreen::Exclusive(ref m)) => Some(&m.monitor),
                 Some(Fullscreen::Borderless(Some(ref m))) => Some(&m.monitor),
                 _ => None,
             };
@@ -180,46 +420,27 @@ impl Window {
                 window.fullscreen();
             }
         }
+        // The hints are read when the window is mapped, so the taskbar never shows it.
+        window.set_skip_taskbar_hint(pl_attribs.skip_taskbar);
+        window.set_skip_pager_hint(pl_attribs.skip_taskbar);
         window.set_visible(attribs.visible);
-        window.set_decorated(attribs.decorations);
+        window.set_decorated(attribs.decorations && !layer_shell);
 
-        match attribs.window_level {
-            WindowLevel::AlwaysOnBottom => window.set_keep_below(true),
-            WindowLevel::Normal => (),
-            WindowLevel::AlwaysOnTop => window.set_keep_above(true),
-        }
+        let (keep_above, keep_below) = keep_above_below(attribs.window_level);
+        window.set_keep_above(keep_above);
+        window.set_keep_below(keep_below);
 
-        // TODO imeplement this?
-        // if attribs.visible_on_all_workspaces {
-        //     window.stick();
-        // }
+        if attribs.visible_on_all_workspaces {
+            window.stick();
+        }
 
         if let Some(icon) = attribs.window_icon {
             window.set_icon(Some(&icon.inner.into()));
         }
 
         // Set theme
-        let settings = Settings::default();
-
-        if let Some(settings) = settings {
-            if let Some(preferred_theme) = attribs.preferred_theme {
-                match preferred_theme {
-                    Theme::Dark => settings.set_gtk_application_prefer_dark_theme(true),
-                    Theme::Light => {
-                        let theme_name = settings.gtk_theme_name().map(|t| t.as_str().to_owned());
-                        if let Some(theme) = theme_name {
-                            // Remove dark variant.
-                            if let Some(theme) = GTK_THEME_SUFFIX_LIST
-                                .iter()
-                                .find(|t| theme.ends_with(*t))
-                                .map(|v| theme.strip_suffix(v))
-                            {
-                                settings.set_gtk_theme_name(theme);
-                            }
-                        }
-                    }
-                }
-            }
+        if attribs.preferred_theme.is_some() {
+            window_target.system_theme.apply(attribs.preferred_theme);
         }
 
         if attribs.visible {
@@ -228,12 +449,6 @@ impl Window {
             window.hide();
         }
 
-        // TODO it's impossible to set parent window from raw handle.
-        // We need a gtk variant of it.
-        if let Some(parent) = pl_attribs.parent {
-            window.set_transient_for(Some(&parent));
-        }
-
         // TODO I don't understand why unfocussed window need focus
         // restore accept-focus after the window has been drawn
         // if the window was initially created without focus
@@ -254,15 +469,28 @@ impl Window {
         let w_pos = window.position();
         let position: Rc<(AtomicI32, AtomicI32)> = Rc::new((w_pos.0.into(), w_pos.1.into()));
         let position_clone = position.clone();
+        let position_frozen = Rc::new(AtomicBool::new(false));
+        let position_frozen_clone = position_frozen.clone();
 
         let w_size = window.size();
         let size: Rc<(AtomicI32, AtomicI32)> = Rc::new((w_size.0.into(), w_size.1.into()));
         let size_clone = size.clone();
 
+        // A window hidden after it was shown keeps its geometry until it is shown again.
+        let hidden = Rc::new(Cell::new(false));
+        let hidden_clone = hidden.clone();
+        window.connect_unmap(move |_| hidden_clone.set(true));
+        let hidden_clone = hidden.clone();
+        window.connect_map(move |_| hidden_clone.set(false));
         window.connect_configure_event(move |_, event| {
-            let (x, y) = event.position();
-            position_clone.0.store(x, Ordering::Release);
-            position_clone.1.store(y, Ordering::Release);
+            if hidden.get() {
+                return false;
+            }
+            if !position_frozen_clone.load(Ordering::Acquire) {
+                let (x, y) = event.position();
+                position_clone.0.store(x, Ordering::Release);
+                position_clone.1.store(y, Ordering::Release);
+            }
 
             let (w, h) = event.size();
             size_clone.0.store(w as i32, Ordering::Release);
@@ -277,11 +505,36 @@ impl Window {
         let max_clone = maximized.clone();
         let minimized = Rc::new(AtomicBool::new(false));
         let minimized_clone = minimized.clone();
+        let sticky = Rc::new(AtomicBool::new(attribs.visible_on_all_workspaces));
+        let sticky_clone = sticky.clone();
+        let fullscreen = Rc::new(RefCell::new(fullscreen));
+        let fullscreen_clone = fullscreen.clone();
 
-        window.connect_window_state_event(move |_, event| {
+        window.connect_window_state_event(move |window, event| {
             let state = event.new_window_state();
             max_clone.store(state.contains(WindowState::MAXIMIZED), Ordering::Release);
             minimized_clone.store(state.contains(WindowState::ICONIFIED), Ordering::Release);
+            sticky_clone.store(state.contains(WindowState::STICKY), Ordering::Release);
+            // Window managers may end the fullscreen of a withdrawn window, a hidden window keeps
+            // its state until it is shown again.
+            if event.changed_mask().contains(WindowState::FULLSCREEN)
+                && !state.contains(WindowState::WITHDRAWN)
+            {
+                let exclusive = match &*fullscreen_clone.borrow() {
+                    Some(fullscreen @ Fullscreen::Exclusive(_)) => Some(fullscreen.clone()),
+                    _ => None,
+                };
+                let fullscreen = state.contains(WindowState::FULLSCREEN).then(|| {
+                    if let Some(exclusive) = exclusive {
+                        return exclusive;
+                    }
+                    let monitor = window
+                        .window()
+                        .and_then(|gdk_window| window.display().monitor_at_window(&gdk_window));
+                    Fullscreen::Borderless(monitor.map(MonitorHandle::new))
+                });
+                fullscreen_clone.replace(fullscreen);
+            }
             glib::Propagation::Proceed
         });
 
@@ -292,12 +545,37 @@ impl Window {
             scale_factor_clone.store(window.scale_factor(), Ordering::Release);
         });
 
+        // The compositor bypass hint is a property of the XID, so it is set whenever one is
+        // created.
+        let compositor_bypass: Rc<AtomicBool> = Default::default();
+        let compositor_bypass_clone = compositor_bypass.clone();
+        window.connect_realize(move |window| {
+            if compositor_bypass_clone.load(Ordering::Acquire) {
+                let is_set = window
+                    .window()
+                    .is_some_and(|gdk_window| util::set_compositor_bypass(&gdk_window, true));
+                compositor_bypass_clone.store(is_set, Ordering::Release);
+            }
+        });
+
+        // GDK destroys the Wayland surface when the window is hidden and creates a new one when
+        // it's shown.
+        let tearing_control: Rc<TearingControl> = Default::default();
+        let tearing_control_clone = tearing_control.clone();
+        window.connect_map(move |window| tearing_control_clone.apply(window.upcast_ref()));
+        let tearing_control_clone = tearing_control.clone();
+        window.connect_unmap(move |_| tearing_control_clone.reset());
+
         // Check if we should paint the transparent background ourselves.
         let mut transparent = false;
         if attribs.transparent && pl_attribs.auto_transparent {
             transparent = true;
         }
         let transparent = Rc::new(AtomicBool::new(transparent));
+        let cursor_locked = Rc::new(AtomicBool::new(false));
+        let ime_allowed = Rc::new(AtomicBool::new(false));
+        let hide_on_close = Rc::new(AtomicBool::new(false));
+        let cursor: Rc<Cell<CursorState>> = Default::default();
 
         // Send WireUp event to let eventloop handle the rest of window setup to prevent gtk panic
         // in other thread.
@@ -305,11 +583,36 @@ impl Window {
             window_id,
             WindowRequest::WireUpEvents {
                 transparent: transparent.clone(),
+                cursor_locked: cursor_locked.clone(),
+                position_frozen: position_frozen.clone(),
+                ime_allowed: ime_allowed.clone(),
+                hide_on_close: hide_on_close.clone(),
+                cursor: cursor.clone(),
+                cursor_moved: pl_attribs.cursor_moved,
+                default_handlers: pl_attribs.default_handlers,
             },
         )) {
             log::warn!("Fail to send wire up events request: {}", e);
         }
 
+        // WM functions can only be set on a realized window, so let the event loop apply them.
+        if attribs.enabled_buttons != WindowButtons::all() {
+            if let Err(e) = window_requests_tx.send((
+                window_id,
+                WindowRequest::EnabledButtons(attribs.enabled_buttons),
+            )) {
+                log::warn!("Fail to send enabled buttons request: {}", e);
+            }
+        }
+
+        if exclusive_fullscreen.is_some() {
+            if let Err(e) = window_requests_tx
+                .send((window_id, WindowRequest::Fullscreen(exclusive_fullscreen)))
+            {
+                log::warn!("Fail to send fullscreen request: {}", e);
+            }
+        }
+
         if let Err(e) = draw_tx.send(window_id) {
             log::warn!("Failed to send redraw event to event channel: {}", e);
         }
@@ -319,19 +622,40 @@ impl Window {
             window,
             default_vbox,
             window_requests_tx,
+            destroyed,
             draw_tx,
             scale_factor,
             position,
+            position_frozen,
             size,
             maximized,
             minimized,
-            fullscreen: RefCell::new(fullscreen),
+            sticky,
+            fullscreen,
             min_size: RefCell::new(attribs.min_inner_size),
-            max_size: RefCell::new(attribs.min_inner_size),
+            max_size: RefCell::new(attribs.max_inner_size),
+            resize_increments: RefCell::new(attribs.resize_increments),
+            enabled_buttons: RefCell::new(attribs.enabled_buttons),
+            window_level: Cell::new(attribs.window_level),
+            skip_taskbar: Cell::new(pl_attribs.skip_taskbar),
             transparent,
+            rgba_visual: pl_attribs.rgba_visual,
+            app_paintable: pl_attribs.app_paintable,
+            auto_transparent: pl_attribs.auto_transparent,
+            cursor_locked,
+            cursor,
+            ime_allowed,
+            compositor_bypass,
+            tearing_control,
+            hide_on_close,
+            layer_shell,
+            system_theme: window_target.system_theme.clone(),
+            device_ids: window_target.device_ids.clone(),
         };
 
-        win.set_skip_taskbar(pl_attribs.skip_taskbar);
+        if attribs.content_protected {
+            win.set_content_protected(true);
+        }
 
         Ok(win)
     }
@@ -353,7 +677,17 @@ impl Window {
 
     #[inline]
     pub fn set_transparent(&self, transparent: bool) {
-        self.transparent.store(transparent, Ordering::Relaxed);
+        self.transparent
+            .store(transparent && self.auto_transparent, Ordering::Relaxed);
+        if let Err(e) = self.window_requests_tx.send((
+            self.window_id,
+            WindowRequest::Transparent {
+                rgba_visual: transparent || self.rgba_visual,
+                app_paintable: transparent || self.app_paintable,
+            },
+        )) {
+            log::warn!("Fail to send transparent request: {}", e);
+        }
     }
 
     #[inline]
@@ -368,28 +702,42 @@ impl Window {
 
     #[inline]
     pub fn is_visible(&self) -> Option<bool> {
-        Some(self.window.is_visible())
+        Some(!self.is_destroyed() && self.window.is_visible())
+    }
+
+    /// Whether the window was destroyed, the getters report a window without a surface then.
+    fn is_destroyed(&self) -> bool {
+        self.destroyed.load(Ordering::Acquire)
     }
 
     #[inline]
     pub fn outer_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        if self.is_destroyed() {
+            return Err(NotSupportedError::new());
+        }
         let (x, y) = &*self.position;
-        Ok(
-            LogicalPosition::new(x.load(Ordering::Acquire), y.load(Ordering::Acquire))
-                .to_physical(self.scale_factor.load(Ordering::Acquire) as f64),
-        )
+        Ok(util::logical_to_physical_position(
+            (x.load(Ordering::Acquire), y.load(Ordering::Acquire)),
+            self.scale_factor(),
+        ))
     }
     #[inline]
     pub fn inner_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        if self.is_destroyed() {
+            return Err(NotSupportedError::new());
+        }
         let (x, y) = &*self.position;
-        Ok(
-            LogicalPosition::new(x.load(Ordering::Acquire), y.load(Ordering::Acquire))
-                .to_physical(self.scale_factor.load(Ordering::Acquire) as f64),
-        )
+        Ok(util::logical_to_physical_position(
+            (x.load(Ordering::Acquire), y.load(Ordering::Acquire)),
+            self.scale_factor(),
+        ))
     }
     #[inline]
     pub fn set_outer_position(&self, position: Position) {
-        let (x, y): (i32, i32) = position.to_logical::<i32>(self.scale_factor()).into();
+        if self.layer_shell {
+            return;
+        }
+        let (x, y) = util::physical_to_logical_position(position, self.scale_factor());
 
         if let Err(e) = self
             .window_requests_tx
@@ -400,29 +748,37 @@ impl Window {
     }
     #[inline]
     pub fn inner_size(&self) -> PhysicalSize<u32> {
+        if self.is_destroyed() {
+            return PhysicalSize::new(0, 0);
+        }
         let (width, height) = &*self.size;
-
-        LogicalSize::new(
-            width.load(Ordering::Acquire) as u32,
-            height.load(Ordering::Acquire) as u32,
+        util::logical_to_physical_size(
+            (
+                width.load(Ordering::Acquire),
+                height.load(Ordering::Acquire),
+            ),
+            self.scale_factor(),
         )
-        .to_physical(self.scale_factor.load(Ordering::Acquire) as f64)
     }
 
     #[inline]
     pub fn outer_size(&self) -> PhysicalSize<u32> {
+        if self.is_destroyed() {
+            return PhysicalSize::new(0, 0);
+        }
         let (width, height) = &*self.size;
-
-        LogicalSize::new(
-            width.load(Ordering::Acquire) as u32,
-            height.load(Ordering::Acquire) as u32,
+        util::logical_to_physical_size(
+            (
+                width.load(Ordering::Acquire),
+                height.load(Ordering::Acquire),
+            ),
+            self.scale_factor(),
         )
-        .to_physical(self.scale_factor.load(Ordering::Acquire) as f64)
     }
 
     #[inline]
     pub fn set_inner_size(&self, size: Size) {
-        let (width, height) = size.to_logical::<i32>(self.scale_factor()).into();
+        let (width, height) = util::physical_to_logical_size(size, self.scale_factor());
 
         if let Err(e) = self
             .window_requests_tx
@@ -435,7 +791,11 @@ impl Window {
     fn set_size_constraints(&self) {
         if let Err(e) = self.window_requests_tx.send((
             self.window_id,
-            WindowRequest::SizeConstraints(*self.min_size.borrow(), *self.max_size.borrow()),
+            WindowRequest::SizeConstraints {
+                min_size: *self.min_size.borrow(),
+                max_size: *self.max_size.borrow(),
+                resize_increments: *self.resize_increments.borrow(),
+            },
         )) {
             log::warn!("Fail to send size constraint request: {}", e);
         }
@@ -443,27 +803,35 @@ impl Window {
 
     #[inline]
     pub fn set_min_inner_size(&self, dimensions: Option<Size>) {
-        let mut min_size = self.min_size.borrow_mut();
-        *min_size = dimensions;
+        self.min_size.replace(dimensions);
         self.set_size_constraints()
     }
 
     #[inline]
     pub fn set_max_inner_size(&self, dimensions: Option<Size>) {
-        let mut max_size = self.min_size.borrow_mut();
-        *max_size = dimensions;
+        self.max_size.replace(dimensions);
         self.set_size_constraints()
     }
 
     #[inline]
     pub fn resize_increments(&self) -> Option<PhysicalSize<u32>> {
-        // TODO implement this
-        None
+        // Report the increments GTK applies, which are whole logical pixels.
+        let scale_factor = self.scale_factor();
+        if self.is_destroyed() {
+            return None;
+        }
+        self.resize_increments.borrow().map(|size| {
+            util::logical_to_physical_size(
+                util::physical_to_logical_size(size, scale_factor),
+                scale_factor,
+            )
+        })
     }
 
     #[inline]
-    pub fn set_resize_increments(&self, _increments: Option<Size>) {
-        // TODO implement this
+    pub fn set_resize_increments(&self, increments: Option<Size>) {
+        self.resize_increments.replace(increments);
+        self.set_size_constraints()
     }
 
     #[inline]
@@ -482,49 +850,88 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_enabled_buttons(&self, _buttons: WindowButtons) {
-        // TODO implement this
+    pub fn set_enabled_buttons(&self, buttons: WindowButtons) {
+        self.enabled_buttons.replace(buttons);
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::EnabledButtons(buttons)))
+        {
+            log::warn!("Fail to send enabled buttons request: {}", e);
+        }
     }
 
     #[inline]
     pub fn enabled_buttons(&self) -> WindowButtons {
-        // TODO implement this
-        WindowButtons::empty()
+        // The close button may also be toggled on the `gtk_window` directly.
+        let mut buttons = *self.enabled_buttons.borrow();
+        buttons.set(WindowButtons::CLOSE, self.window.is_deletable());
+        buttons
     }
 
     #[inline]
     pub fn set_cursor_icon(&self, cursor: CursorIcon) {
+        let mut state = self.cursor.get();
+        state.icon = cursor;
+        self.cursor.set(state);
+        // A hidden cursor stays hidden.
         if let Err(e) = self
             .window_requests_tx
-            .send((self.window_id, WindowRequest::CursorIcon(Some(cursor))))
+            .send((self.window_id, WindowRequest::CursorIcon(state)))
         {
             log::warn!("Fail to send cursor icon request: {}", e);
         }
     }
 
     #[inline]
-    pub fn set_cursor_grab(&self, _mode: CursorGrabMode) -> Result<(), ExternalError> {
-        // TODO implement this
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        // Locking is emulated by warping the pointer, which Wayland doesn't allow.
+        if mode == CursorGrabMode::Locked && self.is_wayland() {
+            return Err(ExternalError::NotSupported(NotSupportedError::new()));
+        }
+
+        self.cursor_locked
+            .store(mode == CursorGrabMode::Locked, Ordering::Release);
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::CursorGrab(mode)))
+        {
+            log::warn!("Fail to send cursor grab request: {}", e);
+        }
         Ok(())
     }
 
     #[inline]
     pub fn set_cursor_visible(&self, visible: bool) {
-        let cursor = if visible {
-            Some(CursorIcon::Default)
-        } else {
-            None
-        };
+        let mut state = self.cursor.get();
+        state.visible = visible;
+        self.cursor.set(state);
         if let Err(e) = self
             .window_requests_tx
-            .send((self.window_id, WindowRequest::CursorIcon(cursor)))
+            .send((self.window_id, WindowRequest::CursorIcon(state)))
         {
             log::warn!("Fail to send cursor visibility request: {}", e);
         }
     }
 
+    pub fn set_cursor_size_override(&self, size: Option<u32>) {
+        let mut state = self.cursor.get();
+        state.size = size;
+        self.cursor.set(state);
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::CursorIcon(state)))
+        {
+            log::warn!("Fail to send cursor size request: {}", e);
+        }
+    }
+
     #[inline]
     pub fn drag_window(&self) -> Result<(), ExternalError> {
+        if util::drag_pointer(self.window.upcast_ref()).is_none() {
+            return Err(ExternalError::Os(os_error!(OsError::Misc(
+                "Failed to start dragging the window"
+            ))));
+        }
         if let Err(e) = self
             .window_requests_tx
             .send((self.window_id, WindowRequest::DragWindow))
@@ -535,8 +942,18 @@ impl Window {
     }
 
     #[inline]
-    pub fn drag_resize_window(&self, _direction: ResizeDirection) -> Result<(), ExternalError> {
-        // TODO implement this
+    pub fn drag_resize_window(&self, direction: ResizeDirection) -> Result<(), ExternalError> {
+        if util::drag_pointer(self.window.upcast_ref()).is_none() {
+            return Err(ExternalError::Os(os_error!(OsError::Misc(
+                "Failed to start resizing the window"
+            ))));
+        }
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::DragResizeWindow(direction)))
+        {
+            log::warn!("Fail to send drag resize window request: {}", e);
+        }
         Ok(())
     }
 
@@ -544,9 +961,9 @@ impl Window {
     pub fn set_cursor_hittest(&self, hittest: bool) -> Result<(), ExternalError> {
         if let Err(e) = self
             .window_requests_tx
-            .send((self.window_id, WindowRequest::CursorIgnoreEvents(!hittest)))
+            .send((self.window_id, WindowRequest::CursorHitTest(hittest)))
         {
-            log::warn!("Fail to send cursor position request: {}", e);
+            log::warn!("Fail to send cursor hittest request: {}", e);
         }
 
         Ok(())
@@ -559,12 +976,17 @@ impl Window {
 
     #[inline]
     pub fn set_cursor_position(&self, position: Position) -> Result<(), ExternalError> {
-        let inner_pos = self.inner_position().unwrap_or_default();
-        let (x, y): (i32, i32) = position.to_logical::<i32>(self.scale_factor()).into();
+        // Both in the logical pixels of GTK, the inner position is physical.
+        let (inner_x, inner_y) = &*self.position;
+        let inner_pos = (
+            inner_x.load(Ordering::Acquire),
+            inner_y.load(Ordering::Acquire),
+        );
+        let (x, y) = util::physical_to_logical_position(position, self.scale_factor());
 
         if let Err(e) = self.window_requests_tx.send((
             self.window_id,
-            WindowRequest::CursorPosition((x + inner_pos.x, y + inner_pos.y)),
+            WindowRequest::CursorPosition((x + inner_pos.0, y + inner_pos.1)),
         )) {
             log::warn!("Fail to send cursor position request: {}", e);
         }
@@ -584,7 +1006,7 @@ impl Window {
 
     #[inline]
     pub fn is_maximized(&self) -> bool {
-        self.maximized.load(Ordering::Acquire)
+        !self.is_destroyed() && self.maximized.load(Ordering::Acquire)
     }
 
     #[inline]
@@ -599,16 +1021,23 @@ impl Window {
 
     #[inline]
     pub fn is_minimized(&self) -> Option<bool> {
-        Some(self.minimized.load(Ordering::Acquire))
+        Some(!self.is_destroyed() && self.minimized.load(Ordering::Acquire))
     }
 
     #[inline]
     pub(crate) fn fullscreen(&self) -> Option<Fullscreen> {
+        if self.is_destroyed() {
+            return None;
+        }
         self.fullscreen.borrow().clone()
     }
 
     #[inline]
     pub(crate) fn set_fullscreen(&self, monitor: Option<Fullscreen>) {
+        if self.layer_shell {
+            return;
+        }
+        let monitor = monitor.map(borderless);
         self.fullscreen.replace(monitor.clone());
         if let Err(e) = self
             .window_requests_tx
@@ -620,10 +1049,13 @@ impl Window {
 
     #[inline]
     pub fn set_decorations(&self, decorations: bool) {
-        if let Err(e) = self
-            .window_requests_tx
-            .send((self.window_id, WindowRequest::Decorations(decorations)))
-        {
+        if self.layer_shell {
+            return;
+        }
+        if let Err(e) = self.window_requests_tx.send((
+            self.window_id,
+            WindowRequest::Decorations(decorations, self.position_frozen.clone()),
+        )) {
             log::warn!("Fail to send decorations request: {}", e);
         }
     }
@@ -633,25 +1065,32 @@ impl Window {
         self.window.is_decorated()
     }
 
+    pub fn is_using_csd(&self) -> bool {
+        if self.is_destroyed() {
+            return false;
+        }
+        // GTK styles the window it decorates itself, `solid-csd` is used without a compositor.
+        let style_context = self.window.style_context();
+        style_context.has_class("csd") || style_context.has_class("solid-csd")
+    }
+
     #[inline]
     pub fn set_window_level(&self, level: WindowLevel) {
-        match level {
-            WindowLevel::AlwaysOnBottom => {
-                if let Err(e) = self
-                    .window_requests_tx
-                    .send((self.window_id, WindowRequest::AlwaysOnTop(true)))
-                {
-                    log::warn!("Fail to send always on top request: {}", e);
-                }
-            }
-            WindowLevel::Normal => (),
-            WindowLevel::AlwaysOnTop => {
-                if let Err(e) = self
-                    .window_requests_tx
-                    .send((self.window_id, WindowRequest::AlwaysOnBottom(true)))
-                {
-                    log::warn!("Fail to send always on bottom request: {}", e);
-                }
+        if self.window_level.replace(level) == level {
+            return;
+        }
+        let (keep_above, keep_below) = keep_above_below(level);
+        let on_top = WindowRequest::AlwaysOnTop(keep_above);
+        let on_bottom = WindowRequest::AlwaysOnBottom(keep_below);
+        // GTK keeps both hints, the one that is cleared goes first.
+        let requests = if keep_above {
+            [on_bottom, on_top]
+        } else {
+            [on_top, on_bottom]
+        };
+        for request in requests {
+            if let Err(e) = self.window_requests_tx.send((self.window_id, request)) {
+                log::warn!("Fail to send window level request: {}", e);
             }
         }
     }
@@ -667,29 +1106,171 @@ impl Window {
     }
 
     #[inline]
-    pub fn set_ime_position(&self, _position: Position) {
-        // TODO implement this
+    pub fn set_ime_position(&self, position: Position) {
+        let position = util::physical_to_logical_position(position, self.scale_factor());
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::ImePosition(position)))
+        {
+            log::warn!("Fail to send IME position request: {}", e);
+        }
     }
 
     #[inline]
-    pub fn set_ime_allowed(&self, _allowed: bool) {
-        // TODO implement this
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        if self.ime_allowed.swap(allowed, Ordering::AcqRel) == allowed {
+            return;
+        }
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::ImeAllowed(allowed)))
+        {
+            log::warn!("Fail to send IME allowed request: {}", e);
+        }
     }
 
     #[inline]
-    pub fn set_ime_purpose(&self, _purpose: ImePurpose) {
-        // TODO implement this
+    pub fn set_ime_purpose(&self, purpose: ImePurpose) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::ImePurpose(purpose)))
+        {
+            log::warn!("Fail to send IME purpose request: {}", e);
+        }
     }
 
     #[inline]
     pub fn focus_window(&self) {
-        if !self.minimized.load(Ordering::Acquire) && self.window.get_visible() {
-            if let Err(e) = self
-                .window_requests_tx
-                .send((self.window_id, WindowRequest::Focus))
-            {
-                log::warn!("Fail to send visible request: {}", e);
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::Focus))
+        {
+            log::warn!("Fail to send focus request: {}", e);
+        }
+    }
+
+    #[inline]
+    pub fn is_focusable(&self) -> bool {
+        self.window.accepts_focus()
+    }
+
+    pub fn set_compositor_bypass_hint(&self, bypass: bool) {
+        if let Err(e) = self.window_requests_tx.send((
+            self.window_id,
+            WindowRequest::CompositorBypass(bypass, self.compositor_bypass.clone()),
+        )) {
+            log::warn!("Fail to send compositor bypass request: {}", e);
+        }
+    }
+
+    pub fn compositor_bypass_hint(&self) -> bool {
+        self.compositor_bypass.load(Ordering::Acquire)
+    }
+
+    pub fn is_composited(&self) -> bool {
+        !self.is_destroyed() && is_composited(&self.window, &self.compositor_bypass)
+    }
+
+    pub fn connect_composited_changed<F: Fn(bool) + 'static>(&self, f: F) {
+        let screen = match GtkWindowExt::screen(&self.window) {
+            Some(screen) => screen,
+            None => return,
+        };
+        let composited = Cell::new(self.is_composited());
+        let update = Rc::new(
+            move |window: &gtk::ApplicationWindow, bypass: &AtomicBool| {
+                let is_composited = is_composited(window, bypass);
+                if composited.replace(is_composited) != is_composited {
+                    f(is_composited);
+                }
+            },
+        );
+
+        let window = self.window.downgrade();
+        let bypass = self.compositor_bypass.clone();
+        let update_ = update.clone();
+        let handler = screen.connect_composited_changed(move |_| {
+            if let Some(window) = window.upgrade() {
+                update_(&window, &bypass);
+            }
+        });
+        // The screen outlives the window.
+        let handler = RefCell::new(Some(handler));
+        self.window.connect_destroy(move |_| {
+            if let Some(handler) = handler.take() {
+                screen.disconnect(handler);
             }
+        });
+
+        let bypass = self.compositor_bypass.clone();
+        self.window
+            .connect_window_state_event(move |window, event| {
+                if event.changed_mask().contains(WindowState::FULLSCREEN) {
+                    update(window, &bypass);
+                }
+                glib::Propagation::Proceed
+            });
+    }
+
+    pub fn connect_raw_event_timestamps<F: Fn(RawTimestamp) + 'static>(&self, f: F) {
+        let device_ids = self.device_ids.clone();
+        self.window.connect_event(move |_, event| {
+            use gdk::EventType::*;
+            let kind = match event.event_type() {
+                MotionNotify => RawInputEvent::CursorMoved,
+                ButtonPress | ButtonRelease => RawInputEvent::MouseInput,
+                Scroll => RawInputEvent::MouseWheel,
+                KeyPress | KeyRelease => RawInputEvent::KeyboardInput,
+                TouchBegin | TouchUpdate | TouchEnd | TouchCancel => RawInputEvent::Touch,
+                TouchpadPinch => RawInputEvent::TouchpadPinch,
+                _ => return glib::Propagation::Proceed,
+            };
+            let device_id = device_ids.borrow_mut().id(event);
+            f(util::raw_timestamp(event.time(), kind, device_id));
+            glib::Propagation::Proceed
+        });
+    }
+
+    pub fn set_allow_tearing(&self, allow: bool) -> Result<(), NotSupportedError> {
+        // X11 has no such hint.
+        if !util::wayland_capabilities(&self.window.display()).tearing_control {
+            return Err(NotSupportedError::new());
+        }
+        if let Err(e) = self.window_requests_tx.send((
+            self.window_id,
+            WindowRequest::AllowTearing(allow, self.tearing_control.clone()),
+        )) {
+            log::warn!("Fail to send allow tearing request: {}", e);
+        }
+        Ok(())
+    }
+
+    pub fn allows_tearing(&self) -> bool {
+        self.tearing_control.is_applied()
+    }
+
+    #[inline]
+    pub fn set_visible_on_all_workspaces(&self, visible: bool) {
+        if let Err(e) = self.window_requests_tx.send((
+            self.window_id,
+            WindowRequest::SetVisibleOnAllWorkspaces(visible),
+        )) {
+            log::warn!("Fail to send visible on all workspaces request: {}", e);
+        }
+    }
+
+    #[inline]
+    pub fn is_visible_on_all_workspaces(&self) -> bool {
+        !self.is_destroyed() && self.sticky.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_content_protected(&self, protected: bool) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::ContentProtected(protected)))
+        {
+            log::warn!("Fail to send content protected request: {}", e);
         }
     }
 
@@ -702,6 +1283,15 @@ impl Window {
         }
     }
 
+    pub fn request_user_attention_for(&self, duration: Duration) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::UserAttentionFor(duration)))
+        {
+            log::warn!("Fail to send user attention request: {}", e);
+        }
+    }
+
     #[inline]
     pub fn request_redraw(&self) {
         if let Err(e) = self.draw_tx.send(self.window_id) {
@@ -711,36 +1301,27 @@ impl Window {
 
     #[inline]
     pub fn current_monitor(&self) -> Option<MonitorHandle> {
+        if self.is_destroyed() {
+            return None;
+        }
         let display = self.window.display();
         // `.window()` returns `None` if the window is invisible;
         // we fallback to the primary monitor
         self.window
             .window()
-            .map(|window| display.monitor_at_window(&window))
-            .unwrap_or_else(|| display.primary_monitor())
-            .map(|monitor| MonitorHandle { monitor })
+            .and_then(|window| display.monitor_at_window(&window))
+            .map(MonitorHandle::new)
+            .or_else(|| monitor::primary_monitor(&display))
     }
 
     #[inline]
     pub fn available_monitors(&self) -> VecDeque<MonitorHandle> {
-        let mut handles = VecDeque::new();
-        let display = self.window.display();
-        let numbers = display.n_monitors();
-
-        for i in 0..numbers {
-            let monitor = MonitorHandle::new(&display, i);
-            handles.push_back(monitor);
-        }
-
-        handles
+        monitor::available_monitors(&self.window.display())
     }
 
     #[inline]
     pub fn primary_monitor(&self) -> Option<MonitorHandle> {
-        let display = self.window.display();
-        display
-            .primary_monitor()
-            .map(|monitor| MonitorHandle { monitor })
+        monitor::primary_monitor(&self.window.display())
     }
 
     fn is_wayland(&self) -> bool {
@@ -749,6 +1330,14 @@ impl Window {
 
     #[inline]
     pub fn raw_window_handle(&self) -> RawWindowHandle {
+        // The surface of a destroyed window is gone, hand out an empty handle.
+        if self.is_destroyed() {
+            return if self.is_wayland() {
+                RawWindowHandle::Wayland(WaylandWindowHandle::empty())
+            } else {
+                RawWindowHandle::Xlib(XlibWindowHandle::empty())
+            };
+        }
         if self.is_wayland() {
             let mut window_handle = WaylandWindowHandle::empty();
             if let Some(window) = self.window.window() {
@@ -795,44 +1384,17 @@ impl Window {
 
     #[inline]
     pub fn set_theme(&self, theme: Option<Theme>) {
-        if let Some(settings) = Settings::default() {
-            if let Some(preferred_theme) = theme {
-                match preferred_theme {
-                    Theme::Dark => settings.set_gtk_application_prefer_dark_theme(true),
-                    Theme::Light => {
-                        let theme_name = settings.gtk_theme_name().map(|t| t.as_str().to_owned());
-                        if let Some(theme) = theme_name {
-                            // Remove dark variant.
-                            if let Some(theme) = GTK_THEME_SUFFIX_LIST
-                                .iter()
-                                .find(|t| theme.ends_with(*t))
-                                .map(|v| theme.strip_suffix(v))
-                            {
-                                settings.set_gtk_theme_name(theme);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        self.system_theme.apply(theme);
     }
 
     #[inline]
     pub fn theme(&self) -> Option<Theme> {
-        if let Some(settings) = Settings::default() {
-            let theme_name = settings.gtk_theme_name().map(|s| s.as_str().to_owned());
-            if let Some(theme) = theme_name {
-                if GTK_THEME_SUFFIX_LIST.iter().any(|t| theme.ends_with(t)) {
-                    return Some(Theme::Dark);
-                }
-            }
-        }
-        Some(Theme::Light)
+        Some(current_theme())
     }
 
     #[inline]
     pub fn has_focus(&self) -> bool {
-        self.window.is_active()
+        !self.is_destroyed() && self.window.is_active()
     }
 
     pub fn title(&self) -> String {
@@ -842,7 +1404,39 @@ impl Window {
             .unwrap_or_default()
     }
 
+    pub fn resize_to_content(&self) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::ResizeToContent))
+        {
+            log::warn!("Fail to send resize to content request: {}", e);
+        }
+    }
+
+    pub fn beep(&self) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::Beep))
+        {
+            log::warn!("Fail to send beep request: {}", e);
+        }
+    }
+
+    pub fn close(&self) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::Close))
+        {
+            log::warn!("Fail to send close request: {}", e);
+        }
+    }
+
+    pub fn set_hide_on_close(&self, hide: bool) {
+        self.hide_on_close.store(hide, Ordering::Release);
+    }
+
     pub fn set_skip_taskbar(&self, skip: bool) {
+        self.skip_taskbar.set(skip);
         if let Err(e) = self
             .window_requests_tx
             .send((self.window_id, WindowRequest::SetSkipTaskbar(skip)))
@@ -850,6 +1444,34 @@ impl Window {
             log::warn!("Fail to send skip taskbar request: {}", e);
         }
     }
+
+    pub fn is_skip_taskbar(&self) -> bool {
+        self.skip_taskbar.get()
+    }
+
+    pub fn set_modal(&self, modal: bool) {
+        if let Err(e) = self
+            .window_requests_tx
+            .send((self.window_id, WindowRequest::Modal(modal)))
+        {
+            log::warn!("Fail to send modal request: {}", e);
+        }
+    }
+}
+
+impl From<ResizeDirection> for WindowEdge {
+    fn from(direction: ResizeDirection) -> Self {
+        match direction {
+            ResizeDirection::East => WindowEdge::East,
+            ResizeDirection::North => WindowEdge::North,
+            ResizeDirection::NorthEast => WindowEdge::NorthEast,
+            ResizeDirection::NorthWest => WindowEdge::NorthWest,
+            ResizeDirection::South => WindowEdge::South,
+            ResizeDirection::SouthEast => WindowEdge::SouthEast,
+            ResizeDirection::SouthWest => WindowEdge::SouthWest,
+            ResizeDirection::West => WindowEdge::West,
+        }
+    }
 }
 
 // We need to keep GTK window which isn't thread safe.
@@ -857,14 +1479,107 @@ impl Window {
 unsafe impl Send for Window {}
 unsafe impl Sync for Window {}
 
+impl Drop for Window {
+    fn drop(&mut self) {
+        // Dropping the `Window` closes it, like on the other backends. The event loop may be
+        // gone already, then so is the window.
+        if !self.is_destroyed() {
+            let _ = self
+                .window_requests_tx
+                .send((self.window_id, WindowRequest::Close));
+        }
+    }
+}
+
 /// A constant used to determine how much inside the window, the resize handler should appear (only used in Linux(gtk) and Windows).
 /// You probably need to scale it by the scale_factor of the window.
 pub const BORDERLESS_RESIZE_INSET: i32 = 5;
 
+/// Sets the `WM_CLASS` of the window on X11, or the app id on Wayland.
+///
+/// This has to happen before the window is realized on X11. On Wayland GTK sets the app id of
+/// the application when the window is mapped, it is replaced right after.
+fn set_application_name(window: &gtk::ApplicationWindow, name: &ApplicationName, is_wayland: bool) {
+    if is_wayland {
+        let app_id = name.general.clone();
+        window.connect_map_event(move |window, _| {
+            if let Some(gdk_window) = window.window() {
+                unsafe {
+                    gdk_wayland_sys::gdk_wayland_window_set_application_id(
+                        gdk_window.as_ptr() as *mut _,
+                        app_id.to_glib_none().0,
+                    );
+                }
+            }
+            glib::Propagation::Proceed
+        });
+    } else {
+        unsafe {
+            gtk::ffi::gtk_window_set_wmclass(
+                window.upcast_ref::<gtk::Window>().to_glib_none().0,
+                name.instance.to_glib_none().0,
+                name.general.to_glib_none().0,
+            );
+        }
+    }
+}
+
+/// Returns the keep above and keep below hints of a window level.
+fn keep_above_below(level: WindowLevel) -> (bool, bool) {
+    match level {
+        WindowLevel::AlwaysOnTop => (true, false),
+        WindowLevel::Normal => (false, false),
+        WindowLevel::AlwaysOnBottom => (false, true),
+    }
+}
+
+/// Switching video modes needs XRandR, so exclusive fullscreen with a video mode of another
+/// backend is borderless fullscreen on the monitor of the video mode.
+fn borderless(fullscreen: Fullscreen) -> Fullscreen {
+    match fullscreen {
+        Fullscreen::Exclusive(video_mode) if video_mode.xrandr_mode.is_none() => {
+            log::warn!(
+                "Exclusive fullscreen is only supported on X11, using borderless fullscreen on the monitor of the video mode"
+            );
+            Fullscreen::Borderless(Some(MonitorHandle::new(video_mode.monitor)))
+        }
+        fullscreen => fullscreen,
+    }
+}
+
+/// Whether the window is composited right now.
+fn is_composited(window: &gtk::ApplicationWindow, compositor_bypass: &AtomicBool) -> bool {
+    let is_screen_composited =
+        GtkWindowExt::screen(window).is_some_and(|screen| screen.is_composited());
+    // Compositors only unredirect fullscreen windows which ask for it.
+    let is_unredirected = compositor_bypass.load(Ordering::Acquire)
+        && window
+            .window()
+            .is_some_and(|w| w.state().contains(WindowState::FULLSCREEN));
+    is_screen_composited && !is_unredirected
+}
+
+/// Returns the edge of `window` under the point at the root coordinates `cx` and `cy`.
 pub fn hit_test(window: &gdk::Window, cx: f64, cy: f64) -> WindowEdge {
     let (left, top) = window.position();
     let (w, h) = (window.width(), window.height());
-    let (right, bottom) = (left + w, top + h);
+    let inset = BORDERLESS_RESIZE_INSET * window.scale_factor();
+    edge_at((left, top, left + w, top + h), inset, cx, cy)
+}
+
+/// Returns the edge of `window` under a point relative to the window.
+///
+/// Unlike [`hit_test`], this doesn't need the position of the window, which Wayland keeps from
+/// clients.
+pub(crate) fn hit_test_relative(window: &gdk::Window, x: f64, y: f64) -> WindowEdge {
+    let inset = BORDERLESS_RESIZE_INSET * window.scale_factor();
+    edge_at((0, 0, window.width(), window.height()), inset, x, y)
+}
+
+/// Returns the edge of the `(left, top, right, bottom)` bounds which is within `inset` of the
+/// point, a point exactly `inset` away from the left or top edge is already inside the window.
+fn edge_at(bounds: (i32, i32, i32, i32), inset: i32, cx: f64, cy: f64) -> WindowEdge {
+    let (left, top, right, bottom) = bounds;
     let (cx, cy) = (cx as i32, cy as i32);
 
     const LEFT: i32 = 0b0001;
@@ -876,7 +1591,6 @@ pub fn hit_test(window: &gdk::Window, cx: f64, cy: f64) -> WindowEdge {
     const BOTTOMLEFT: i32 = BOTTOM | LEFT;
     const BOTTOMRIGHT: i32 = BOTTOM | RIGHT;
 
-    let inset = BORDERLESS_RESIZE_INSET * window.scale_factor();
     #[rustfmt::skip]
   let result =
       (LEFT * (if cx < (left + inset) { 1 } else { 0 }))
@@ -898,3 +1612,64 @@ pub fn hit_test(window: &gdk::Window, cx: f64, cy: f64) -> WindowEdge {
         _ => WindowEdge::__Unknown(8),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_test_inset_boundary() {
+        let bounds = (0, 0, 100, 100);
+        let inset = BORDERLESS_RESIZE_INSET;
+        assert_eq!(
+            edge_at(bounds, inset, (inset - 1) as f64, 50.),
+            WindowEdge::West
+        );
+        assert_eq!(
+            edge_at(bounds, inset, inset as f64, 50.),
+            WindowEdge::__Unknown(8)
+        );
+        assert_eq!(
+            edge_at(bounds, inset, (100 - inset) as f64, 50.),
+            WindowEdge::East
+        );
+        assert_eq!(
+            edge_at(bounds, inset, (100 - inset - 1) as f64, 50.),
+            WindowEdge::__Unknown(8)
+        );
+        assert_eq!(edge_at(bounds, inset, 0., 0.), WindowEdge::NorthWest);
+        assert_eq!(
+            edge_at(bounds, inset, 99., (100 - inset) as f64),
+            WindowEdge::SouthEast
+        );
+    }
+
+    #[test]
+    fn hit_test_relative_to_a_scaled_window() {
+        // A 400x300 window at a scale factor of 2, with its inset scaled alike.
+        let bounds = (0, 0, 400, 300);
+        let inset = BORDERLESS_RESIZE_INSET * 2;
+        assert_eq!(edge_at(bounds, inset, 9., 150.), WindowEdge::West);
+        assert_eq!(edge_at(bounds, inset, 10., 150.), WindowEdge::__Unknown(8));
+        assert_eq!(edge_at(bounds, inset, 395., 295.), WindowEdge::SouthEast);
+        assert_eq!(edge_at(bounds, inset, 200., 0.), WindowEdge::North);
+    }
+
+    #[test]
+    fn hidden_cursor_keeps_its_icon() {
+        let mut state = CursorState::default();
+        assert_eq!(state.cursor(), Some(CursorIcon::Default));
+        state.visible = false;
+        state.icon = CursorIcon::Crosshair;
+        assert_eq!(state.cursor(), None);
+        state.visible = true;
+        assert_eq!(state.cursor(), Some(CursorIcon::Crosshair));
+    }
+
+    #[test]
+    fn window_levels_set_one_hint_at_most() {
+        assert_eq!(keep_above_below(WindowLevel::AlwaysOnTop), (true, false));
+        assert_eq!(keep_above_below(WindowLevel::Normal), (false, false));
+        assert_eq!(keep_above_below(WindowLevel::AlwaysOnBottom), (false, true));
+    }
+}
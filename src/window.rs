@@ -138,6 +138,7 @@ pub struct WindowAttributes {
     pub resize_increments: Option<Size>,
     pub content_protected: bool,
     pub window_level: WindowLevel,
+    pub visible_on_all_workspaces: bool,
     pub parent_window: Option<RawWindowHandle>,
     pub active: bool,
 }
@@ -159,6 +160,7 @@ impl Default for WindowAttributes {
             transparent: false,
             decorations: true,
             window_level: Default::default(),
+            visible_on_all_workspaces: false,
             window_icon: None,
             preferred_theme: None,
             resize_increments: None,
@@ -403,7 +405,8 @@ impl WindowBuilder {
     ///
     /// - **macOS**: if `false`, [`NSWindowSharingNone`] is used but doesn't completely
     /// prevent all apps from reading the window content, for instance, QuickTime.
-    /// - **iOS / Android / Web / x11 / Orbital:** Ignored.
+    /// - **X11 / Wayland:** Ignored, no protocol supports it. A warning is logged.
+    /// - **iOS / Android / Web / Orbital:** Ignored.
     ///
     /// [`NSWindowSharingNone`]: https://developer.apple.com/documentation/appkit/nswindowsharingtype/nswindowsharingnone
     #[inline]
@@ -412,6 +415,17 @@ impl WindowBuilder {
         self
     }
 
+    /// Sets whether the window is shown on all workspaces, like a pinned utility window.
+    ///
+    /// The default is `false`.
+    ///
+    /// See [`Window::set_visible_on_all_workspaces`] for details.
+    #[inline]
+    pub fn with_visible_on_all_workspaces(mut self, visible: bool) -> Self {
+        self.window.visible_on_all_workspaces = visible;
+        self
+    }
+
     /// Whether the window will be initially focused or not.
     ///
     /// The window should be assumed as not focused by default
@@ -726,7 +740,7 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **iOS / Android / Web / Wayland / Windows / Orbital:** Always returns [`None`].
+    /// - **iOS / Android / Web / Windows / Orbital:** Always returns [`None`].
     #[inline]
     pub fn resize_increments(&self) -> Option<PhysicalSize<u32>> {
         self.window.resize_increments()
@@ -772,7 +786,10 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **Windows / X11 / Web / iOS / Android / Orbital:** Unsupported.
+    /// - **X11 / Wayland:** The RGBA visual is only switched before the window is first shown, since
+    ///   changing it would replace the window and its raw handle. Afterwards only the clearing of
+    ///   the background changes.
+    /// - **Windows / Web / iOS / Android / Orbital:** Unsupported.
     #[inline]
     pub fn set_transparent(&self, transparent: bool) {
         self.window.set_transparent(transparent)
@@ -838,7 +855,8 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **Wayland / X11 / Orbital:** Not implemented.
+    /// - **Wayland:** Only [`WindowButtons::CLOSE`] has an effect.
+    /// - **Orbital:** Not implemented.
     /// - **Web / iOS / Android:** Unsupported.
     pub fn set_enabled_buttons(&self, buttons: WindowButtons) {
         self.window.set_enabled_buttons(buttons)
@@ -848,7 +866,7 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **Wayland / X11 / Orbital:** Not implemented. Always returns [`WindowButtons::all`].
+    /// - **Orbital:** Not implemented. Always returns [`WindowButtons::all`].
     /// - **Web / iOS / Android:** Unsupported. Always returns [`WindowButtons::all`].
     pub fn enabled_buttons(&self) -> WindowButtons {
         self.window.enabled_buttons()
@@ -919,7 +937,10 @@ impl Window {
     ///
     ///   The dock and the menu bar are disabled in exclusive fullscreen mode.
     /// - **iOS:** Can only be called on the main thread.
-    /// - **Wayland:** Does not support exclusive fullscreen mode and will no-op a request.
+    /// - **X11:** [`Fullscreen::Exclusive`] switches the video mode through XRandR, the previous
+    ///   one is restored when the window leaves fullscreen or is destroyed.
+    /// - **Wayland:** [`Fullscreen::Exclusive`] doesn't change the video mode, the window is made
+    ///   borderless fullscreen on the monitor of the video mode instead.
     /// - **Windows:** Screen saver is disabled in fullscreen mode.
     /// - **Android / Orbital:** Unsupported.
     #[inline]
@@ -934,6 +955,9 @@ impl Window {
     /// - **iOS:** Can only be called on the main thread.
     /// - **Android / Orbital:** Will always return `None`.
     /// - **Wayland:** Can return `Borderless(None)` when there are no monitors.
+    /// - **Wayland:** Never returns [`Fullscreen::Exclusive`], see [`Window::set_fullscreen`].
+    /// - **X11 / Wayland:** Follows the window manager entering or leaving fullscreen on its own,
+    ///   with the monitor the window ended up on.
     #[inline]
     pub fn fullscreen(&self) -> Option<Fullscreen> {
         self.window.fullscreen().map(|f| f.into())
@@ -1058,7 +1082,7 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **iOS / Android / Web / Windows / X11 / macOS / Orbital:** Unsupported.
+    /// - **iOS / Android / Web / Windows / macOS / Orbital:** Unsupported.
     #[inline]
     pub fn set_ime_purpose(&self, purpose: ImePurpose) {
         self.window.set_ime_purpose(purpose);
@@ -1073,7 +1097,11 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **iOS / Android / Web / Wayland / Orbital:** Unsupported.
+    /// - **iOS / Android / Web / Orbital:** Unsupported.
+    /// - **Wayland:** The compositor may only mark the window as demanding attention.
+    /// - **X11 / Wayland:** Minimized and hidden windows are restored and shown first. A window
+    ///   built with [`WindowBuilder::with_active`]`(false)` is only raised, see
+    ///   [`WindowExtUnix::is_focusable`](crate::platform::unix::WindowExtUnix::is_focusable).
     #[inline]
     pub fn focus_window(&self) {
         self.window.focus_window()
@@ -1112,10 +1140,9 @@ impl Window {
     /// ## Platform-specific
     ///
     /// - **macOS:** This is an app-wide setting.
-    /// - **Wayland:** You can also use `WINIT_WAYLAND_CSD_THEME` env variable to set the theme.
-    /// Possible values for env variable are: "dark" and light". When unspecified, a theme is automatically selected.
-    /// -**x11:** Sets `_GTK_THEME_VARIANT` hint to `dark` or `light` and if `None` is used, it will default to  [`Theme::Dark`].
-    /// - **iOS / Android / Web / x11 / Orbital:** Unsupported.
+    /// - **X11 / Wayland:** This is an app-wide setting, it changes the GTK settings. `None` restores
+    ///   the theme of the system.
+    /// - **iOS / Android / Web / Orbital:** Unsupported.
     #[inline]
     pub fn set_theme(&self, theme: Option<Theme>) {
         self.window.set_theme(theme)
@@ -1125,8 +1152,8 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **macOS:** This is an app-wide setting.
-    /// - **iOS / Android / Wayland / x11 / Orbital:** Unsupported.
+    /// - **macOS / X11 / Wayland:** This is an app-wide setting.
+    /// - **iOS / Android / Orbital:** Unsupported.
     #[inline]
     pub fn theme(&self) -> Option<Theme> {
         self.window.theme()
@@ -1138,14 +1165,27 @@ impl Window {
     ///
     /// - **macOS**: if `false`, [`NSWindowSharingNone`] is used but doesn't completely
     /// prevent all apps from reading the window content, for instance, QuickTime.
-    /// - **iOS / Android / x11 / Wayland / Web / Orbital:** Unsupported.
+    /// - **X11 / Wayland:** Unsupported, no protocol supports it. A warning is logged.
+    /// - **iOS / Android / Web / Orbital:** Unsupported.
     ///
     /// [`NSWindowSharingNone`]: https://developer.apple.com/documentation/appkit/nswindowsharingtype/nswindowsharingnone
     pub fn set_content_protected(&self, _protected: bool) {
-        #[cfg(any(macos_platform, windows_platform))]
+        #[cfg(any(macos_platform, windows_platform, x11_platform, wayland_platform))]
         self.window.set_content_protected(_protected);
     }
 
+    /// Sets whether the window is shown on all workspaces, or only on the current one.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **X11 / Wayland:** The window manager may ignore it, Wayland has no protocol for it.
+    /// - **Windows / macOS / iOS / Android / Web / Orbital:** Unsupported.
+    #[inline]
+    pub fn set_visible_on_all_workspaces(&self, _visible: bool) {
+        #[cfg(any(x11_platform, wayland_platform))]
+        self.window.set_visible_on_all_workspaces(_visible);
+    }
+
     /// Gets the current title of the window.
     ///
     /// ## Platform-specific
@@ -1238,6 +1278,7 @@ impl Window {
     ///
     /// - **X11:** Un-grabs the cursor.
     /// - **Wayland:** Requires the cursor to be inside the window to be dragged.
+    /// - **X11 / Wayland:** Returns an [`ExternalError::Os`] if the seat has no pointer.
     /// - **macOS:** May prevent the button release event to be triggered.
     /// - **iOS / Android / Web / Orbital:** Always returns an [`ExternalError::NotSupported`].
     #[inline]
@@ -1252,7 +1293,8 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// Only X11 is supported at this time.
+    /// Only X11 and Wayland are supported at this time. They return an [`ExternalError::Os`] if
+    /// the seat has no pointer.
     #[inline]
     pub fn drag_resize_window(&self, direction: ResizeDirection) -> Result<(), ExternalError> {
         self.window.drag_resize_window(direction)
@@ -1265,7 +1307,7 @@ impl Window {
     ///
     /// ## Platform-specific
     ///
-    /// - **iOS / Android / Web / X11 / Orbital:** Always returns an [`ExternalError::NotSupported`].
+    /// - **iOS / Android / Web / Orbital:** Always returns an [`ExternalError::NotSupported`].
     #[inline]
     pub fn set_cursor_hittest(&self, hittest: bool) -> Result<(), ExternalError> {
         self.window.set_cursor_hittest(hittest)
@@ -1382,8 +1424,9 @@ pub enum CursorGrabMode {
     ///
     /// ## Platform-specific
     ///
-    /// - **X11 / Windows:** Not implemented. Always returns [`ExternalError::NotSupported`] for now.
-    /// - **iOS / Android / Orbital:** Always returns an [`ExternalError::NotSupported`].
+    /// - **X11:** Emulated by warping the cursor back to the center of the window.
+    /// - **Windows:** Not implemented. Always returns [`ExternalError::NotSupported`] for now.
+    /// - **Wayland / iOS / Android / Orbital:** Always returns an [`ExternalError::NotSupported`].
     Locked,
 }
 
@@ -0,0 +1,628 @@
+//! Tests of windows on a real display.
+//!
+//! GTK has to run on the main thread, so these run without the libtest harness, one after the
+//! other. They are skipped when no display can be opened.
+
+#[cfg(any(x11_platform, wayland_platform))]
+mod display {
+    use std::{
+        cell::Cell,
+        ffi::CStr,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use gdk::prelude::{DisplayExtManual, SeatExt};
+    use glib::{
+        translate::{ToGlibPtr, ToGlibPtrMut},
+        ObjectType,
+    };
+    use gtk::traits::{GtkWindowExt, WidgetExt};
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+    use winit::{
+        dpi::{LogicalPosition, LogicalSize, PhysicalSize},
+        event::{ElementState, Event, WindowEvent},
+        event_loop::EventLoop,
+        platform::{
+            run_return::EventLoopExtRunReturn,
+            unix::{
+                DefaultHandlers, EventLoopWindowTargetExtUnix, WindowBuilderExtUnix, WindowExtUnix,
+            },
+        },
+        window::{Fullscreen, Icon, UserAttentionType, Window, WindowBuilder, WindowButtons},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Runs `event_loop` until `done` returns `true` for an event, panicking after [`TIMEOUT`].
+    fn run_until(event_loop: &mut EventLoop<()>, mut done: impl FnMut(&Event<'_, ()>) -> bool) {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut finished = false;
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_wait_until(deadline);
+            if done(&event) {
+                finished = true;
+                control_flow.set_exit();
+            } else if Instant::now() >= deadline {
+                control_flow.set_exit();
+            }
+        });
+        assert!(finished, "timed out waiting for the event");
+    }
+
+    /// Runs `event_loop` for `duration`, returning the window events it delivered.
+    fn run_for(event_loop: &mut EventLoop<()>, duration: Duration) -> Vec<WindowEvent<'static>> {
+        let deadline = Instant::now() + duration;
+        let mut events = Vec::new();
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_wait_until(deadline);
+            if let Event::WindowEvent { event, .. } = event {
+                events.extend(event.to_static());
+            }
+            if Instant::now() >= deadline {
+                control_flow.set_exit();
+            }
+        });
+        events
+    }
+
+    /// Whether a window manager handles the window states, Wayland compositors always do.
+    fn has_window_manager() -> bool {
+        let display = gdk::Display::default().unwrap();
+        if !display.backend().is_x11() {
+            return true;
+        }
+        let name = unsafe {
+            CStr::from_ptr(gdk_x11_sys::gdk_x11_screen_get_window_manager_name(
+                display.default_screen().as_ptr() as *mut _,
+            ))
+        };
+        name.to_bytes() != b"unknown"
+    }
+
+    /// Whether the window manager made `window` fullscreen.
+    fn is_fullscreen(window: &Window) -> bool {
+        window.gtk_window().window().map_or(false, |gdk_window| {
+            gdk_window.state().contains(gdk::WindowState::FULLSCREEN)
+        })
+    }
+
+    /// Delivers a press of the left button at `(x, y)` of `window`, like the display server would.
+    fn press_left_button(window: &Window, (x, y): (f64, f64)) {
+        let gdk_window = window.gtk_window().window().unwrap();
+        let (root_x, root_y) = gdk_window.root_coords(x as i32, y as i32);
+        press_left_button_in(window, &gdk_window, (x, y), (root_x as f64, root_y as f64));
+    }
+
+    /// Delivers a press of the left button to `window`, at `position` of `event_window` and at
+    /// `root` of the screen.
+    fn press_left_button_in(
+        window: &Window,
+        event_window: &gdk::Window,
+        (x, y): (f64, f64),
+        (root_x, root_y): (f64, f64),
+    ) {
+        let mut event = gdk::Event::new(gdk::EventType::ButtonPress);
+        event.set_device(pointer(event_window).as_ref());
+        unsafe {
+            let event: *mut gdk::ffi::GdkEvent = event.to_glib_none_mut().0;
+            let button = &mut *(event as *mut gdk::ffi::GdkEventButton);
+            button.window = event_window.to_glib_full();
+            button.send_event = 1;
+            button.x = x;
+            button.y = y;
+            button.x_root = root_x;
+            button.y_root = root_y;
+            button.button = 1;
+        }
+        window.gtk_window().event(&event);
+    }
+
+    /// Delivers a motion of the pointer to `window`, see [`press_left_button_in`].
+    fn move_pointer_in(
+        window: &Window,
+        event_window: &gdk::Window,
+        (x, y): (f64, f64),
+        (root_x, root_y): (f64, f64),
+    ) {
+        let mut event = gdk::Event::new(gdk::EventType::MotionNotify);
+        event.set_device(pointer(event_window).as_ref());
+        unsafe {
+            let event: *mut gdk::ffi::GdkEvent = event.to_glib_none_mut().0;
+            let motion = &mut *(event as *mut gdk::ffi::GdkEventMotion);
+            motion.window = event_window.to_glib_full();
+            motion.send_event = 1;
+            motion.x = x;
+            motion.y = y;
+            motion.x_root = root_x;
+            motion.y_root = root_y;
+        }
+        window.gtk_window().event(&event);
+    }
+
+    fn pointer(gdk_window: &gdk::Window) -> Option<gdk::Device> {
+        gdk_window
+            .display()
+            .default_seat()
+            .and_then(|seat| seat.pointer())
+    }
+
+    /// Creates a child `GdkWindow` at `(x, y)` of `parent`, like the one of a widget.
+    fn child_window(parent: &gdk::Window, x: i32, y: i32) -> gdk::Window {
+        let attributes = gdk::WindowAttr {
+            x: Some(x),
+            y: Some(y),
+            width: 100,
+            height: 100,
+            window_type: gdk::WindowType::Child,
+            ..Default::default()
+        };
+        gdk::Window::new(Some(parent), &attributes)
+    }
+
+    fn is_window_event(event: &Event<'_, ()>, expected: fn(&WindowEvent<'_>) -> bool) -> bool {
+        matches!(event, Event::WindowEvent { event, .. } if expected(event))
+    }
+
+    fn destroyed_window_is_a_husk() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+        assert_eq!(window.is_visible(), Some(true));
+        assert!(window.outer_position().is_ok());
+        assert_ne!(window.inner_size(), PhysicalSize::new(0, 0));
+
+        window.close();
+        run_until(&mut event_loop, |event| {
+            is_window_event(event, |event| matches!(event, WindowEvent::Destroyed))
+        });
+        assert_eq!(window.is_visible(), Some(false));
+        assert!(window.outer_position().is_err());
+        assert!(window.inner_position().is_err());
+        assert_eq!(window.inner_size(), PhysicalSize::new(0, 0));
+        assert_eq!(window.outer_size(), PhysicalSize::new(0, 0));
+        assert!(!window.is_maximized());
+        assert_eq!(window.is_minimized(), Some(false));
+        assert!(window.fullscreen().is_none());
+        assert!(window.current_monitor().is_none());
+        assert!(!window.has_focus());
+        match window.raw_window_handle() {
+            RawWindowHandle::Xlib(handle) => assert_eq!(handle.window, 0),
+            RawWindowHandle::Wayland(handle) => assert!(handle.surface.is_null()),
+            handle => panic!("unexpected handle {handle:?}"),
+        }
+        // Refused with a warning instead of reaching the event loop.
+        window.set_title("Destroyed");
+    }
+
+    fn dropped_window_is_destroyed() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        let id = window.id();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        drop(window);
+        run_until(&mut event_loop, |event| {
+            matches!(
+                event,
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Destroyed,
+                } if *window_id == id
+            )
+        });
+    }
+
+    fn close_request_wakes_a_waiting_loop() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        // A timer keeps GTK busy while the app sleeps in `Wait`, then the window is closed like
+        // the window manager would.
+        let busy =
+            glib::timeout_add_local(Duration::from_millis(10), || glib::ControlFlow::Continue);
+        let closed_at = Rc::new(Cell::new(None));
+        let closed_at_clone = closed_at.clone();
+        let gtk_window = window.gtk_window().clone();
+        glib::timeout_add_local_once(Duration::from_millis(100), move || {
+            closed_at_clone.set(Some(Instant::now()));
+            gtk_window.close();
+        });
+        // Wakes the loop up if `CloseRequested` never arrives.
+        let proxy = event_loop.create_proxy();
+        let timeout = glib::timeout_add_local_once(TIMEOUT, move || {
+            let _ = proxy.send_event(());
+        });
+
+        let mut latency = None;
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_wait();
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    latency = closed_at.get().map(|closed_at| closed_at.elapsed());
+                    control_flow.set_exit();
+                }
+                Event::UserEvent(()) => control_flow.set_exit(),
+                _ => (),
+            }
+        });
+        busy.remove();
+        let latency = latency.expect("CloseRequested never arrived");
+        timeout.remove();
+        assert!(
+            latency < Duration::from_millis(50),
+            "CloseRequested arrived after {latency:?}"
+        );
+    }
+
+    fn startup_reports_the_geometry_once() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        let events = run_for(&mut event_loop, Duration::from_millis(500));
+
+        let resized: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WindowEvent::Resized(size) => Some(*size),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(resized.last(), Some(&window.inner_size()));
+        assert!(
+            resized.windows(2).all(|pair| pair[0] != pair[1]),
+            "the same size was reported twice: {resized:?}"
+        );
+        let moved: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WindowEvent::Moved(position) => Some(*position),
+                _ => None,
+            })
+            .collect();
+        assert!(
+            moved.windows(2).all(|pair| pair[0] != pair[1]),
+            "the same position was reported twice: {moved:?}"
+        );
+    }
+
+    fn fullscreen_of_a_hidden_window_applies_once_shown() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)
+            .unwrap();
+        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        run_for(&mut event_loop, Duration::from_millis(100));
+        assert_eq!(window.is_visible(), Some(false));
+        assert!(!is_fullscreen(&window));
+
+        window.set_visible(true);
+        run_until(&mut event_loop, |_| is_fullscreen(&window));
+        assert!(window.fullscreen().is_some());
+    }
+
+    fn tearing_hint_is_applied_once_shown() {
+        let mut event_loop = EventLoop::new();
+        let supported = event_loop.wayland_capabilities().tearing_control;
+        let window = WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)
+            .unwrap();
+        assert_eq!(window.set_allow_tearing(true).is_ok(), supported);
+        run_for(&mut event_loop, Duration::from_millis(100));
+        assert!(!window.allows_tearing());
+
+        window.set_visible(true);
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+        assert_eq!(window.allows_tearing(), supported);
+
+        assert_eq!(window.set_allow_tearing(false).is_ok(), supported);
+        run_for(&mut event_loop, Duration::from_millis(100));
+        assert!(!window.allows_tearing());
+    }
+
+    fn window_hidden_on_close_keeps_its_state() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(300, 200))
+            .build(&event_loop)
+            .unwrap();
+        window.set_hide_on_close(true);
+        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        run_until(&mut event_loop, |_| is_fullscreen(&window));
+        let size = window.inner_size();
+
+        // What the close button does.
+        window.gtk_window().close();
+        run_for(&mut event_loop, Duration::from_millis(100));
+        assert_eq!(window.is_visible(), Some(false));
+        assert_eq!(window.inner_size(), size);
+        assert!(window.fullscreen().is_some());
+
+        window.set_visible(true);
+        run_until(&mut event_loop, |_| is_fullscreen(&window));
+        assert_eq!(window.inner_size(), size);
+    }
+
+    fn edge_press_reaches_the_app_without_borderless_resize() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_decorations(false)
+            .with_default_handlers(DefaultHandlers::all() - DefaultHandlers::BORDERLESS_RESIZE)
+            .build(&event_loop)
+            .unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        press_left_button(&window, (1.0, 1.0));
+        run_until(&mut event_loop, |event| {
+            is_window_event(event, |event| {
+                matches!(
+                    event,
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        ..
+                    }
+                )
+            })
+        });
+    }
+
+    fn event_coordinates_of_a_scaled_window() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_decorations(false)
+            .with_inner_size(LogicalSize::new(200.0, 150.0))
+            .build(&event_loop)
+            .unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+        let scale_factor = window.scale_factor();
+        let toplevel = window.gtk_window().window().unwrap();
+        // A child widget inside another one, at (35, 30) of the toplevel.
+        let inner = child_window(&child_window(&toplevel, 30, 20), 5, 10);
+        let popup = gdk::Window::new(
+            None,
+            &gdk::WindowAttr {
+                width: 10,
+                height: 10,
+                window_type: gdk::WindowType::Temp,
+                ..Default::default()
+            },
+        );
+        let (_, left, top) = toplevel.origin();
+        let root = |x, y| (left as f64 + x, top as f64 + y);
+        let cursor_moved_to = |event_loop: &mut EventLoop<()>, x, y| {
+            let expected = LogicalPosition::new(x, y).to_physical::<f64>(scale_factor);
+            run_until(event_loop, |event| {
+                matches!(
+                    event,
+                    Event::WindowEvent {
+                        event: WindowEvent::CursorMoved { position, .. },
+                        ..
+                    } if *position == expected
+                )
+            });
+        };
+
+        // Positions of the toplevel and its children are logical, the event loop scales them.
+        move_pointer_in(&window, &toplevel, (7.0, 8.0), root(7.0, 8.0));
+        cursor_moved_to(&mut event_loop, 7.0, 8.0);
+        move_pointer_in(&window, &inner, (7.0, 8.0), root(42.0, 38.0));
+        cursor_moved_to(&mut event_loop, 42.0, 38.0);
+        // Other windows are placed from the root coordinates.
+        move_pointer_in(&window, &popup, (1.0, 1.0), root(12.5, 6.5));
+        cursor_moved_to(&mut event_loop, 12.5, 6.5);
+
+        // The borderless resize inset of 5 logical pixels is scaled too. Presses on it are
+        // swallowed, the ones just inside reach the app.
+        let inset = 5.0 * scale_factor;
+        let is_press = |event: &Event<'_, ()>| {
+            is_window_event(event, |event| {
+                matches!(
+                    event,
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        ..
+                    }
+                )
+            })
+        };
+        press_left_button_in(
+            &window,
+            &inner,
+            (inset - 36.0, 8.0),
+            root(inset - 1.0, 38.0),
+        );
+        assert!(!run_for(&mut event_loop, Duration::from_millis(100))
+            .iter()
+            .any(|event| matches!(event, WindowEvent::MouseInput { .. })));
+        press_left_button_in(&window, &inner, (inset - 35.0, 8.0), root(inset, 38.0));
+        run_until(&mut event_loop, is_press);
+    }
+
+    fn ime_is_not_enabled_without_keyboard_handlers() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_default_handlers(DefaultHandlers::all() - DefaultHandlers::KEYBOARD)
+            .build(&event_loop)
+            .unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        window.set_ime_allowed(true);
+        let events = run_for(&mut event_loop, Duration::from_millis(100));
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, WindowEvent::Ime(_))));
+    }
+
+    fn timed_user_attention_is_cleared() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        window.request_user_attention_for(
+            Duration::from_millis(100),
+            UserAttentionType::Informational,
+        );
+        run_until(&mut event_loop, |_| window.gtk_window().is_urgency_hint());
+
+        run_for(&mut event_loop, Duration::from_millis(200));
+        assert!(!window.gtk_window().is_urgency_hint());
+    }
+
+    fn beep_works_with_and_without_a_surface() {
+        let mut event_loop = EventLoop::new();
+        let shown = WindowBuilder::new().build(&event_loop).unwrap();
+        let hidden = WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)
+            .unwrap();
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        // Beeping may be a no-op on Wayland, but must not fail there.
+        shown.beep();
+        hidden.beep();
+        run_for(&mut event_loop, Duration::from_millis(100));
+        shown.close();
+        shown.beep();
+        run_for(&mut event_loop, Duration::from_millis(100));
+    }
+
+    fn setters_before_run_are_applied() {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        window.set_title("Early");
+        window.set_inner_size(LogicalSize::new(320, 240));
+        window.set_resizable(false);
+        window.set_enabled_buttons(WindowButtons::MINIMIZE);
+        window.set_decorations(false);
+        window.set_window_icon(Some(
+            Icon::from_rgba(vec![255; 16 * 16 * 4], 16, 16).unwrap(),
+        ));
+        window.set_transparent(true);
+        window.request_user_attention(Some(UserAttentionType::Critical));
+        window.set_skip_taskbar(true);
+        window.set_modal(true);
+        run_until(&mut event_loop, |event| {
+            matches!(event, Event::RedrawRequested(_))
+        });
+
+        let gtk_window = window.gtk_window();
+        assert_eq!(gtk_window.title().as_deref(), Some("Early"));
+        assert_eq!(gtk_window.size(), (320, 240));
+        assert!(!gtk_window.is_resizable());
+        assert!(!gtk_window.is_deletable());
+        assert!(!gtk_window.is_decorated());
+        assert!(gtk_window.icon().is_some());
+        assert!(gtk_window.is_app_paintable());
+        assert!(gtk_window.is_urgency_hint());
+        assert!(gtk_window.skips_taskbar_hint());
+        assert!(gtk_window.is_modal());
+    }
+
+    pub fn run() {
+        if gtk::init().is_err() {
+            println!("No display available, skipping the window tests");
+            return;
+        }
+        // The tests and whether they need a window manager.
+        let tests: &[(&str, fn(), bool)] = &[
+            (
+                "destroyed_window_is_a_husk",
+                destroyed_window_is_a_husk,
+                false,
+            ),
+            (
+                "dropped_window_is_destroyed",
+                dropped_window_is_destroyed,
+                false,
+            ),
+            (
+                "close_request_wakes_a_waiting_loop",
+                close_request_wakes_a_waiting_loop,
+                false,
+            ),
+            (
+                "startup_reports_the_geometry_once",
+                startup_reports_the_geometry_once,
+                false,
+            ),
+            (
+                "fullscreen_of_a_hidden_window_applies_once_shown",
+                fullscreen_of_a_hidden_window_applies_once_shown,
+                true,
+            ),
+            (
+                "tearing_hint_is_applied_once_shown",
+                tearing_hint_is_applied_once_shown,
+                false,
+            ),
+            (
+                "window_hidden_on_close_keeps_its_state",
+                window_hidden_on_close_keeps_its_state,
+                true,
+            ),
+            (
+                "edge_press_reaches_the_app_without_borderless_resize",
+                edge_press_reaches_the_app_without_borderless_resize,
+                false,
+            ),
+            (
+                "event_coordinates_of_a_scaled_window",
+                event_coordinates_of_a_scaled_window,
+                false,
+            ),
+            (
+                "ime_is_not_enabled_without_keyboard_handlers",
+                ime_is_not_enabled_without_keyboard_handlers,
+                false,
+            ),
+            (
+                "timed_user_attention_is_cleared",
+                timed_user_attention_is_cleared,
+                false,
+            ),
+            (
+                "beep_works_with_and_without_a_surface",
+                beep_works_with_and_without_a_surface,
+                false,
+            ),
+            (
+                "setters_before_run_are_applied",
+                setters_before_run_are_applied,
+                false,
+            ),
+        ];
+        let has_window_manager = has_window_manager();
+        for (name, test, needs_window_manager) in tests {
+            if *needs_window_manager && !has_window_manager {
+                println!("test {name} ... ignored, no window manager");
+                continue;
+            }
+            test();
+            println!("test {name} ... ok");
+        }
+    }
+}
+
+fn main() {
+    #[cfg(any(x11_platform, wayland_platform))]
+    display::run();
+}